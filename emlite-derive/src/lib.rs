@@ -0,0 +1,705 @@
+//! Derive macros for converting Rust types to and from `emlite::Val`.
+//!
+//! Use them through `emlite` with its `derive` feature enabled. Three shapes
+//! are supported:
+//!
+//! - Structs with named fields map to plain JS objects, one property per
+//!   field. `#[emlite(rename = "...")]` changes a property name and
+//!   `#[emlite(skip)]` leaves a field out (it's `Default`ed when converting
+//!   back). Tuple structs map to arrays.
+//! - Newtypes, i.e. tuple structs with one field or structs marked
+//!   `#[emlite(transparent)]`, convert exactly like their field. Wrappers
+//!   around a `Val` can add `#[emlite(deref)]` to deref to it.
+//! - Enums without fields map to their variant names as strings, or to their
+//!   discriminants as numbers with `#[emlite(discriminant)]`. Enums with
+//!   fields need `#[emlite(tag = "...")]` and map to objects holding the tag
+//!   in that property next to the variant's fields.
+//!
+//! For enums, `#[derive(FromVal)]` implements `TryFromVal`, which fails with a
+//! `ConversionError` on an unrecognized tag. They only get `FromVal` itself
+//! when a unit variant is marked `#[emlite(other)]` to catch those tags.
+//!
+//! `JsCast` is derived separately, for `#[repr(transparent)]` wrappers of a JS
+//! class. `#[emlite(js_class = "...")]` names the class when it differs from
+//! the struct, and `#[emlite(extends = Parent)]` sets up upcasting to a parent
+//! wrapper; the first one is the `Deref` target.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::{format_ident, quote};
+use syn::spanned::Spanned;
+use syn::{
+    Attribute, Data, DeriveInput, Expr, ExprLit, ExprUnary, Fields, Ident, Lit, LitStr, Member,
+    Path, Type, UnOp, parse_macro_input,
+};
+
+/// Derives `From<T> for Val`, and so `emlite::IntoVal`, along with
+/// `emlite::VecElement`.
+#[proc_macro_derive(IntoVal, attributes(emlite))]
+pub fn derive_into_val(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    Model::parse(&input)
+        .map(|m| m.expand_into_val())
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Derives `emlite::FromVal`, plus `emlite::AsHandle` for wrappers around a
+/// `Val`. Enums get `emlite::TryFromVal`, and `FromVal` only if they have an
+/// `#[emlite(other)]` variant.
+#[proc_macro_derive(FromVal, attributes(emlite))]
+pub fn derive_from_val(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    Model::parse(&input)
+        .map(|m| m.expand_from_val())
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// Derives `emlite::JsCast` for a `#[repr(transparent)]` struct holding a
+/// `Val`, along with its conversions, `AsHandle`, `Deref` and upcasts.
+#[proc_macro_derive(JsCast, attributes(emlite))]
+pub fn derive_js_cast(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_js_cast(&input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+#[derive(Default)]
+struct Attrs {
+    rename: Option<String>,
+    skip: bool,
+    other: bool,
+    transparent: bool,
+    deref: bool,
+    discriminant: bool,
+    tag: Option<String>,
+    js_class: Option<String>,
+    extends: Vec<Path>,
+}
+
+impl Attrs {
+    fn parse(attrs: &[Attribute]) -> syn::Result<Attrs> {
+        let mut out = Attrs::default();
+        for attr in attrs.iter().filter(|a| a.path().is_ident("emlite")) {
+            attr.parse_nested_meta(|meta| {
+                let flag = |f: &mut bool| {
+                    *f = true;
+                    Ok(())
+                };
+                if meta.path.is_ident("rename") {
+                    out.rename = Some(meta.value()?.parse::<LitStr>()?.value());
+                    Ok(())
+                } else if meta.path.is_ident("tag") {
+                    out.tag = Some(meta.value()?.parse::<LitStr>()?.value());
+                    Ok(())
+                } else if meta.path.is_ident("js_class") {
+                    out.js_class = Some(meta.value()?.parse::<LitStr>()?.value());
+                    Ok(())
+                } else if meta.path.is_ident("extends") {
+                    out.extends.push(meta.value()?.parse::<Path>()?);
+                    Ok(())
+                } else if meta.path.is_ident("skip") {
+                    flag(&mut out.skip)
+                } else if meta.path.is_ident("other") {
+                    flag(&mut out.other)
+                } else if meta.path.is_ident("transparent") {
+                    flag(&mut out.transparent)
+                } else if meta.path.is_ident("deref") {
+                    flag(&mut out.deref)
+                } else if meta.path.is_ident("discriminant") {
+                    flag(&mut out.discriminant)
+                } else {
+                    Err(meta.error("unknown emlite attribute"))
+                }
+            })?;
+        }
+        Ok(out)
+    }
+}
+
+struct Field {
+    member: Member,
+    ty: Type,
+    name: String,
+    skip: bool,
+}
+
+impl Field {
+    fn parse(fields: &Fields) -> syn::Result<Vec<Field>> {
+        fields
+            .iter()
+            .enumerate()
+            .map(|(i, f)| {
+                let attrs = Attrs::parse(&f.attrs)?;
+                let member = match &f.ident {
+                    Some(ident) => Member::Named(ident.clone()),
+                    None => Member::Unnamed(i.into()),
+                };
+                let name = match (&attrs.rename, &f.ident) {
+                    (Some(name), _) => name.clone(),
+                    (None, Some(ident)) => ident.to_string().trim_start_matches("r#").into(),
+                    (None, None) => i.to_string(),
+                };
+                Ok(Field {
+                    member,
+                    ty: f.ty.clone(),
+                    name,
+                    skip: attrs.skip,
+                })
+            })
+            .collect()
+    }
+}
+
+enum Tag {
+    Str(String),
+    Num(i64),
+}
+
+impl Tag {
+    fn to_val(&self) -> TokenStream2 {
+        match self {
+            Tag::Str(s) => quote!(::emlite::Val::from(#s)),
+            Tag::Num(n) => {
+                let n = *n as f64;
+                quote!(::emlite::Val::from(#n))
+            }
+        }
+    }
+}
+
+struct Variant {
+    ident: Ident,
+    tag: Tag,
+    fields: Vec<Field>,
+    named: bool,
+    other: bool,
+}
+
+enum Shape {
+    Transparent(Box<Field>),
+    Object(Vec<Field>),
+    Array(Vec<Field>),
+    Unit,
+    Enum {
+        tag: Option<String>,
+        numeric: bool,
+        variants: Vec<Variant>,
+    },
+}
+
+struct Model<'a> {
+    input: &'a DeriveInput,
+    shape: Shape,
+    deref: bool,
+}
+
+fn discriminant(expr: &Expr) -> syn::Result<i64> {
+    let int = |lit: &Lit| match lit {
+        Lit::Int(i) => i.base10_parse::<i64>(),
+        _ => Err(syn::Error::new(
+            lit.span(),
+            "expected an integer discriminant",
+        )),
+    };
+    match expr {
+        Expr::Lit(ExprLit { lit, .. }) => int(lit),
+        Expr::Unary(ExprUnary {
+            op: UnOp::Neg(_),
+            expr,
+            ..
+        }) => match &**expr {
+            Expr::Lit(ExprLit { lit, .. }) => int(lit).map(|n| -n),
+            e => Err(syn::Error::new(
+                e.span(),
+                "expected an integer discriminant",
+            )),
+        },
+        e => Err(syn::Error::new(
+            e.span(),
+            "expected an integer discriminant",
+        )),
+    }
+}
+
+impl<'a> Model<'a> {
+    fn parse(input: &'a DeriveInput) -> syn::Result<Model<'a>> {
+        let attrs = Attrs::parse(&input.attrs)?;
+        let shape = match &input.data {
+            Data::Struct(s) => {
+                let fields = Field::parse(&s.fields)?;
+                let newtype = matches!(s.fields, Fields::Unnamed(_)) && fields.len() == 1;
+                match s.fields {
+                    _ if attrs.transparent || newtype => {
+                        let mut fields = fields.into_iter();
+                        match (fields.next(), fields.next()) {
+                            (Some(f), None) => Shape::Transparent(Box::new(f)),
+                            _ => {
+                                return Err(syn::Error::new_spanned(
+                                    &input.ident,
+                                    "transparent structs must have exactly one field",
+                                ));
+                            }
+                        }
+                    }
+                    Fields::Named(_) => Shape::Object(fields),
+                    Fields::Unnamed(_) => Shape::Array(fields),
+                    Fields::Unit => Shape::Unit,
+                }
+            }
+            Data::Enum(e) => {
+                let mut variants = Vec::new();
+                let mut next = 0i64;
+                for v in &e.variants {
+                    let vattrs = Attrs::parse(&v.attrs)?;
+                    if let Some((_, expr)) = &v.discriminant {
+                        next = discriminant(expr)?;
+                    }
+                    let tag = if attrs.discriminant {
+                        Tag::Num(next)
+                    } else {
+                        Tag::Str(vattrs.rename.unwrap_or_else(|| v.ident.to_string()))
+                    };
+                    next += 1;
+                    if !v.fields.is_empty() && attrs.tag.is_none() {
+                        return Err(syn::Error::new_spanned(
+                            v,
+                            "enums with fields need #[emlite(tag = \"...\")]",
+                        ));
+                    }
+                    if matches!(v.fields, Fields::Unnamed(_)) {
+                        return Err(syn::Error::new_spanned(
+                            v,
+                            "tuple variants are not supported, use named fields",
+                        ));
+                    }
+                    if vattrs.other && !v.fields.is_empty() {
+                        return Err(syn::Error::new_spanned(
+                            v,
+                            "#[emlite(other)] must be on a unit variant",
+                        ));
+                    }
+                    variants.push(Variant {
+                        ident: v.ident.clone(),
+                        tag,
+                        fields: Field::parse(&v.fields)?,
+                        named: matches!(v.fields, Fields::Named(_)),
+                        other: vattrs.other,
+                    });
+                }
+                Shape::Enum {
+                    tag: attrs.tag,
+                    numeric: attrs.discriminant,
+                    variants,
+                }
+            }
+            Data::Union(u) => {
+                return Err(syn::Error::new_spanned(
+                    u.union_token,
+                    "unions can't be converted to JS values",
+                ));
+            }
+        };
+        if attrs.deref && !matches!(shape, Shape::Transparent(_)) {
+            return Err(syn::Error::new_spanned(
+                &input.ident,
+                "#[emlite(deref)] is only supported on transparent structs",
+            ));
+        }
+        Ok(Model {
+            input,
+            shape,
+            deref: attrs.deref,
+        })
+    }
+
+    fn expand_into_val(&self) -> TokenStream2 {
+        let name = &self.input.ident;
+        let (impl_g, ty_g, where_clause) = self.input.generics.split_for_impl();
+        let body = match &self.shape {
+            Shape::Transparent(f) => {
+                let m = &f.member;
+                quote!(::emlite::Val::from(value.#m))
+            }
+            Shape::Object(fields) => {
+                let sets = fields.iter().filter(|f| !f.skip).map(|f| {
+                    let (m, key) = (&f.member, &f.name);
+                    quote!(obj.set(#key, ::emlite::Val::from(value.#m));)
+                });
+                quote! {
+                    let obj = ::emlite::Val::object();
+                    #(#sets)*
+                    obj
+                }
+            }
+            Shape::Array(fields) => {
+                // Skipped fields leave a hole so the others keep their index
+                let sets = fields
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, f)| !f.skip)
+                    .map(|(i, f)| {
+                        let (m, i) = (&f.member, i as u32);
+                        quote!(arr.set(#i, ::emlite::Val::from(value.#m));)
+                    });
+                quote! {
+                    let arr = ::emlite::Val::array();
+                    #(#sets)*
+                    arr
+                }
+            }
+            Shape::Unit => quote!(::emlite::Val::undefined()),
+            Shape::Enum { tag, variants, .. } => {
+                let arms = variants.iter().map(|v| {
+                    let ident = &v.ident;
+                    let tag_val = v.tag.to_val();
+                    let Some(tag) = tag else {
+                        return quote!(#name::#ident => #tag_val,);
+                    };
+                    let bindings = v
+                        .fields
+                        .iter()
+                        .enumerate()
+                        .map(|(i, f)| (&f.member, format_ident!("__field{}", i)))
+                        .collect::<Vec<_>>();
+                    let pattern = bindings.iter().map(|(m, b)| quote!(#m: #b));
+                    let sets = bindings.iter().zip(&v.fields).filter(|(_, f)| !f.skip).map(
+                        |((_, b), f)| {
+                            let key = &f.name;
+                            quote!(obj.set(#key, ::emlite::Val::from(#b));)
+                        },
+                    );
+                    let pattern = if v.named {
+                        quote!(#name::#ident { #(#pattern,)* })
+                    } else {
+                        quote!(#name::#ident)
+                    };
+                    quote! {
+                        #[allow(unused_variables)]
+                        #pattern => {
+                            let obj = ::emlite::Val::object();
+                            obj.set(#tag, #tag_val);
+                            #(#sets)*
+                            obj
+                        }
+                    }
+                });
+                quote! {
+                    match value {
+                        #(#arms)*
+                    }
+                }
+            }
+        };
+        let by_ref = match &self.shape {
+            // Wrappers around a Val can also be passed by reference, like Val itself
+            Shape::Transparent(f) if is_val(&f.ty) => {
+                let m = &f.member;
+                quote! {
+                    impl #impl_g ::core::convert::From<&#name #ty_g> for ::emlite::Val #where_clause {
+                        fn from(value: &#name #ty_g) -> ::emlite::Val {
+                            ::core::clone::Clone::clone(&value.#m)
+                        }
+                    }
+                    impl #impl_g ::emlite::VecElement for &#name #ty_g #where_clause {}
+                }
+            }
+            _ => quote!(),
+        };
+        quote! {
+            impl #impl_g ::core::convert::From<#name #ty_g> for ::emlite::Val #where_clause {
+                fn from(value: #name #ty_g) -> ::emlite::Val {
+                    #body
+                }
+            }
+            impl #impl_g ::emlite::VecElement for #name #ty_g #where_clause {}
+            #by_ref
+        }
+    }
+
+    fn expand_from_val(&self) -> TokenStream2 {
+        let name = &self.input.ident;
+        let (impl_g, ty_g, where_clause) = self.input.generics.split_for_impl();
+        let get = |key: TokenStream2, f: &Field| {
+            let (m, ty) = (&f.member, &f.ty);
+            if f.skip {
+                quote!(#m: ::core::default::Default::default())
+            } else {
+                quote!(#m: v.get(#key).as_::<#ty>())
+            }
+        };
+        let (from_val, take_ownership) = match &self.shape {
+            Shape::Transparent(f) => {
+                let (m, ty) = (&f.member, &f.ty);
+                (
+                    quote!(#name { #m: <#ty as ::emlite::FromVal>::from_val(v) }),
+                    quote! {
+                        fn take_ownership(h: ::emlite::common::Handle) -> Self {
+                            #name { #m: <#ty as ::emlite::FromVal>::take_ownership(h) }
+                        }
+                    },
+                )
+            }
+            Shape::Object(fields) => {
+                let inits = fields.iter().map(|f| {
+                    let key = &f.name;
+                    get(quote!(#key), f)
+                });
+                (quote!(#name { #(#inits,)* }), quote!())
+            }
+            Shape::Array(fields) => {
+                let inits = fields.iter().enumerate().map(|(i, f)| {
+                    let i = i as u32;
+                    get(quote!(#i), f)
+                });
+                (quote!(#name { #(#inits,)* }), quote!())
+            }
+            Shape::Unit => (quote!(#name), quote!()),
+            Shape::Enum {
+                tag,
+                numeric,
+                variants,
+            } => {
+                let read_tag = match tag {
+                    Some(tag) => quote!(v.get(#tag)),
+                    None => quote!(::core::clone::Clone::clone(v)),
+                };
+                let construct = |var: &Variant| {
+                    let ident = &var.ident;
+                    if var.named {
+                        let inits = var.fields.iter().map(|f| {
+                            let key = &f.name;
+                            get(quote!(#key), f)
+                        });
+                        quote!(#name::#ident { #(#inits,)* })
+                    } else {
+                        quote!(#name::#ident)
+                    }
+                };
+                let expected = LitStr::new(&name.to_string(), name.span());
+                let fallback = match variants.iter().find(|v| v.other) {
+                    Some(v) => {
+                        let ident = &v.ident;
+                        quote!(::core::result::Result::Ok(#name::#ident))
+                    }
+                    None => quote! {
+                        ::core::result::Result::Err(::emlite::ConversionError::new(#expected, &tag_val))
+                    },
+                };
+                let body = if *numeric {
+                    let arms = variants.iter().map(|var| {
+                        let Tag::Num(n) = var.tag else { unreachable!() };
+                        let n = n as f64;
+                        let value = construct(var);
+                        quote!(if tag == #n { return ::core::result::Result::Ok(#value); })
+                    });
+                    quote! {
+                        let tag_val = #read_tag;
+                        let tag = tag_val.as_::<f64>();
+                        #(#arms)*
+                        #fallback
+                    }
+                } else {
+                    let arms = variants.iter().map(|var| {
+                        let Tag::Str(s) = &var.tag else {
+                            unreachable!()
+                        };
+                        let value = construct(var);
+                        quote!(::core::option::Option::Some(#s) => ::core::result::Result::Ok(#value),)
+                    });
+                    quote! {
+                        let tag_val = #read_tag;
+                        let tag = tag_val.as_::<::core::option::Option<::emlite::__private::String>>();
+                        match tag.as_deref() {
+                            #(#arms)*
+                            _ => #fallback,
+                        }
+                    }
+                };
+                // Unknown tags are an error, so only enums with a variant to
+                // catch them convert infallibly
+                let from_val = variants.iter().find(|v| v.other).map(|v| {
+                    let ident = &v.ident;
+                    quote! {
+                        impl #impl_g ::emlite::FromVal for #name #ty_g #where_clause {
+                            fn from_val(v: &::emlite::Val) -> Self {
+                                <Self as ::emlite::TryFromVal>::try_from_val(v).unwrap_or(#name::#ident)
+                            }
+                        }
+                    }
+                });
+                return quote! {
+                    impl #impl_g ::emlite::TryFromVal for #name #ty_g #where_clause {
+                        fn try_from_val(
+                            v: &::emlite::Val,
+                        ) -> ::core::result::Result<Self, ::emlite::ConversionError> {
+                            #body
+                        }
+                    }
+                    #from_val
+                };
+            }
+        };
+        // Only wrappers around a Val have a handle of their own; plain data is
+        // copied out of JS and holds none
+        let as_handle = match &self.shape {
+            Shape::Transparent(f) if is_val(&f.ty) => {
+                let m = &f.member;
+                quote! {
+                    impl #impl_g ::emlite::AsHandle for #name #ty_g #where_clause {
+                        fn as_handle(&self) -> ::emlite::common::Handle {
+                            ::emlite::AsHandle::as_handle(&self.#m)
+                        }
+                    }
+                }
+            }
+            _ => quote!(),
+        };
+        let deref = match (&self.shape, self.deref) {
+            (Shape::Transparent(f), true) => {
+                let (m, ty) = (&f.member, &f.ty);
+                quote! {
+                    impl #impl_g ::core::ops::Deref for #name #ty_g #where_clause {
+                        type Target = #ty;
+                        fn deref(&self) -> &#ty {
+                            &self.#m
+                        }
+                    }
+                    impl #impl_g ::core::ops::DerefMut for #name #ty_g #where_clause {
+                        fn deref_mut(&mut self) -> &mut #ty {
+                            &mut self.#m
+                        }
+                    }
+                }
+            }
+            _ => quote!(),
+        };
+        quote! {
+            impl #impl_g ::emlite::FromVal for #name #ty_g #where_clause {
+                fn from_val(v: &::emlite::Val) -> Self {
+                    #from_val
+                }
+                #take_ownership
+            }
+            #as_handle
+            #deref
+        }
+    }
+}
+
+fn expand_js_cast(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let attrs = Attrs::parse(&input.attrs)?;
+    let field = match &input.data {
+        Data::Struct(s) if s.fields.len() == 1 => Field::parse(&s.fields)?.remove(0),
+        _ => {
+            return Err(syn::Error::new_spanned(
+                name,
+                "JsCast can only be derived for structs with a single Val field",
+            ));
+        }
+    };
+    if !is_val(&field.ty) {
+        return Err(syn::Error::new_spanned(&field.ty, "expected a Val field"));
+    }
+    // `unchecked_from_val_ref` casts a &Val to &Self, which relies on it
+    let transparent = input.attrs.iter().any(|a| {
+        a.path().is_ident("repr")
+            && a.parse_args::<Ident>()
+                .is_ok_and(|repr| repr == "transparent")
+    });
+    if !transparent {
+        return Err(syn::Error::new_spanned(
+            name,
+            "JsCast needs #[repr(transparent)]",
+        ));
+    }
+    let m = &field.member;
+    let js_class = attrs.js_class.unwrap_or_else(|| name.to_string());
+    let (impl_g, ty_g, where_clause) = input.generics.split_for_impl();
+    let deref = match attrs.extends.first() {
+        Some(parent) => quote! {
+            impl #impl_g ::core::ops::Deref for #name #ty_g #where_clause {
+                type Target = #parent;
+                fn deref(&self) -> &#parent {
+                    ::emlite::JsCast::unchecked_ref(&self.#m)
+                }
+            }
+        },
+        None => quote! {
+            impl #impl_g ::core::ops::Deref for #name #ty_g #where_clause {
+                type Target = ::emlite::Val;
+                fn deref(&self) -> &::emlite::Val {
+                    &self.#m
+                }
+            }
+        },
+    };
+    let upcasts = attrs.extends.iter().map(|parent| {
+        quote! {
+            impl #impl_g ::core::convert::AsRef<#parent> for #name #ty_g #where_clause {
+                fn as_ref(&self) -> &#parent {
+                    ::emlite::JsCast::unchecked_ref(&self.#m)
+                }
+            }
+            impl #impl_g ::core::convert::From<#name #ty_g> for #parent #where_clause {
+                fn from(value: #name #ty_g) -> #parent {
+                    ::emlite::JsCast::unchecked_into(value.#m)
+                }
+            }
+        }
+    });
+    Ok(quote! {
+        impl #impl_g ::emlite::JsCast for #name #ty_g #where_clause {
+            fn instanceof(v: &::emlite::Val) -> bool {
+                static CTOR: ::emlite::__private::CachedHandle =
+                    ::emlite::__private::CachedHandle::new();
+                ::emlite::__private::instanceof_global(v, &CTOR, #js_class)
+            }
+            fn unchecked_from_val(v: ::emlite::Val) -> Self {
+                #name { #m: v }
+            }
+            fn unchecked_from_val_ref(v: &::emlite::Val) -> &Self {
+                unsafe { &*(v as *const ::emlite::Val as *const Self) }
+            }
+        }
+        impl #impl_g ::core::convert::AsRef<::emlite::Val> for #name #ty_g #where_clause {
+            fn as_ref(&self) -> &::emlite::Val {
+                &self.#m
+            }
+        }
+        impl #impl_g ::core::convert::From<#name #ty_g> for ::emlite::Val #where_clause {
+            fn from(value: #name #ty_g) -> ::emlite::Val {
+                value.#m
+            }
+        }
+        impl #impl_g ::core::convert::From<&#name #ty_g> for ::emlite::Val #where_clause {
+            fn from(value: &#name #ty_g) -> ::emlite::Val {
+                ::core::clone::Clone::clone(&value.#m)
+            }
+        }
+        impl #impl_g ::emlite::VecElement for #name #ty_g #where_clause {}
+        impl #impl_g ::emlite::VecElement for &#name #ty_g #where_clause {}
+        impl #impl_g ::emlite::AsHandle for #name #ty_g #where_clause {
+            fn as_handle(&self) -> ::emlite::common::Handle {
+                ::emlite::AsHandle::as_handle(&self.#m)
+            }
+        }
+        impl #impl_g ::emlite::FromVal for #name #ty_g #where_clause {
+            fn from_val(v: &::emlite::Val) -> Self {
+                #name { #m: ::core::clone::Clone::clone(v) }
+            }
+        }
+        #deref
+        #(#upcasts)*
+    })
+}
+
+fn is_val(ty: &Type) -> bool {
+    match ty {
+        Type::Path(p) => p.path.segments.last().is_some_and(|s| s.ident == "Val"),
+        _ => false,
+    }
+}
@@ -10,14 +10,14 @@ fn main() {
         "addEventListener",
         &argv![
             "click",
-            Val::make_fn(|ev| {
+            Closure::wrap(|ev: Val| {
                 let console = Console::get();
                 console.call("clear", &[]);
-                console.log(&[ev[0].get("clientX")]);
-                println!("client x: {}", ev[0].get("clientX").as_::<i32>());
+                console.log(&[ev.get("clientX")]);
+                println!("client x: {}", ev.get("clientX").as_::<i32>());
                 println!("hello from Rust");
-                Val::undefined()
             })
+            .forget()
         ],
     );
     body.call("appendChild", &argv![elem]);
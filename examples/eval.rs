@@ -1,15 +1,12 @@
-use emlite::{Console, eval};
+use emlite::{Console, Val, eval, js};
 
 fn main() {
     emlite::init();
     let con = Console::get();
-    let ret = eval!(
-        r#"
-        let con = EMLITE_VALMAP.toValue({});
-        con.log("Hello");
-        6
-    "#,
-        con.as_handle()
-    );
-    con.log(&[ret]);
+    // Compiled once, with values passed as arguments
+    let ret: i32 =
+        js!(|con, greeting| "con.log(greeting); return 6").call(&[con.into(), "Hello".into()]);
+    // eval! splices its arguments into the source, recompiling it every time
+    let twice = eval!("{} * 2", ret);
+    Console::get().log(&[Val::from(ret), twice]);
 }
@@ -0,0 +1,66 @@
+use crate::Val;
+use crate::common::Handle;
+use core::mem::ManuallyDrop;
+
+/// A handle created on first use and kept alive for the rest of the program,
+/// for caching JS values in `static`s.
+///
+/// The mock host is per thread, so there the handle is cached per thread and
+/// forgotten by `emlite::init()`.
+pub struct CachedHandle {
+    #[cfg(not(all(not(target_arch = "wasm32"), feature = "mock-host")))]
+    handle: core::sync::atomic::AtomicU32,
+    // Gives every static its own address to key the mock's cache by
+    #[cfg(all(not(target_arch = "wasm32"), feature = "mock-host"))]
+    _unique: u8,
+}
+
+impl CachedHandle {
+    pub const fn new() -> Self {
+        CachedHandle {
+            #[cfg(not(all(not(target_arch = "wasm32"), feature = "mock-host")))]
+            handle: core::sync::atomic::AtomicU32::new(0),
+            #[cfg(all(not(target_arch = "wasm32"), feature = "mock-host"))]
+            _unique: 0,
+        }
+    }
+
+    /// Returns the cached value, creating it with `init` the first time. The
+    /// value is borrowed from the cache, so dropping it is a no-op.
+    pub fn get_or_init(&'static self, init: impl FnOnce() -> Val) -> ManuallyDrop<Val> {
+        match self.get_or_try_init(|| Ok::<_, core::convert::Infallible>(init())) {
+            Ok(v) => v,
+            Err(e) => match e {},
+        }
+    }
+
+    /// Like [`CachedHandle::get_or_init`], but caches nothing when `init`
+    /// fails, so the next call tries again.
+    pub fn get_or_try_init<E>(
+        &'static self,
+        init: impl FnOnce() -> Result<Val, E>,
+    ) -> Result<ManuallyDrop<Val>, E> {
+        let leak = || init().map(|v| ManuallyDrop::new(v).as_handle());
+        #[cfg(not(all(not(target_arch = "wasm32"), feature = "mock-host")))]
+        let h = {
+            use core::sync::atomic::Ordering;
+            match self.handle.load(Ordering::Relaxed) {
+                0 => {
+                    let h: Handle = leak()?;
+                    self.handle.store(h, Ordering::Relaxed);
+                    h
+                }
+                h => h,
+            }
+        };
+        #[cfg(all(not(target_arch = "wasm32"), feature = "mock-host"))]
+        let h: Handle = crate::mockenv::cached_handle(self as *const Self as usize, leak)?;
+        Ok(ManuallyDrop::new(Val::take_ownership(h)))
+    }
+}
+
+impl Default for CachedHandle {
+    fn default() -> Self {
+        CachedHandle::new()
+    }
+}
@@ -0,0 +1,109 @@
+//! Checked casts between `Val` and typed wrappers around it.
+//!
+//! Wrappers for JS classes can form a hierarchy: each derefs to its parent
+//! class, down to `Val`, so methods of every ancestor are reachable and
+//! upcasting is free. Downcasting goes through [`JsCast::dyn_into`] or
+//! [`JsCast::dyn_ref`], which check the value with `instanceof`.
+
+use crate::Val;
+use crate::cache::CachedHandle;
+use core::mem::ManuallyDrop;
+
+/// A typed wrapper around a `Val`, e.g. for a JS class.
+///
+/// `#[derive(JsCast)]` implements it for `#[repr(transparent)]` structs
+/// holding a single `Val`.
+pub trait JsCast: AsRef<Val> + Into<Val> {
+    /// Checks whether `v` is an instance of the type's JS class
+    fn instanceof(v: &Val) -> bool;
+
+    /// Checks whether `v` has this type, as [`JsCast::dyn_into`] and
+    /// [`JsCast::dyn_ref`] do. Defaults to [`JsCast::instanceof`], which
+    /// types that aren't classes can refine.
+    fn has_type(v: &Val) -> bool {
+        Self::instanceof(v)
+    }
+
+    /// Wraps `v` without checking its type
+    fn unchecked_from_val(v: Val) -> Self;
+
+    /// Views `v` as this type without checking it
+    fn unchecked_from_val_ref(v: &Val) -> &Self;
+
+    /// Checks whether the value is an instance of `T`'s JS class
+    fn is_instance_of<T: JsCast>(&self) -> bool {
+        T::instanceof(self.as_ref())
+    }
+
+    /// Converts to `T` if the value has that type, or gives it back otherwise
+    fn dyn_into<T: JsCast>(self) -> Result<T, Self>
+    where
+        Self: Sized,
+    {
+        let v = self.into();
+        if T::has_type(&v) {
+            Ok(T::unchecked_from_val(v))
+        } else {
+            Err(Self::unchecked_from_val(v))
+        }
+    }
+
+    /// Views the value as `T` if it has that type
+    fn dyn_ref<T: JsCast>(&self) -> Option<&T> {
+        if T::has_type(self.as_ref()) {
+            Some(T::unchecked_from_val_ref(self.as_ref()))
+        } else {
+            None
+        }
+    }
+
+    /// Converts to `T` without checking the value's type
+    fn unchecked_into<T: JsCast>(self) -> T
+    where
+        Self: Sized,
+    {
+        T::unchecked_from_val(self.into())
+    }
+
+    /// Views the value as `T` without checking its type
+    fn unchecked_ref<T: JsCast>(&self) -> &T {
+        T::unchecked_from_val_ref(self.as_ref())
+    }
+}
+
+impl JsCast for Val {
+    fn instanceof(_v: &Val) -> bool {
+        true
+    }
+
+    fn unchecked_from_val(v: Val) -> Self {
+        v
+    }
+
+    fn unchecked_from_val_ref(v: &Val) -> &Self {
+        v
+    }
+}
+
+/// Returns the global `name`, kept in `ctor` once it exists. While it's
+/// missing, e.g. until the script defining a class loads, it's looked up
+/// again on every call.
+#[doc(hidden)]
+pub fn cached_global(ctor: &'static CachedHandle, name: &str) -> Option<ManuallyDrop<Val>> {
+    ctor.get_or_try_init(|| {
+        let v = Val::global(name);
+        if v.is_undefined() || v.is_null() {
+            Err(())
+        } else {
+            Ok(v)
+        }
+    })
+    .ok()
+}
+
+/// Checks `v` against the global constructor `name`, looked up once it
+/// exists and kept in `ctor`. Missing constructors have no instances.
+#[doc(hidden)]
+pub fn instanceof_global(v: &Val, ctor: &'static CachedHandle, name: &str) -> bool {
+    cached_global(ctor, name).is_some_and(|ctor| v.instanceof((*ctor).clone()))
+}
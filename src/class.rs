@@ -0,0 +1,234 @@
+//! Declarative bindings for JS classes, see [`bind_class!`](crate::bind_class).
+
+/// Declares a typed wrapper for a JS class, generating its constructor,
+/// methods, accessors and static methods.
+///
+/// ```ignore
+/// emlite::bind_class! {
+///     /// A point, from `class Point { ... }` in JS
+///     pub Point;
+///     fn new(x: f64, y: f64);
+///     fn translate(&self, dx: f64, dy: f64);
+///     #[js_name = "toString"]
+///     fn to_js_string(&self) -> String;
+///     #[getter]
+///     fn x(&self) -> f64;
+///     #[setter]
+///     fn set_x(&self, x: f64);
+///     static fn origin() -> Self;
+/// }
+/// ```
+///
+/// The class is looked up on `globalThis` by the wrapper's name, or by the
+/// name given as `pub Point = "JsPoint";`, once, and then cached. `new`
+/// constructs an instance, `&self` methods call the JS method of the same
+/// name (or `js_name`) and static methods the class's own. `#[getter]` and
+/// `#[setter]` read and write a property instead, named after the method
+/// minus any `set_` prefix unless given as `#[setter = "x"]`. Arguments are
+/// converted with [`IntoVal`](crate::IntoVal) and results with
+/// [`FromVal`](crate::FromVal), and methods without a return type return
+/// `()`.
+///
+/// The wrapper is a [`JsCast`](crate::JsCast) type, downcast to with an
+/// `instanceof` check, which derefs to `Val`.
+#[macro_export]
+macro_rules! bind_class {
+    (@js_name $f:ident) => {
+        ::core::stringify!($f)
+    };
+    (@js_name $f:ident $js:literal) => {
+        $js
+    };
+    (@setter_name $f:ident) => {{
+        let name = ::core::stringify!($f);
+        name.strip_prefix("set_").unwrap_or(name)
+    }};
+    (@setter_name $f:ident $js:literal) => {
+        $js
+    };
+    (@ret) => { () };
+    (@ret $ret:ty) => { $ret };
+
+    (@members $name:ident;) => {};
+    (
+        @members $name:ident;
+        $(#[doc = $doc:literal])*
+        #[getter $(= $js:literal)?]
+        fn $f:ident(&self) -> $ret:ty;
+        $($rest:tt)*
+    ) => {
+        impl $name {
+            $(#[doc = $doc])*
+            pub fn $f(&self) -> $ret {
+                self.0.get($crate::bind_class!(@js_name $f $($js)?)).as_::<$ret>()
+            }
+        }
+        $crate::bind_class!(@members $name; $($rest)*);
+    };
+    (
+        @members $name:ident;
+        $(#[doc = $doc:literal])*
+        #[setter $(= $js:literal)?]
+        fn $f:ident(&self, $v:ident: $ty:ty $(,)?);
+        $($rest:tt)*
+    ) => {
+        impl $name {
+            $(#[doc = $doc])*
+            pub fn $f(&self, $v: $ty) {
+                self.0.set(
+                    $crate::bind_class!(@setter_name $f $($js)?),
+                    $crate::IntoVal::into_val($v),
+                )
+            }
+        }
+        $crate::bind_class!(@members $name; $($rest)*);
+    };
+    (
+        @members $name:ident;
+        $(#[doc = $doc:literal])*
+        fn new($($arg:ident: $ty:ty),* $(,)?);
+        $($rest:tt)*
+    ) => {
+        impl $name {
+            $(#[doc = $doc])*
+            #[allow(clippy::too_many_arguments)]
+            pub fn new($($arg: $ty),*) -> Self {
+                $name(Self::__class().new(&[$($crate::IntoVal::into_val($arg)),*]))
+            }
+        }
+        $crate::bind_class!(@members $name; $($rest)*);
+    };
+    (
+        @members $name:ident;
+        $(#[doc = $doc:literal])*
+        $(#[js_name = $js:literal])?
+        fn $f:ident(&self $(, $arg:ident: $ty:ty)* $(,)?) $(-> $ret:ty)?;
+        $($rest:tt)*
+    ) => {
+        impl $name {
+            $(#[doc = $doc])*
+            #[allow(clippy::too_many_arguments, clippy::unused_unit)]
+            pub fn $f(&self $(, $arg: $ty)*) -> $crate::bind_class!(@ret $($ret)?) {
+                self.0
+                    .call(
+                        $crate::bind_class!(@js_name $f $($js)?),
+                        &[$($crate::IntoVal::into_val($arg)),*],
+                    )
+                    .as_()
+            }
+        }
+        $crate::bind_class!(@members $name; $($rest)*);
+    };
+    (
+        @members $name:ident;
+        $(#[doc = $doc:literal])*
+        $(#[js_name = $js:literal])?
+        static fn $f:ident($($arg:ident: $ty:ty),* $(,)?) $(-> $ret:ty)?;
+        $($rest:tt)*
+    ) => {
+        impl $name {
+            $(#[doc = $doc])*
+            #[allow(clippy::too_many_arguments, clippy::unused_unit)]
+            pub fn $f($($arg: $ty),*) -> $crate::bind_class!(@ret $($ret)?) {
+                Self::__class()
+                    .call(
+                        $crate::bind_class!(@js_name $f $($js)?),
+                        &[$($crate::IntoVal::into_val($arg)),*],
+                    )
+                    .as_()
+            }
+        }
+        $crate::bind_class!(@members $name; $($rest)*);
+    };
+
+    (
+        $(#[$meta:meta])*
+        $vis:vis $name:ident $(= $js:literal)?;
+        $($members:tt)*
+    ) => {
+        $(#[$meta])*
+        #[derive(Clone, Debug)]
+        #[repr(transparent)]
+        $vis struct $name($crate::Val);
+
+        impl $name {
+            fn __cache() -> &'static $crate::__private::CachedHandle {
+                static CLASS: $crate::__private::CachedHandle = $crate::__private::CachedHandle::new();
+                &CLASS
+            }
+
+            fn __class() -> ::core::mem::ManuallyDrop<$crate::Val> {
+                $crate::__private::cached_global(
+                    Self::__cache(),
+                    $crate::bind_class!(@js_name $name $($js)?),
+                )
+                .unwrap_or(::core::mem::ManuallyDrop::new($crate::Val::undefined()))
+            }
+
+            /// Returns the JS class
+            #[allow(dead_code)]
+            pub fn constructor() -> $crate::Val {
+                $crate::Val::clone(&Self::__class())
+            }
+        }
+
+        impl $crate::JsCast for $name {
+            fn instanceof(v: &$crate::Val) -> bool {
+                $crate::__private::instanceof_global(
+                    v,
+                    Self::__cache(),
+                    $crate::bind_class!(@js_name $name $($js)?),
+                )
+            }
+            fn unchecked_from_val(v: $crate::Val) -> Self {
+                $name(v)
+            }
+            fn unchecked_from_val_ref(v: &$crate::Val) -> &Self {
+                unsafe { &*(v as *const $crate::Val as *const Self) }
+            }
+        }
+
+        impl ::core::convert::AsRef<$crate::Val> for $name {
+            fn as_ref(&self) -> &$crate::Val {
+                &self.0
+            }
+        }
+
+        impl ::core::ops::Deref for $name {
+            type Target = $crate::Val;
+            fn deref(&self) -> &$crate::Val {
+                &self.0
+            }
+        }
+
+        impl ::core::convert::From<$name> for $crate::Val {
+            fn from(v: $name) -> $crate::Val {
+                v.0
+            }
+        }
+
+        impl ::core::convert::From<&$name> for $crate::Val {
+            fn from(v: &$name) -> $crate::Val {
+                $crate::Val::clone(&v.0)
+            }
+        }
+
+        impl $crate::VecElement for $name {}
+
+        impl $crate::VecElement for &$name {}
+
+        impl $crate::AsHandle for $name {
+            fn as_handle(&self) -> $crate::common::Handle {
+                self.0.as_handle()
+            }
+        }
+
+        impl $crate::FromVal for $name {
+            fn from_val(v: &$crate::Val) -> Self {
+                $name($crate::Val::clone(v))
+            }
+        }
+
+        $crate::bind_class!(@members $name; $($members)*);
+    };
+}
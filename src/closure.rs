@@ -0,0 +1,268 @@
+use crate::common::Handle;
+use crate::sys::*;
+use crate::{EmlitePredefHandles, IntoVal, TryFromVal, Val};
+use alloc::boxed::Box;
+use alloc::rc::Rc;
+use alloc::vec::Vec;
+use core::cell::{Cell, RefCell};
+use core::marker::PhantomData;
+use core::ops::Deref;
+
+/// A closure body, returning either a value or an exception to throw
+#[allow(clippy::type_complexity)]
+enum Body {
+    Mut(Box<dyn FnMut(&[Val]) -> Result<Val, Val>>),
+    Once(Box<dyn FnOnce(&[Val]) -> Result<Val, Val>>),
+}
+
+/// State shared between a [`Closure`] and the JS function created for it.
+///
+/// The JS function holds one strong reference, encoded as a pointer in its
+/// `data` handle, which is released when the host finalizes the function.
+/// The closure body itself is owned by the Rust side and dropped with it.
+struct Inner {
+    body: RefCell<Option<Body>>,
+    dropped: Cell<bool>,
+}
+
+impl Inner {
+    fn call(&self, args: &[Val]) -> Option<Result<Val, Val>> {
+        // Take the body out while it runs so re-entrant calls and drops from
+        // inside the closure never alias it.
+        let body = self.body.borrow_mut().take()?;
+        match body {
+            Body::Mut(mut f) => {
+                let ret = f(args);
+                if !self.dropped.get() {
+                    *self.body.borrow_mut() = Some(Body::Mut(f));
+                }
+                Some(ret)
+            }
+            Body::Once(f) => Some(f(args)),
+        }
+    }
+}
+
+/// A Rust closure exposed to JS as a function, which owns the closure body.
+///
+/// Dropping a `Closure` frees the body; calling the JS function afterwards
+/// throws an `Error` instead of touching freed memory. Use [`Closure::forget`]
+/// to hand ownership to JS, e.g. for event listeners that live as long as the
+/// page. Either way the host releases its side of the bookkeeping when it
+/// finalizes the function, by calling it with a null `argv` handle.
+pub struct Closure<F: ?Sized> {
+    val: Val,
+    inner: Rc<Inner>,
+    _marker: PhantomData<Box<F>>,
+}
+
+impl Closure<dyn FnMut(&[Val]) -> Val> {
+    /// Creates a JS function backed by `f`
+    pub fn new<T>(f: T) -> Self
+    where
+        T: FnMut(&[Val]) -> Val + 'static,
+    {
+        let mut f = f;
+        Closure::from_body(Body::Mut(Box::new(move |args| Ok(f(args)))))
+    }
+}
+
+impl Closure<dyn FnOnce(&[Val]) -> Val> {
+    /// Creates a JS function backed by `f` which frees itself after its first call.
+    /// Later calls throw an `Error`.
+    pub fn once<T>(f: T) -> Self
+    where
+        T: FnOnce(&[Val]) -> Val + 'static,
+    {
+        Closure::from_body(Body::Once(Box::new(move |args| Ok(f(args)))))
+    }
+}
+
+impl<F: ?Sized> Closure<F> {
+    /// Creates a JS function backed by a closure with typed arguments and
+    /// return value, of up to 8 arguments:
+    ///
+    /// ```ignore
+    /// let greet = Closure::wrap(|times: u32, name: String| -> Result<String, JsError> {
+    ///     if times > 3 {
+    ///         return Err(JsError::with_constructor("RangeError", "too many"));
+    ///     }
+    ///     Ok(name.repeat(times as usize))
+    /// });
+    /// ```
+    ///
+    /// Arguments are converted with [`TryFromVal`], missing ones reading as
+    /// `undefined`; if one doesn't convert, the call throws a `TypeError`
+    /// without running the closure. The return value is converted with
+    /// [`IntoVal`], and `Err` returns are thrown.
+    pub fn wrap<T, Args>(f: T) -> Self
+    where
+        T: IntoClosure<Args, Signature = F>,
+    {
+        Closure::from_body(Body::Mut(f.into_body()))
+    }
+
+    fn from_body(body: Body) -> Self {
+        let inner = Rc::new(Inner {
+            body: RefCell::new(Some(body)),
+            dropped: Cell::new(false),
+        });
+        // The JS function's reference, reclaimed in `shim` on finalization
+        let data = Val::from(Rc::into_raw(inner.clone()) as usize);
+        let val = Val::make_fn_raw(shim, data.as_handle());
+        Closure {
+            val,
+            inner,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Returns the JS function
+    pub fn as_val(&self) -> &Val {
+        &self.val
+    }
+
+    /// Gives up Rust-side ownership, leaving the closure alive for as long as
+    /// the JS function is, and returns the function
+    pub fn forget(self) -> Val {
+        let this = core::mem::ManuallyDrop::new(self);
+        // Release our reference without dropping the body
+        unsafe {
+            drop(core::ptr::read(&this.inner));
+            core::ptr::read(&this.val)
+        }
+    }
+}
+
+impl<F: ?Sized> Drop for Closure<F> {
+    fn drop(&mut self) {
+        self.inner.dropped.set(true);
+        let body = self.inner.body.borrow_mut().take();
+        drop(body);
+    }
+}
+
+impl<F: ?Sized> Deref for Closure<F> {
+    type Target = Val;
+
+    fn deref(&self) -> &Self::Target {
+        &self.val
+    }
+}
+
+impl<F: ?Sized> AsRef<Val> for Closure<F> {
+    fn as_ref(&self) -> &Val {
+        &self.val
+    }
+}
+
+impl<F: ?Sized> From<&Closure<F>> for Val {
+    fn from(c: &Closure<F>) -> Self {
+        c.val.clone()
+    }
+}
+
+impl<F: ?Sized> crate::VecElement for &Closure<F> {}
+
+impl<F: ?Sized> core::fmt::Debug for Closure<F> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Closure").field("val", &self.val).finish()
+    }
+}
+
+extern "C" fn shim(args: Handle, data: Handle) -> Handle {
+    // data is a handle to a BigInt that encodes the pointer to `Inner`
+    let ptr = unsafe { emlite_val_get_value_biguint(data) } as usize as *const Inner;
+    if ptr.is_null() {
+        return EmlitePredefHandles::Undefined as Handle;
+    }
+    if args == EmlitePredefHandles::Null as Handle {
+        // Finalize: the host is done with the function
+        drop(unsafe { Rc::from_raw(ptr) });
+        return EmlitePredefHandles::Undefined as Handle;
+    }
+    let inner = unsafe { &*ptr };
+    let vals: Vec<Val> = Val::take_ownership(args).to_vec();
+    let ret = inner.call(&vals);
+    drop(vals);
+    match ret {
+        // The host takes ownership of the returned handle. Handing out
+        // `ret.as_handle()` and then dropping `ret` would release it first,
+        // leaving the host to read a freed (or reused) handle.
+        Some(Ok(ret)) => ret.into_handle(),
+        Some(Err(e)) => throw(e),
+        None => throw(
+            Val::global("Error")
+                .new(&["closure invoked recursively or after being dropped".into()]),
+        ),
+    }
+}
+
+/// Throws `e` to the JS caller of the callback, handing over its reference
+fn throw(e: Val) -> Handle {
+    unsafe { emlite_val_throw_from_callback_unified(e.into_handle()) }
+}
+
+/// The return value of a [`Closure::wrap`] closure: any [`IntoVal`] value, or
+/// a `Result` whose `Err` is thrown.
+pub trait ClosureReturn {
+    /// Converts into the value to return, or the exception to throw
+    fn into_js_result(self) -> Result<Val, Val>;
+}
+
+impl<T: IntoVal> ClosureReturn for T {
+    fn into_js_result(self) -> Result<Val, Val> {
+        Ok(self.into_val())
+    }
+}
+
+impl<T: IntoVal, E: Into<Val>> ClosureReturn for Result<T, E> {
+    fn into_js_result(self) -> Result<Val, Val> {
+        self.map(IntoVal::into_val).map_err(Into::into)
+    }
+}
+
+/// A Rust closure [`Closure::wrap`] accepts, taking `Args` as a tuple of
+/// [`TryFromVal`] arguments and returning a [`ClosureReturn`].
+pub trait IntoClosure<Args>: 'static {
+    /// The `dyn FnMut` type of the closure, e.g. `dyn FnMut(f64) -> bool`
+    type Signature: ?Sized;
+    #[doc(hidden)]
+    #[allow(clippy::type_complexity)]
+    fn into_body(self) -> Box<dyn FnMut(&[Val]) -> Result<Val, Val>>;
+}
+
+/// Converts argument `i`, or reports why it can't be to JS as a `TypeError`
+fn arg<T: TryFromVal>(args: &[Val], i: usize) -> Result<T, Val> {
+    let undefined = Val::undefined();
+    T::try_from_val(args.get(i).unwrap_or(&undefined))
+        .map_err(|e| Val::global("TypeError").new(&[alloc::format!("argument {i}: {e}").into()]))
+}
+
+macro_rules! impl_into_closure {
+    ($($a:ident $i:tt),*) => {
+        impl<Fun, R, $($a),*> IntoClosure<($($a,)*)> for Fun
+        where
+            Fun: FnMut($($a),*) -> R + 'static,
+            R: ClosureReturn,
+            $($a: TryFromVal,)*
+        {
+            type Signature = dyn FnMut($($a),*) -> R;
+
+            #[allow(unused_variables)]
+            fn into_body(mut self) -> Box<dyn FnMut(&[Val]) -> Result<Val, Val>> {
+                Box::new(move |args: &[Val]| self($(arg::<$a>(args, $i)?),*).into_js_result())
+            }
+        }
+    };
+}
+
+impl_into_closure!();
+impl_into_closure!(A 0);
+impl_into_closure!(A 0, B 1);
+impl_into_closure!(A 0, B 1, C 2);
+impl_into_closure!(A 0, B 1, C 2, D 3);
+impl_into_closure!(A 0, B 1, C 2, D 3, E 4);
+impl_into_closure!(A 0, B 1, C 2, D 3, E 4, F 5);
+impl_into_closure!(A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+impl_into_closure!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
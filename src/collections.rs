@@ -0,0 +1,215 @@
+//! Conversions between Rust collections and JS arrays, objects and `Set`s.
+//!
+//! `Vec`s, arrays and tuples map to JS arrays, except for `Vec<u16>`, which
+//! holds UTF-16 code units and maps to a string. String-keyed maps map to
+//! plain objects, and are read back from either objects or `Map`s. Sets map
+//! to `Set`s and are read back from any iterable.
+
+use crate::sys::*;
+use crate::{FromVal, IntoVal, Val};
+use alloc::borrow::Cow;
+use alloc::boxed::Box;
+use alloc::collections::{BTreeMap, BTreeSet};
+use alloc::rc::Rc;
+use alloc::string::String;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use core::hash::{BuildHasher, Hash};
+#[cfg(feature = "std")]
+use std::collections::{HashMap, HashSet};
+
+fn array_of<T: IntoVal>(items: impl IntoIterator<Item = T>) -> Val {
+    let arr = Val::array();
+    for item in items {
+        unsafe { emlite_val_push(arr.as_handle(), item.into_val().as_handle()) };
+    }
+    arr
+}
+
+fn object_of<T: IntoVal>(entries: impl IntoIterator<Item = (String, T)>) -> Val {
+    let obj = Val::object();
+    for (k, v) in entries {
+        obj.set(k, v.into_val());
+    }
+    obj
+}
+
+fn set_of<T: IntoVal>(items: impl IntoIterator<Item = T>) -> Val {
+    Val::global("Set").new(&[array_of(items)])
+}
+
+/// Reads the entries of a `Map`, or the own enumerable properties of an object
+fn entries_of<T: FromVal>(v: &Val) -> impl Iterator<Item = (String, T)> {
+    let entries = if v.instanceof(Val::global("Map")) {
+        v.iter().map(|e| (e.at(0).as_(), e.at(1))).collect()
+    } else {
+        v.entries()
+    };
+    entries.into_iter().map(|(k, v)| (k, v.as_()))
+}
+
+/// A type whose `Vec`s convert to JS arrays.
+///
+/// Implemented for everything `Val` converts from except `u16`, since a
+/// `Vec<u16>` holds UTF-16 code units and converts to a string.
+/// `#[derive(IntoVal)]` and `#[derive(JsCast)]` implement it; types with a
+/// hand-written `From<T> for Val` opt in with `impl VecElement for T {}`.
+pub trait VecElement: IntoVal {}
+
+macro_rules! impl_vec_element {
+    ($($t:ty),*) => {$(
+        impl VecElement for $t {}
+    )*}
+}
+
+impl_vec_element!(
+    bool,
+    i8,
+    u8,
+    i16,
+    i32,
+    u32,
+    i64,
+    u64,
+    isize,
+    usize,
+    f32,
+    f64,
+    (),
+    char,
+    &str,
+    String,
+    &String,
+    &[u16],
+    Cow<'_, str>,
+    Box<str>,
+    Rc<str>,
+    Val,
+    &Val,
+    Vec<u16>,
+    &Vec<u16>
+);
+
+impl<T: IntoVal> VecElement for Option<T> {}
+
+impl<T: VecElement> From<Vec<T>> for Val {
+    fn from(v: Vec<T>) -> Self {
+        array_of(v)
+    }
+}
+
+impl<T: VecElement> VecElement for Vec<T> {}
+
+impl<T: FromVal> FromVal for Vec<T> {
+    /// Values that aren't arrays convert to an empty `Vec`
+    fn from_val(v: &Val) -> Self {
+        T::vec_from_val(v).unwrap_or_default()
+    }
+    fn option_from_val(v: &Val) -> Option<Self> {
+        T::vec_from_val(v)
+    }
+}
+
+impl<T: IntoVal, const N: usize> From<[T; N]> for Val {
+    fn from(v: [T; N]) -> Self {
+        array_of(v)
+    }
+}
+
+impl<T: IntoVal, const N: usize> VecElement for [T; N] {}
+
+impl<T: FromVal, const N: usize> FromVal for [T; N] {
+    /// Missing elements read as `undefined`
+    fn from_val(v: &Val) -> Self {
+        core::array::from_fn(|i| v.at(i as u32).as_())
+    }
+}
+
+macro_rules! impl_tuple {
+    ($($t:ident $i:tt),+) => {
+        impl<$($t: IntoVal),+> From<($($t,)+)> for Val {
+            fn from(v: ($($t,)+)) -> Self {
+                let arr = Val::array();
+                $(unsafe { emlite_val_push(arr.as_handle(), v.$i.into_val().as_handle()) };)+
+                arr
+            }
+        }
+        impl<$($t: IntoVal),+> VecElement for ($($t,)+) {}
+        impl<$($t: FromVal),+> FromVal for ($($t,)+) {
+            fn from_val(v: &Val) -> Self {
+                ($(v.at($i as u32).as_::<$t>(),)+)
+            }
+        }
+    };
+}
+
+impl_tuple!(A 0);
+impl_tuple!(A 0, B 1);
+impl_tuple!(A 0, B 1, C 2);
+impl_tuple!(A 0, B 1, C 2, D 3);
+impl_tuple!(A 0, B 1, C 2, D 3, E 4);
+impl_tuple!(A 0, B 1, C 2, D 3, E 4, F 5);
+impl_tuple!(A 0, B 1, C 2, D 3, E 4, F 5, G 6);
+impl_tuple!(A 0, B 1, C 2, D 3, E 4, F 5, G 6, H 7);
+
+impl<T: IntoVal> From<BTreeMap<String, T>> for Val {
+    fn from(m: BTreeMap<String, T>) -> Self {
+        object_of(m)
+    }
+}
+
+impl<T: IntoVal> VecElement for BTreeMap<String, T> {}
+
+impl<T: FromVal> FromVal for BTreeMap<String, T> {
+    fn from_val(v: &Val) -> Self {
+        entries_of(v).collect()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: IntoVal, S> From<HashMap<String, T, S>> for Val {
+    fn from(m: HashMap<String, T, S>) -> Self {
+        object_of(m)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: IntoVal, S> VecElement for HashMap<String, T, S> {}
+
+#[cfg(feature = "std")]
+impl<T: FromVal, S: BuildHasher + Default> FromVal for HashMap<String, T, S> {
+    fn from_val(v: &Val) -> Self {
+        entries_of(v).collect()
+    }
+}
+
+impl<T: IntoVal> From<BTreeSet<T>> for Val {
+    fn from(s: BTreeSet<T>) -> Self {
+        set_of(s)
+    }
+}
+
+impl<T: IntoVal> VecElement for BTreeSet<T> {}
+
+impl<T: FromVal + Ord> FromVal for BTreeSet<T> {
+    fn from_val(v: &Val) -> Self {
+        v.iter().map(|v| v.as_()).collect()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: IntoVal, S> From<HashSet<T, S>> for Val {
+    fn from(s: HashSet<T, S>) -> Self {
+        set_of(s)
+    }
+}
+
+#[cfg(feature = "std")]
+impl<T: IntoVal, S> VecElement for HashSet<T, S> {}
+
+#[cfg(feature = "std")]
+impl<T: FromVal + Eq + Hash, S: BuildHasher + Default> FromVal for HashSet<T, S> {
+    fn from_val(v: &Val) -> Self {
+        v.iter().map(|v| v.as_()).collect()
+    }
+}
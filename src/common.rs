@@ -1,5 +1,48 @@
 #![allow(dead_code)]
 
+use alloc::vec::Vec;
+
 pub type Handle = u32;
 
 pub(crate) const EMLITE_TARGET: i32 = 1042;
+
+/// The results of `typeof`, indexed by the tag the host returns for them
+pub(crate) const TYPEOF_NAMES: [&str; 8] = [
+    "undefined",
+    "object",
+    "boolean",
+    "number",
+    "bigint",
+    "string",
+    "function",
+    "symbol",
+];
+
+/// Returns the `typeof` name for a host tag
+pub(crate) fn typeof_name(tag: u32) -> &'static str {
+    TYPEOF_NAMES
+        .get(tag as usize)
+        .copied()
+        .unwrap_or("undefined")
+}
+
+/// Runs a `*_into` import against `buf`'s allocation, growing it once the host
+/// reports the full length. Returns false if the value isn't a string.
+pub(crate) unsafe fn read_into<T>(
+    buf: &mut Vec<T>,
+    mut copy: impl FnMut(*mut T, usize) -> usize,
+) -> bool {
+    buf.clear();
+    loop {
+        let cap = buf.capacity();
+        let len = copy(buf.as_mut_ptr(), cap);
+        if len == usize::MAX {
+            return false;
+        }
+        if len <= cap {
+            unsafe { buf.set_len(len) };
+            return true;
+        }
+        buf.reserve(len);
+    }
+}
@@ -0,0 +1,167 @@
+//! Checked conversions out of JS values.
+//!
+//! [`FromVal`](crate::FromVal) converts leniently, the way the host's getters
+//! coerce: `as_::<u8>()` on `300` or `"x"` yields some number regardless.
+//! [`TryFromVal`] instead checks the JS type and that the value fits.
+
+use crate::sys::*;
+use crate::{FromVal, Val};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt;
+
+/// `Number.MAX_SAFE_INTEGER`, the largest integer a Number holds exactly
+const MAX_SAFE_INTEGER: f64 = 9007199254740991.0;
+
+/// The error of a [`TryFromVal`] conversion.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ConversionError {
+    /// The Rust type, or shape, that was expected, e.g. `"u8"`
+    pub expected: &'static str,
+    /// The `typeof` of the value that was found instead, e.g. `"string"`.
+    /// Values of the right type that don't fit, like `300` for `u8`, report
+    /// their own type.
+    pub found_typeof: String,
+}
+
+impl ConversionError {
+    /// Creates an error for `found`, which isn't a valid `expected`
+    pub fn new(expected: &'static str, found: &Val) -> Self {
+        ConversionError {
+            expected,
+            found_typeof: found.typeof_str().into(),
+        }
+    }
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "expected {}, found {}", self.expected, self.found_typeof)
+    }
+}
+
+impl core::error::Error for ConversionError {}
+
+impl From<ConversionError> for Val {
+    /// Converts to a JS `TypeError` carrying the message
+    fn from(e: ConversionError) -> Self {
+        Val::global("TypeError").new(&[alloc::format!("{e}").into()])
+    }
+}
+
+impl crate::VecElement for ConversionError {}
+
+/// A fallible conversion out of a JS value, checking its type and range.
+///
+/// Integers accept both Numbers and BigInts, as long as the value is integral
+/// and in range. Numbers read as 64-bit integers must also be safe integers,
+/// since larger ones may already have been rounded.
+pub trait TryFromVal: Sized {
+    /// Converts `v`, reporting a type or range mismatch as an error
+    fn try_from_val(v: &Val) -> Result<Self, ConversionError>;
+}
+
+impl Val {
+    /// Converts the value with [`TryFromVal`], checking its type and range
+    pub fn try_as<T: TryFromVal>(&self) -> Result<T, ConversionError> {
+        T::try_from_val(self)
+    }
+}
+
+/// Reads an integral Number or BigInt exactly, or `None` if it's neither or
+/// doesn't fit in 64 bits
+fn integer(v: &Val) -> Option<i128> {
+    if v.is_number() {
+        let n = unsafe { emlite_val_get_value_double(v.as_handle()) };
+        // Also rejects NaN and infinities
+        if n.abs() <= MAX_SAFE_INTEGER && n == n as i64 as f64 {
+            return Some(n as i128);
+        }
+        return None;
+    }
+    if v.is_bigint() {
+        // The host wraps BigInts to 64 bits, so check the value survived
+        let i = unsafe { emlite_val_get_value_bigint(v.as_handle()) };
+        if Val::from(i) == *v {
+            return Some(i as i128);
+        }
+        let u = unsafe { emlite_val_get_value_biguint(v.as_handle()) };
+        if Val::from(u) == *v {
+            return Some(u as i128);
+        }
+    }
+    None
+}
+
+macro_rules! impl_try_int {
+    ($($t:ty),*) => {$(
+        impl TryFromVal for $t {
+            fn try_from_val(v: &Val) -> Result<Self, ConversionError> {
+                integer(v)
+                    .and_then(|i| <$t>::try_from(i).ok())
+                    .ok_or_else(|| ConversionError::new(stringify!($t), v))
+            }
+        }
+    )*}
+}
+
+impl_try_int!(i8, i16, i32, i64, isize, u8, u16, u32, u64, usize);
+
+impl TryFromVal for f64 {
+    fn try_from_val(v: &Val) -> Result<Self, ConversionError> {
+        if !v.is_number() {
+            return Err(ConversionError::new("f64", v));
+        }
+        Ok(unsafe { emlite_val_get_value_double(v.as_handle()) })
+    }
+}
+
+impl TryFromVal for f32 {
+    /// Accepts any Number f32 can hold without overflowing; precision may be lost
+    fn try_from_val(v: &Val) -> Result<Self, ConversionError> {
+        let n = f64::try_from_val(v).map_err(|_| ConversionError::new("f32", v))?;
+        let f = n as f32;
+        if f.is_infinite() && n.is_finite() {
+            return Err(ConversionError::new("f32", v));
+        }
+        Ok(f)
+    }
+}
+
+impl TryFromVal for bool {
+    fn try_from_val(v: &Val) -> Result<Self, ConversionError> {
+        if !v.is_bool() {
+            return Err(ConversionError::new("bool", v));
+        }
+        Ok(bool::from_val(v))
+    }
+}
+
+impl TryFromVal for String {
+    fn try_from_val(v: &Val) -> Result<Self, ConversionError> {
+        Option::<String>::from_val(v).ok_or_else(|| ConversionError::new("string", v))
+    }
+}
+
+impl TryFromVal for Vec<u16> {
+    fn try_from_val(v: &Val) -> Result<Self, ConversionError> {
+        v.to_utf16()
+            .ok_or_else(|| ConversionError::new("string", v))
+    }
+}
+
+impl TryFromVal for Val {
+    fn try_from_val(v: &Val) -> Result<Self, ConversionError> {
+        Ok(v.clone())
+    }
+}
+
+impl<T: TryFromVal> TryFromVal for Option<T> {
+    /// `null` and `undefined` convert to `None`
+    fn try_from_val(v: &Val) -> Result<Self, ConversionError> {
+        if v.is_null() || v.is_undefined() {
+            return Ok(None);
+        }
+        T::try_from_val(v).map(Some)
+    }
+}
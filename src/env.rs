@@ -1,7 +1,5 @@
-use crate::common::Handle;
+use crate::common::{Handle, read_into, typeof_name};
 
-use alloc::alloc::dealloc;
-use core::alloc::Layout;
 use core::ffi::{c_char, c_double, c_int, c_longlong, c_uint, c_ulonglong, c_void};
 
 unsafe extern "C" {
@@ -9,7 +7,9 @@ unsafe extern "C" {
     pub fn emlite_val_new_array() -> Handle;
     pub fn emlite_val_new_object() -> Handle;
 
-    pub fn emlite_val_typeof(val: Handle) -> *mut c_char;
+    // `typeof val` as an index into `TYPEOF_NAMES`: undefined, object,
+    // boolean, number, bigint, string, function, symbol
+    pub fn emlite_val_typeof_tag(val: Handle) -> c_uint;
 
     pub fn emlite_val_construct_new(ctor: Handle, argv: Handle) -> Handle;
     pub fn emlite_val_func_call(func: Handle, argv: Handle) -> Handle;
@@ -27,15 +27,25 @@ unsafe extern "C" {
 
     pub fn emlite_val_get_value_bool(val: Handle) -> bool;
     pub fn emlite_val_get_value_int(val: Handle) -> c_int;
-    pub fn emlite_val_get_value_uint(val: Handle) -> c_int;
+    pub fn emlite_val_get_value_uint(val: Handle) -> c_uint;
     pub fn emlite_val_get_value_bigint(val: Handle) -> c_longlong;
     pub fn emlite_val_get_value_biguint(val: Handle) -> c_ulonglong;
     pub fn emlite_val_get_value_double(val: Handle) -> c_double;
-    pub fn emlite_val_get_value_string(val: Handle) -> *mut c_char;
-    pub fn emlite_val_get_value_string_utf16(val: Handle) -> *mut u16;
+
+    // Strings are copied into a buffer owned by the caller. The host writes at
+    // most `cap` units (bytes or UTF-16 code units) starting at `buf` and
+    // returns the string's full length, or `usize::MAX` if `val` isn't a
+    // string. A return value above `cap` means nothing usable was written and
+    // the call should be repeated with a larger buffer. The host keeps no
+    // reference to `buf` and allocates nothing in linear memory.
+    pub fn emlite_val_get_value_string_into(val: Handle, buf: *mut c_char, cap: usize) -> usize;
+    pub fn emlite_val_get_value_string_utf16_into(val: Handle, buf: *mut u16, cap: usize) -> usize;
 
     pub fn emlite_val_get(val: Handle, idx: Handle) -> Handle;
     pub fn emlite_val_set(val: Handle, idx: Handle, val: Handle);
+    // Indexed access with a raw integer, sparing a Number handle for the key
+    pub fn emlite_val_get_index(val: Handle, idx: u32) -> Handle;
+    pub fn emlite_val_set_index(val: Handle, idx: u32, val: Handle);
     pub fn emlite_val_has(val: Handle, idx: Handle) -> bool;
 
     pub fn emlite_val_is_string(val: Handle) -> bool;
@@ -62,8 +72,54 @@ unsafe extern "C" {
 
     pub fn emlite_val_obj_has_own_prop(obj: Handle, prop: *const c_char, len: usize) -> bool;
 
+    // Variants taking the arguments as `argc` contiguous handles at `args`,
+    // read by the host during the call, instead of an array handle. In the
+    // exception-aware `_try` ones, `threw` is set when the returned handle is
+    // a thrown value rather than a completion value.
+    pub fn emlite_val_obj_call_args(
+        obj: Handle,
+        name: *const c_char,
+        len: usize,
+        args: *const Handle,
+        argc: usize,
+    ) -> Handle;
+    pub fn emlite_val_construct_new_args(ctor: Handle, args: *const Handle, argc: usize) -> Handle;
+    pub fn emlite_val_func_call_args(func: Handle, args: *const Handle, argc: usize) -> Handle;
+    pub fn emlite_val_obj_call_args_try(
+        obj: Handle,
+        name: *const c_char,
+        len: usize,
+        args: *const Handle,
+        argc: usize,
+        threw: *mut bool,
+    ) -> Handle;
+    pub fn emlite_val_construct_new_args_try(
+        ctor: Handle,
+        args: *const Handle,
+        argc: usize,
+        threw: *mut bool,
+    ) -> Handle;
+    pub fn emlite_val_func_call_args_try(
+        func: Handle,
+        args: *const Handle,
+        argc: usize,
+        threw: *mut bool,
+    ) -> Handle;
+
     pub fn emlite_val_make_callback(id: Handle, data: Handle) -> Handle;
 
+    // Typed arrays: `kind` is a `TypedArrayKind` and lengths count elements.
+    // `make` copies out of linear memory, `view` aliases it without copying.
+    pub fn emlite_val_make_typed_array(kind: c_uint, data: *const c_void, len: usize) -> Handle;
+    pub fn emlite_val_make_typed_array_view(kind: c_uint, data: *mut c_void, len: usize) -> Handle;
+    pub fn emlite_val_typed_array_len(val: Handle, kind: c_uint) -> usize;
+    pub fn emlite_val_typed_array_copy(
+        val: Handle,
+        kind: c_uint,
+        data: *mut c_void,
+        len: usize,
+    ) -> usize;
+
     pub fn emlite_print_object_map();
 
     pub fn emlite_reset_object_map();
@@ -74,7 +130,6 @@ unsafe extern "C" {
 // Unified interface functions to abstract away wasip2 vs other target differences
 use alloc::string::{String, ToString};
 use alloc::vec::Vec;
-use core::ffi::CStr;
 
 pub unsafe fn emlite_val_make_str_unified(s: &str) -> Handle {
     unsafe { emlite_val_make_str(s.as_ptr() as _, s.len()) }
@@ -84,56 +139,118 @@ pub unsafe fn emlite_val_make_str_utf16_unified(s: &[u16]) -> Handle {
     unsafe { emlite_val_make_str_utf16(s.as_ptr(), s.len()) }
 }
 
-pub unsafe fn emlite_val_obj_call_unified(obj: Handle, method: &str, argv: Handle) -> Handle {
-    unsafe { emlite_val_obj_call(obj, method.as_ptr() as _, method.len(), argv) }
+pub unsafe fn emlite_val_obj_call_unified(obj: Handle, method: &str, args: &[Handle]) -> Handle {
+    unsafe {
+        emlite_val_obj_call_args(
+            obj,
+            method.as_ptr() as _,
+            method.len(),
+            args.as_ptr(),
+            args.len(),
+        )
+    }
+}
+
+pub unsafe fn emlite_val_construct_new_unified(ctor: Handle, args: &[Handle]) -> Handle {
+    unsafe { emlite_val_construct_new_args(ctor, args.as_ptr(), args.len()) }
+}
+
+pub unsafe fn emlite_val_func_call_unified(func: Handle, args: &[Handle]) -> Handle {
+    unsafe { emlite_val_func_call_args(func, args.as_ptr(), args.len()) }
 }
 
 pub unsafe fn emlite_val_obj_has_own_prop_unified(obj: Handle, prop: &str) -> bool {
     unsafe { emlite_val_obj_has_own_prop(obj, prop.as_ptr() as _, prop.len()) }
 }
 
-pub unsafe fn emlite_val_typeof_unified(h: Handle) -> String {
-    unsafe {
-        let ptr = emlite_val_typeof(h);
-        if ptr.is_null() {
-            String::from("undefined")
-        } else {
-            String::from_utf8_lossy(CStr::from_ptr(ptr).to_bytes()).to_string()
-        }
-    }
+pub unsafe fn emlite_val_obj_call_try_unified(
+    obj: Handle,
+    method: &str,
+    args: &[Handle],
+) -> Result<Handle, Handle> {
+    let mut threw = false;
+    let h = unsafe {
+        emlite_val_obj_call_args_try(
+            obj,
+            method.as_ptr() as _,
+            method.len(),
+            args.as_ptr(),
+            args.len(),
+            &mut threw,
+        )
+    };
+    if threw { Err(h) } else { Ok(h) }
 }
 
-pub unsafe fn emlite_val_get_value_string_unified(h: Handle) -> Option<String> {
-    unsafe {
-        let ptr = emlite_val_get_value_string(h);
-        if ptr.is_null() {
-            None
-        } else {
-            Some(CStr::from_ptr(ptr).to_string_lossy().into_owned())
-        }
-    }
+pub unsafe fn emlite_val_construct_new_try_unified(
+    ctor: Handle,
+    args: &[Handle],
+) -> Result<Handle, Handle> {
+    let mut threw = false;
+    let h =
+        unsafe { emlite_val_construct_new_args_try(ctor, args.as_ptr(), args.len(), &mut threw) };
+    if threw { Err(h) } else { Ok(h) }
 }
 
-pub unsafe fn emlite_val_get_value_string_utf16_unified(h: Handle) -> Option<Vec<u16>> {
+pub unsafe fn emlite_val_func_call_try_unified(
+    func: Handle,
+    args: &[Handle],
+) -> Result<Handle, Handle> {
+    let mut threw = false;
+    let h = unsafe { emlite_val_func_call_args_try(func, args.as_ptr(), args.len(), &mut threw) };
+    if threw { Err(h) } else { Ok(h) }
+}
+
+/// Throws `val` to the JS caller of the running callback. The host unwinds
+/// the wasm stack, so this never returns.
+pub unsafe fn emlite_val_throw_from_callback_unified(val: Handle) -> Handle {
+    unsafe { emlite_val_throw(val) }
+}
+
+pub unsafe fn emlite_val_typeof_unified(h: Handle) -> &'static str {
+    typeof_name(unsafe { emlite_val_typeof_tag(h) })
+}
+
+pub unsafe fn emlite_val_get_value_string_into_unified(h: Handle, out: &mut String) -> bool {
+    let mut bytes = core::mem::take(out).into_bytes();
+    let ok = unsafe {
+        read_into(&mut bytes, |buf, cap| {
+            emlite_val_get_value_string_into(h, buf as _, cap)
+        })
+    };
+    *out = String::from_utf8(bytes)
+        .unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned());
+    ok
+}
+
+pub unsafe fn emlite_val_get_value_string_utf16_into_unified(
+    h: Handle,
+    out: &mut Vec<u16>,
+) -> bool {
     unsafe {
-        let ptr = emlite_val_get_value_string_utf16(h);
-        if ptr.is_null() {
-            None
-        } else {
-            // Find length by searching for null terminator
-            let mut len = 0;
-            let mut current = ptr;
-            while *current != 0 {
-                len += 1;
-                current = current.add(1);
-            }
-            // Convert to Vec<u16>
-            let slice = core::slice::from_raw_parts(ptr, len);
-            Some(slice.to_vec())
-        }
+        read_into(out, |buf, cap| {
+            emlite_val_get_value_string_utf16_into(h, buf, cap)
+        })
     }
 }
 
+pub unsafe fn emlite_val_make_typed_array_unified(kind: u32, data: &[u8], len: usize) -> Handle {
+    unsafe { emlite_val_make_typed_array(kind, data.as_ptr() as _, len) }
+}
+
+pub unsafe fn emlite_val_typed_array_len_unified(h: Handle, kind: u32) -> usize {
+    unsafe { emlite_val_typed_array_len(h, kind) }
+}
+
+pub unsafe fn emlite_val_typed_array_copy_unified(
+    h: Handle,
+    kind: u32,
+    dst: &mut [u8],
+    len: usize,
+) -> usize {
+    unsafe { emlite_val_typed_array_copy(h, kind, dst.as_mut_ptr() as _, len) }
+}
+
 pub unsafe fn emlite_val_not_unified(h: Handle) -> bool {
     unsafe { emlite_val_not(h) }
 }
@@ -0,0 +1,130 @@
+use crate::{ConversionError, Val};
+use alloc::string::String;
+use core::fmt;
+
+/// A value thrown by JavaScript code, as reported by the `try_*` family of calls.
+///
+/// The thrown value is kept as-is, so non-Error throws (`throw "oops"`) are not lost.
+#[derive(Clone, Debug)]
+pub struct JsException {
+    value: Val,
+}
+
+impl JsException {
+    /// Wraps a thrown value
+    pub fn new(value: Val) -> JsException {
+        JsException { value }
+    }
+
+    /// Returns the thrown value
+    pub fn value(&self) -> &Val {
+        &self.value
+    }
+
+    /// Returns the thrown value, consuming the exception
+    pub fn into_value(self) -> Val {
+        self.value
+    }
+
+    /// Checks whether the thrown value is an instance of `Error`
+    pub fn is_error(&self) -> bool {
+        self.value.is_error()
+    }
+
+    /// The `name` property of the thrown value, e.g. `TypeError`
+    pub fn name(&self) -> Option<String> {
+        self.string_prop("name")
+    }
+
+    /// The `message` property of the thrown value
+    pub fn message(&self) -> Option<String> {
+        self.string_prop("message")
+    }
+
+    /// The `stack` property of the thrown value, if the engine provides one
+    pub fn stack(&self) -> Option<String> {
+        self.string_prop("stack")
+    }
+
+    fn string_prop(&self, prop: &str) -> Option<String> {
+        match self.value.typeof_str() {
+            "object" | "function" if !self.value.is_null() => self.value.get(prop).as_string(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for JsException {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.name(), self.message()) {
+            (Some(name), Some(msg)) if !msg.is_empty() => write!(f, "{name}: {msg}"),
+            (Some(name), _) => f.write_str(&name),
+            _ => {
+                let s = Val::global("String").invoke(core::slice::from_ref(&self.value));
+                f.write_str(s.as_string().as_deref().unwrap_or("<exception>"))
+            }
+        }
+    }
+}
+
+impl core::error::Error for JsException {}
+
+impl From<Val> for JsException {
+    fn from(value: Val) -> Self {
+        JsException::new(value)
+    }
+}
+
+impl From<JsException> for Val {
+    fn from(e: JsException) -> Self {
+        e.value
+    }
+}
+
+impl crate::VecElement for JsException {}
+
+/// An error raised from Rust to be thrown in JS, e.g. as the `Err` of a
+/// [`Closure::wrap`](crate::Closure::wrap) closure.
+#[derive(Clone, Debug)]
+pub struct JsError {
+    value: Val,
+}
+
+impl JsError {
+    /// Creates an `Error` with `message`
+    pub fn new(message: &str) -> JsError {
+        JsError::with_constructor("Error", message)
+    }
+
+    /// Creates an error of the global constructor `name`, e.g. `"RangeError"`
+    pub fn with_constructor(name: &str, message: &str) -> JsError {
+        JsError {
+            value: Val::global(name).new(&[message.into()]),
+        }
+    }
+
+    /// Returns the error value
+    pub fn value(&self) -> &Val {
+        &self.value
+    }
+}
+
+impl From<JsException> for JsError {
+    fn from(e: JsException) -> Self {
+        JsError { value: e.value }
+    }
+}
+
+impl From<ConversionError> for JsError {
+    fn from(e: ConversionError) -> Self {
+        JsError { value: e.into() }
+    }
+}
+
+impl From<JsError> for Val {
+    fn from(e: JsError) -> Self {
+        e.value
+    }
+}
+
+impl crate::VecElement for JsError {}
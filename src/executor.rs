@@ -0,0 +1,216 @@
+//! A minimal single-threaded executor driven by the JS microtask queue.
+//!
+//! Every wake of a task queues one microtask (`queueMicrotask`, or
+//! `Promise.resolve().then` where that's missing) which polls the task once,
+//! calling a JS function created for the task when it's spawned.
+//! No thread or event loop of our own is involved, so async Rust interleaves
+//! with JS callbacks and promise reactions on the host's event loop.
+//!
+//! Wakers are reference counted with `Rc` and must stay on the thread that
+//! spawned the task, which is always the case on wasm.
+
+use crate::{Closure, Val};
+use alloc::boxed::Box;
+use alloc::rc::{Rc, Weak};
+use core::cell::{Cell, RefCell};
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+type PollFn = Closure<dyn FnMut(&[Val]) -> Val>;
+
+struct Task {
+    future: RefCell<Option<Pin<Box<dyn Future<Output = ()>>>>>,
+    /// The JS function queued to poll the task, which doesn't keep it alive
+    poll: PollFn,
+    /// Keeps the task alive while a poll is queued
+    scheduled: RefCell<Option<Rc<Task>>>,
+    cancelled: Cell<bool>,
+}
+
+impl Task {
+    fn new(future: Pin<Box<dyn Future<Output = ()>>>) -> Rc<Self> {
+        Rc::new_cyclic(|task: &Weak<Task>| {
+            let task = task.clone();
+            Task {
+                future: RefCell::new(Some(future)),
+                poll: Closure::new(move |_| {
+                    if let Some(task) = task.upgrade() {
+                        task.run();
+                    }
+                    Val::undefined()
+                }),
+                scheduled: RefCell::new(None),
+                cancelled: Cell::new(false),
+            }
+        })
+    }
+
+    fn schedule(self: &Rc<Self>) {
+        if self.cancelled.get() || self.scheduled.borrow().is_some() {
+            return;
+        }
+        *self.scheduled.borrow_mut() = Some(self.clone());
+        queue_microtask(self.poll.as_val().clone());
+    }
+
+    fn run(self: &Rc<Self>) {
+        self.scheduled.take();
+        // Take the future out while polling so the task can wake or cancel
+        // itself from inside `poll`
+        let Some(mut future) = self.future.borrow_mut().take() else {
+            return;
+        };
+        let waker = waker(self.clone());
+        let mut cx = Context::from_waker(&waker);
+        if future.as_mut().poll(&mut cx).is_pending() && !self.cancelled.get() {
+            *self.future.borrow_mut() = Some(future);
+        }
+    }
+
+    fn cancel(&self) {
+        self.cancelled.set(true);
+        let future = self.future.borrow_mut().take();
+        drop(future);
+    }
+}
+
+fn queue_microtask(f: Val) {
+    let queue = Val::global("queueMicrotask");
+    if queue.is_function() {
+        queue.invoke(&[f]);
+    } else {
+        Val::global("Promise")
+            .call("resolve", &[])
+            .call("then", &[f]);
+    }
+}
+
+static VTABLE: RawWakerVTable = RawWakerVTable::new(clone_raw, wake_raw, wake_by_ref_raw, drop_raw);
+
+fn waker(task: Rc<Task>) -> Waker {
+    unsafe { Waker::from_raw(RawWaker::new(Rc::into_raw(task) as *const (), &VTABLE)) }
+}
+
+unsafe fn clone_raw(ptr: *const ()) -> RawWaker {
+    unsafe { Rc::increment_strong_count(ptr as *const Task) };
+    RawWaker::new(ptr, &VTABLE)
+}
+
+unsafe fn wake_raw(ptr: *const ()) {
+    let task = unsafe { Rc::from_raw(ptr as *const Task) };
+    task.schedule();
+}
+
+unsafe fn wake_by_ref_raw(ptr: *const ()) {
+    let task = core::mem::ManuallyDrop::new(unsafe { Rc::from_raw(ptr as *const Task) });
+    task.schedule();
+}
+
+unsafe fn drop_raw(ptr: *const ()) {
+    unsafe { Rc::decrement_strong_count(ptr as *const Task) };
+}
+
+struct JoinState<T> {
+    output: Option<T>,
+    finished: bool,
+    waker: Option<Waker>,
+}
+
+/// A handle to a task started with [`spawn_local`].
+///
+/// Awaiting it yields the task's output. Dropping it detaches the task, which
+/// keeps running; [`JoinHandle::cancel`] stops it instead.
+pub struct JoinHandle<T> {
+    task: Rc<Task>,
+    state: Rc<RefCell<JoinState<T>>>,
+}
+
+impl<T> JoinHandle<T> {
+    /// Cancels the task, dropping its future at its current await point
+    pub fn cancel(self) {
+        self.task.cancel();
+    }
+
+    /// Checks whether the task has run to completion
+    pub fn is_finished(&self) -> bool {
+        self.state.borrow().finished
+    }
+}
+
+impl<T> Future for JoinHandle<T> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let mut state = self.state.borrow_mut();
+        match state.output.take() {
+            Some(output) => Poll::Ready(output),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl<T> core::fmt::Debug for JoinHandle<T> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("JoinHandle")
+            .field("cancelled", &self.task.cancelled.get())
+            .finish()
+    }
+}
+
+/// Runs `future` on the current thread's JS event loop.
+///
+/// The first poll happens on a microtask, not inside this call.
+pub fn spawn_local<F>(future: F) -> JoinHandle<F::Output>
+where
+    F: Future + 'static,
+{
+    let state = Rc::new(RefCell::new(JoinState {
+        output: None,
+        finished: false,
+        waker: None,
+    }));
+    let join_state = state.clone();
+    let task = Task::new(Box::pin(async move {
+        let output = future.await;
+        let waker = {
+            let mut state = join_state.borrow_mut();
+            state.output = Some(output);
+            state.finished = true;
+            state.waker.take()
+        };
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }));
+    task.schedule();
+    JoinHandle { task, state }
+}
+
+/// Returns a future which yields to the other queued tasks once before completing.
+pub fn yield_now() -> YieldNow {
+    YieldNow { yielded: false }
+}
+
+/// The future returned by [`yield_now`].
+#[derive(Debug)]
+#[must_use = "futures do nothing unless awaited"]
+pub struct YieldNow {
+    yielded: bool,
+}
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.yielded {
+            return Poll::Ready(());
+        }
+        self.yielded = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
@@ -0,0 +1,147 @@
+//! Typed references to JS functions.
+
+use crate::common::Handle;
+use crate::{AsHandle, FromVal, IntoVal, JsCast, Val};
+use core::marker::PhantomData;
+use core::ops::Deref;
+
+/// A JS function taking `Args`, a tuple of up to 8 [`IntoVal`] arguments,
+/// and returning `R`.
+///
+/// The types aren't checked against the function, which JS doesn't declare;
+/// arguments are converted on each call and the result with [`FromVal`].
+/// [`JsCast::dyn_into`] only checks that a value is a function:
+///
+/// ```ignore
+/// let parse: JsFunction<(String, u32), f64> =
+///     Val::global("parseInt").dyn_into().unwrap();
+/// assert_eq!(parse.call("ff".into(), 16), 255.0);
+/// ```
+#[repr(transparent)]
+pub struct JsFunction<Args, R = Val> {
+    val: Val,
+    _marker: PhantomData<fn(Args) -> R>,
+}
+
+impl<Args, R> JsFunction<Args, R> {
+    /// Returns the function
+    pub fn as_val(&self) -> &Val {
+        &self.val
+    }
+
+    /// Returns a function calling this one with `this` bound, like
+    /// `Function.prototype.bind`
+    pub fn bind(&self, this: &Val) -> Self {
+        Self::unchecked_from_val(self.val.call("bind", core::slice::from_ref(this)))
+    }
+
+    /// Calls the function with `this` and the arguments as a tuple, like
+    /// `Function.prototype.apply`
+    pub fn apply(&self, this: &Val, args: Args) -> R
+    where
+        Args: IntoVal,
+        R: FromVal,
+    {
+        self.val
+            .call("apply", &[this.clone(), args.into_val()])
+            .as_()
+    }
+}
+
+macro_rules! impl_call {
+    ($($a:ident $x:ident),*) => {
+        impl<$($a: IntoVal,)* R: FromVal> JsFunction<($($a,)*), R> {
+            /// Calls the function with an undefined `this`
+            #[allow(clippy::too_many_arguments)]
+            pub fn call(&self, $($x: $a),*) -> R {
+                self.val.invoke(&[$($x.into_val()),*]).as_()
+            }
+
+            /// Calls the function with `this`, like `Function.prototype.call`
+            #[allow(clippy::too_many_arguments, clippy::cloned_ref_to_slice_refs)]
+            pub fn call_with_this(&self, this: &Val, $($x: $a),*) -> R {
+                self.val
+                    .call("call", &[this.clone(), $($x.into_val()),*])
+                    .as_()
+            }
+        }
+    };
+}
+
+impl_call!();
+impl_call!(A a);
+impl_call!(A a, B b);
+impl_call!(A a, B b, C c);
+impl_call!(A a, B b, C c, D d);
+impl_call!(A a, B b, C c, D d, E e);
+impl_call!(A a, B b, C c, D d, E e, F f);
+impl_call!(A a, B b, C c, D d, E e, F f, G g);
+impl_call!(A a, B b, C c, D d, E e, F f, G g, H h);
+
+impl<Args, R> JsCast for JsFunction<Args, R> {
+    fn instanceof(v: &Val) -> bool {
+        v.is_function()
+    }
+
+    fn unchecked_from_val(v: Val) -> Self {
+        JsFunction {
+            val: v,
+            _marker: PhantomData,
+        }
+    }
+
+    fn unchecked_from_val_ref(v: &Val) -> &Self {
+        // Sound since the struct is a transparent wrapper around a Val
+        unsafe { &*(v as *const Val as *const Self) }
+    }
+}
+
+impl<Args, R> Clone for JsFunction<Args, R> {
+    fn clone(&self) -> Self {
+        Self::unchecked_from_val(self.val.clone())
+    }
+}
+
+impl<Args, R> core::fmt::Debug for JsFunction<Args, R> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("JsFunction")
+            .field("val", &self.val)
+            .finish()
+    }
+}
+
+impl<Args, R> Deref for JsFunction<Args, R> {
+    type Target = Val;
+
+    fn deref(&self) -> &Val {
+        &self.val
+    }
+}
+
+impl<Args, R> AsRef<Val> for JsFunction<Args, R> {
+    fn as_ref(&self) -> &Val {
+        &self.val
+    }
+}
+
+impl<Args, R> AsHandle for JsFunction<Args, R> {
+    fn as_handle(&self) -> Handle {
+        self.val.as_handle()
+    }
+}
+
+impl<Args, R> From<JsFunction<Args, R>> for Val {
+    fn from(f: JsFunction<Args, R>) -> Self {
+        f.val
+    }
+}
+
+impl<Args, R> From<&JsFunction<Args, R>> for Val {
+    fn from(f: &JsFunction<Args, R>) -> Self {
+        f.val.clone()
+    }
+}
+
+impl<Args, R> crate::VecElement for JsFunction<Args, R> {}
+
+impl<Args, R> crate::VecElement for &JsFunction<Args, R> {}
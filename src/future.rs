@@ -0,0 +1,137 @@
+use crate::{Closure, Val, spawn_local};
+use alloc::rc::Rc;
+use core::cell::RefCell;
+use core::future::{Future, IntoFuture};
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+
+type SettleFn = Closure<dyn FnOnce(&[Val]) -> Val>;
+
+struct State {
+    result: Option<Result<Val, Val>>,
+    waker: Option<Waker>,
+    /// The `then` callbacks, which keep the state alive until one of them
+    /// runs and drops both
+    callbacks: Option<(SettleFn, SettleFn)>,
+}
+
+/// A Rust future that resolves when a JS Promise settles.
+///
+/// The promise is hooked once with `then` callbacks which store the outcome
+/// and wake the task: fulfillment resolves to `Ok(value)`, rejection to
+/// `Err(reason)`. Any value can be awaited; non-thenables resolve immediately
+/// on the next microtask, as with JS `await`. The callbacks are freed once
+/// the promise settles, even if the future was dropped before.
+///
+/// `Val` implements `IntoFuture`, so a promise can be awaited directly:
+/// `let res = Val::global("fetch").invoke(&argv!["/data"]).await;`
+pub struct JsFuture {
+    state: Rc<RefCell<State>>,
+}
+
+impl JsFuture {
+    /// Starts waiting on `promise`
+    pub fn new(promise: &Val) -> JsFuture {
+        let state = Rc::new(RefCell::new(State {
+            result: None,
+            waker: None,
+            callbacks: None,
+        }));
+        let settle = |state: Rc<RefCell<State>>, ok: bool| {
+            Closure::once(move |args| {
+                let v = args.first().cloned().unwrap_or(Val::undefined());
+                let (waker, callbacks) = {
+                    let mut state = state.borrow_mut();
+                    state.result = Some(if ok { Ok(v) } else { Err(v) });
+                    (state.waker.take(), state.callbacks.take())
+                };
+                drop(callbacks);
+                if let Some(waker) = waker {
+                    waker.wake();
+                }
+                Val::undefined()
+            })
+        };
+        let on_fulfilled = settle(state.clone(), true);
+        let on_rejected = settle(state.clone(), false);
+        Val::global("Promise")
+            .call("resolve", core::slice::from_ref(promise))
+            .call("then", &[Val::from(&on_fulfilled), Val::from(&on_rejected)]);
+        state.borrow_mut().callbacks = Some((on_fulfilled, on_rejected));
+        JsFuture { state }
+    }
+}
+
+impl From<Val> for JsFuture {
+    fn from(promise: Val) -> Self {
+        JsFuture::new(&promise)
+    }
+}
+
+impl From<&Val> for JsFuture {
+    fn from(promise: &Val) -> Self {
+        JsFuture::new(promise)
+    }
+}
+
+impl Future for JsFuture {
+    type Output = Result<Val, Val>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut state = self.state.borrow_mut();
+        match state.result.take() {
+            Some(result) => Poll::Ready(result),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl IntoFuture for Val {
+    type Output = Result<Val, Val>;
+    type IntoFuture = JsFuture;
+
+    fn into_future(self) -> Self::IntoFuture {
+        JsFuture::new(&self)
+    }
+}
+
+/// Converts a Rust future into a JS Promise.
+///
+/// The future is run with [`spawn_local`]; the Promise fulfills with the `Ok`
+/// value or rejects with the `Err` value, passed through unchanged.
+pub fn future_to_promise<F>(future: F) -> Val
+where
+    F: Future<Output = Result<Val, Val>> + 'static,
+{
+    let resolvers = Rc::new(RefCell::new(None));
+    let slot = resolvers.clone();
+    // The executor runs synchronously inside the constructor
+    let executor = Closure::once(move |args| {
+        let arg = |i: usize| args.get(i).cloned().unwrap_or(Val::undefined());
+        *slot.borrow_mut() = Some((arg(0), arg(1)));
+        Val::undefined()
+    });
+    let promise = Val::global("Promise").new(&[Val::from(&executor)]);
+    drop(executor);
+    if let Some((resolve, reject)) = resolvers.borrow_mut().take() {
+        spawn_local(async move {
+            match future.await {
+                Ok(v) => resolve.invoke(&[v]),
+                Err(e) => reject.invoke(&[e]),
+            };
+        });
+    }
+    promise
+}
+
+impl core::fmt::Debug for JsFuture {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let settled = self.state.borrow().result.is_some();
+        f.debug_struct("JsFuture")
+            .field("settled", &settled)
+            .finish()
+    }
+}
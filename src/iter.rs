@@ -0,0 +1,152 @@
+use crate::{JsException, Val, WellKnownSymbol};
+use alloc::string::String;
+use alloc::vec::Vec;
+
+impl Val {
+    /// Returns the names of the object's own enumerable string-keyed
+    /// properties, as `Object.keys` does
+    pub fn keys(&self) -> Vec<String> {
+        Val::global("Object")
+            .call("keys", core::slice::from_ref(self))
+            .to_vec()
+    }
+
+    /// Returns the values of the object's own enumerable string-keyed
+    /// properties, as `Object.values` does
+    pub fn values(&self) -> Vec<Val> {
+        Val::global("Object")
+            .call("values", core::slice::from_ref(self))
+            .to_vec()
+    }
+
+    /// Returns `[name, value]` pairs of the object's own enumerable
+    /// string-keyed properties, as `Object.entries` does
+    pub fn entries(&self) -> Vec<(String, Val)> {
+        Val::global("Object")
+            .call("entries", core::slice::from_ref(self))
+            .to_vec::<Val>()
+            .into_iter()
+            .map(|pair| (pair.at(0).as_(), pair.at(1)))
+            .collect()
+    }
+
+    /// Iterates a JS iterable (an array, `Map`, `Set`, generator, `NodeList`...)
+    /// through its `Symbol.iterator` method, like `for...of`.
+    ///
+    /// Values are fetched lazily, one `next()` call per item. If the value
+    /// isn't iterable or the iterator throws, iteration ends and the exception
+    /// is kept in [`JsIterator::exception`].
+    pub fn iter(&self) -> JsIterator {
+        let method = self.get(WellKnownSymbol::Iterator);
+        if method.is_undefined() || method.is_null() {
+            return JsIterator::failed(type_error("value is not iterable"));
+        }
+        match method.try_call("call", core::slice::from_ref(self)) {
+            Ok(iter) if is_object(&iter) => JsIterator::new(iter),
+            Ok(_) => JsIterator::failed(type_error(
+                "Result of the Symbol.iterator method is not an object",
+            )),
+            Err(e) => JsIterator::failed(e),
+        }
+    }
+}
+
+pub(crate) fn is_object(v: &Val) -> bool {
+    match v.typeof_str() {
+        "object" => !v.is_null(),
+        "function" => true,
+        _ => false,
+    }
+}
+
+fn type_error(msg: &str) -> JsException {
+    JsException::new(Val::global("TypeError").new(&[msg.into()]))
+}
+
+/// A Rust iterator driving a JS iterator object, returned by [`Val::iter`].
+///
+/// Dropping it before the JS iterator is exhausted calls the iterator's
+/// `return()` method, so generators run their `finally` blocks and resources
+/// held by the iterator are released, as when breaking out of `for...of`.
+#[derive(Debug)]
+pub struct JsIterator {
+    iter: Val,
+    done: bool,
+    exception: Option<JsException>,
+}
+
+impl JsIterator {
+    fn new(iter: Val) -> JsIterator {
+        JsIterator {
+            iter,
+            done: false,
+            exception: None,
+        }
+    }
+
+    fn failed(e: JsException) -> JsIterator {
+        JsIterator {
+            iter: Val::undefined(),
+            done: true,
+            exception: Some(e),
+        }
+    }
+
+    /// Returns the exception that ended iteration early, if any
+    pub fn exception(&self) -> Option<&JsException> {
+        self.exception.as_ref()
+    }
+
+    /// Takes the exception that ended iteration early, if any
+    pub fn take_exception(&mut self) -> Option<JsException> {
+        self.exception.take()
+    }
+
+    /// Returns the underlying JS iterator object
+    pub fn as_val(&self) -> &Val {
+        &self.iter
+    }
+}
+
+impl Iterator for JsIterator {
+    type Item = Val;
+
+    fn next(&mut self) -> Option<Val> {
+        if self.done {
+            return None;
+        }
+        let result = match self.iter.try_call("next", &[]) {
+            Ok(result) if is_object(&result) => Ok(result),
+            Ok(_) => Err(type_error("Iterator result is not an object")),
+            Err(e) => Err(e),
+        };
+        let result = match result {
+            Ok(result) => result,
+            Err(e) => {
+                // An iterator that threw is already closed, so `return()` isn't called
+                self.done = true;
+                self.exception = Some(e);
+                return None;
+            }
+        };
+        if result.get("done").as_::<bool>() {
+            self.done = true;
+            return None;
+        }
+        Some(result.get("value"))
+    }
+}
+
+impl core::iter::FusedIterator for JsIterator {}
+
+impl Drop for JsIterator {
+    fn drop(&mut self) {
+        if self.done {
+            return;
+        }
+        let ret = self.iter.get("return");
+        if !ret.is_undefined() && !ret.is_null() {
+            let _ = ret.try_call("call", core::slice::from_ref(&self.iter));
+        }
+    }
+}
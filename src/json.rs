@@ -0,0 +1,169 @@
+//! JSON text conversion through the host's `JSON` object.
+
+use crate::sys::*;
+use crate::{Closure, FromVal, JsException, Val};
+use alloc::rc::Rc;
+use alloc::string::String;
+use core::cell::RefCell;
+
+type Callback = Rc<RefCell<dyn FnMut(&str, Val) -> Val>>;
+type KeyValueFn = Closure<dyn FnMut(&[Val]) -> Val>;
+
+/// Wraps a `(key, value)` callback as the `function (key, value)` that
+/// `JSON.stringify` and `JSON.parse` expect.
+fn key_value_fn(f: Callback) -> KeyValueFn {
+    Closure::new(move |args: &[Val]| {
+        let key = args.first().map(String::from_val).unwrap_or_default();
+        let value = args.get(1).cloned().unwrap_or(Val::undefined());
+        (f.borrow_mut())(&key, value)
+    })
+}
+
+fn json() -> Val {
+    Val::global("JSON")
+}
+
+/// Options for serializing to JSON text, the `replacer` and `space` arguments
+/// of `JSON.stringify`.
+///
+/// ```no_run
+/// use emlite::{Val, json::Stringify};
+/// let v = Val::object();
+/// v.set("secret", "hunter2");
+/// let text = Stringify::new()
+///     .pretty()
+///     .replacer(|key, value| if key == "secret" { Val::undefined() } else { value })
+///     .stringify(&v);
+/// ```
+#[derive(Default)]
+pub struct Stringify {
+    space: Option<Val>,
+    replacer: Option<Callback>,
+}
+
+impl Stringify {
+    /// Compact output without a replacer
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Indents nested levels by two spaces
+    pub fn pretty(self) -> Self {
+        self.indent(2)
+    }
+
+    /// Indents nested levels by `n` spaces, at most 10
+    pub fn indent(mut self, n: u32) -> Self {
+        self.space = Some(n.into());
+        self
+    }
+
+    /// Indents nested levels with `s`, truncated to 10 characters
+    pub fn indent_with(mut self, s: &str) -> Self {
+        self.space = Some(s.into());
+        self
+    }
+
+    /// Passes every `(key, value)` pair through `f` before it's serialized,
+    /// starting with the value itself under the key `""`. Returning
+    /// `undefined` leaves the property out.
+    pub fn replacer<F: FnMut(&str, Val) -> Val + 'static>(mut self, f: F) -> Self {
+        self.replacer = Some(Rc::new(RefCell::new(f)));
+        self
+    }
+
+    /// Serializes `v` to JSON text.
+    ///
+    /// Returns an error if serialization throws (a BigInt, a cycle, a
+    /// throwing `toJSON` or replacer) or if `v` has no JSON representation
+    /// (`undefined`, a function or a symbol).
+    pub fn stringify(&self, v: &Val) -> Result<String, JsException> {
+        let replacer = self.replacer.clone().map(key_value_fn);
+        let args = [
+            v.clone(),
+            replacer
+                .as_ref()
+                .map_or(Val::undefined(), |f| f.as_val().clone()),
+            self.space.clone().unwrap_or(Val::undefined()),
+        ];
+        json()
+            .try_call("stringify", &args)?
+            .as_string()
+            .ok_or_else(|| {
+                let msg = alloc::format!("{} has no JSON representation", v.typeof_str());
+                JsException::new(Val::global("TypeError").new(&[msg.into()]))
+            })
+    }
+}
+
+impl Val {
+    /// Serializes the value to compact JSON text, see [`Stringify`] for
+    /// indentation and replacers
+    pub fn to_json_string(&self) -> Result<String, JsException> {
+        Stringify::new().stringify(self)
+    }
+
+    /// Parses JSON text, reporting malformed input as a `SyntaxError`
+    pub fn parse_json(text: &str) -> Result<Val, JsException> {
+        json().try_call("parse", &[text.into()])
+    }
+
+    /// Parses JSON text, passing every parsed `(key, value)` pair through
+    /// `reviver`, innermost first and ending with the root under the key `""`.
+    /// Returning `undefined` removes the property.
+    pub fn parse_json_with<F: FnMut(&str, Val) -> Val + 'static>(
+        text: &str,
+        reviver: F,
+    ) -> Result<Val, JsException> {
+        let reviver = key_value_fn(Rc::new(RefCell::new(reviver)));
+        json().try_call("parse", &[text.into(), reviver.as_val().clone()])
+    }
+}
+
+#[cfg(feature = "serde_json")]
+impl Val {
+    /// Converts to a `serde_json::Value` with JSON semantics: `toJSON` is
+    /// honored and `undefined`s, functions and symbols are dropped
+    pub fn to_json_value(&self) -> Result<serde_json::Value, JsException> {
+        let text = self.to_json_string()?;
+        serde_json::from_str(&text).map_err(|e| {
+            JsException::new(Val::global("SyntaxError").new(&[alloc::format!("{e}").into()]))
+        })
+    }
+
+    /// Builds the JS value for a JSON tree directly, without going through text
+    pub fn from_json_value(v: &serde_json::Value) -> Val {
+        use serde_json::Value;
+        match v {
+            Value::Null => Val::null(),
+            Value::Bool(b) => (*b).into(),
+            Value::Number(n) => n.as_f64().unwrap_or(f64::NAN).into(),
+            Value::String(s) => s.into(),
+            Value::Array(items) => {
+                let arr = Val::array();
+                for item in items {
+                    let item = Val::from_json_value(item);
+                    unsafe { emlite_val_push(arr.as_handle(), item.as_handle()) };
+                }
+                arr
+            }
+            Value::Object(map) => {
+                let obj = Val::object();
+                for (k, v) in map {
+                    obj.set(k, Val::from_json_value(v));
+                }
+                obj
+            }
+        }
+    }
+}
+
+#[cfg(feature = "serde_json")]
+impl From<&serde_json::Value> for Val {
+    fn from(v: &serde_json::Value) -> Self {
+        Val::from_json_value(v)
+    }
+}
+
+#[cfg(feature = "serde_json")]
+impl crate::VecElement for &serde_json::Value {}
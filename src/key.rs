@@ -0,0 +1,351 @@
+//! Property keys, and the caches that spare string keys a new JS string per
+//! lookup.
+
+use crate::common::Handle;
+use crate::sys::*;
+use crate::{AsHandle, JsString, Val, WellKnownSymbol};
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::mem::ManuallyDrop;
+
+/// How many names the automatic cache keeps
+const CAPACITY: usize = 64;
+
+/// Longer names bypass the automatic cache
+const MAX_LEN: usize = 64;
+
+/// A property key, borrowed for the duration of a lookup.
+///
+/// Anything [`PropertyKey`] accepts converts to one of these; building one
+/// directly picks the lookup explicitly, e.g. `obj.get(PropKey::Index(3))`.
+#[derive(Clone, Copy, Debug)]
+pub enum PropKey<'a> {
+    /// A property name, looked up in the key cache
+    Str(&'a str),
+    /// An integer index, passed to the host as is rather than as a Number by
+    /// `get` and `set`
+    Index(u32),
+    /// A well-known symbol, like `Symbol.iterator`
+    Symbol(WellKnownSymbol),
+    /// Any other key, like a symbol, used without touching its refcount
+    Val(&'a Val),
+}
+
+impl PropKey<'_> {
+    /// Calls `f` with a handle to the key, creating one if needed
+    pub(crate) fn with_handle<R>(self, f: impl FnOnce(Handle) -> R) -> R {
+        match self {
+            PropKey::Str(s) => with_name(s, f),
+            PropKey::Index(i) => f(Val::from(i).as_handle()),
+            PropKey::Symbol(s) => f(Val::from(s).as_handle()),
+            PropKey::Val(v) => f(v.as_handle()),
+        }
+    }
+}
+
+impl<'a> From<&'a str> for PropKey<'a> {
+    fn from(s: &'a str) -> Self {
+        PropKey::Str(s)
+    }
+}
+
+impl From<u32> for PropKey<'_> {
+    fn from(i: u32) -> Self {
+        PropKey::Index(i)
+    }
+}
+
+impl From<WellKnownSymbol> for PropKey<'_> {
+    fn from(s: WellKnownSymbol) -> Self {
+        PropKey::Symbol(s)
+    }
+}
+
+impl<'a> From<&'a Val> for PropKey<'a> {
+    fn from(v: &'a Val) -> Self {
+        PropKey::Val(v)
+    }
+}
+
+/// A property key accepted by [`Val::get`], [`Val::set`] and [`Val::has`].
+///
+/// String keys are looked up in a small cache of recently used names, so a
+/// name used over and over keeps one JS string rather than creating and
+/// releasing one per lookup. The `no-key-cache` feature turns the cache off.
+/// Non-negative integers are passed to the host as raw indices, values
+/// passed by reference are used as is, without touching their refcount, and
+/// other keys are converted into a `Val` for the lookup. The host has no
+/// index form of `has`, so [`Val::has`] converts indices into a Number too.
+pub trait PropertyKey {
+    /// Calls `f` with the key, valid for the duration of the call
+    #[doc(hidden)]
+    fn with_key<R>(self, f: impl FnOnce(PropKey<'_>) -> R) -> R;
+}
+
+impl PropertyKey for PropKey<'_> {
+    fn with_key<R>(self, f: impl FnOnce(PropKey<'_>) -> R) -> R {
+        f(self)
+    }
+}
+
+impl PropertyKey for &str {
+    fn with_key<R>(self, f: impl FnOnce(PropKey<'_>) -> R) -> R {
+        f(PropKey::Str(self))
+    }
+}
+
+impl PropertyKey for String {
+    fn with_key<R>(self, f: impl FnOnce(PropKey<'_>) -> R) -> R {
+        f(PropKey::Str(&self))
+    }
+}
+
+impl PropertyKey for &String {
+    fn with_key<R>(self, f: impl FnOnce(PropKey<'_>) -> R) -> R {
+        f(PropKey::Str(self))
+    }
+}
+
+impl<T: AsHandle + ?Sized> PropertyKey for &T {
+    fn with_key<R>(self, f: impl FnOnce(PropKey<'_>) -> R) -> R {
+        // Borrows the handle, so dropping it is a no-op
+        let v = ManuallyDrop::new(Val::take_ownership(self.as_handle()));
+        f(PropKey::Val(&v))
+    }
+}
+
+impl PropertyKey for Val {
+    fn with_key<R>(self, f: impl FnOnce(PropKey<'_>) -> R) -> R {
+        f(PropKey::Val(&self))
+    }
+}
+
+impl PropertyKey for JsString {
+    fn with_key<R>(self, f: impl FnOnce(PropKey<'_>) -> R) -> R {
+        f(PropKey::Val(&self))
+    }
+}
+
+impl PropertyKey for WellKnownSymbol {
+    fn with_key<R>(self, f: impl FnOnce(PropKey<'_>) -> R) -> R {
+        f(PropKey::Symbol(self))
+    }
+}
+
+macro_rules! impl_index_key {
+    ($($t:ty),*) => {$(
+        impl PropertyKey for $t {
+            fn with_key<R>(self, f: impl FnOnce(PropKey<'_>) -> R) -> R {
+                match u32::try_from(self) {
+                    Ok(i) => f(PropKey::Index(i)),
+                    Err(_) => f(PropKey::Val(&Val::from(self))),
+                }
+            }
+        }
+    )*};
+}
+
+impl_index_key!(i8, u8, i16, u16, i32, u32, i64, u64, isize, usize);
+
+macro_rules! impl_number_key {
+    ($($t:ty),*) => {$(
+        impl PropertyKey for $t {
+            fn with_key<R>(self, f: impl FnOnce(PropKey<'_>) -> R) -> R {
+                f(PropKey::Val(&Val::from(self)))
+            }
+        }
+    )*};
+}
+
+impl_number_key!(f32, f64);
+
+impl Val {
+    /// Reads a chain of properties, like `a?.b?.[0]?.c` in JS, returning
+    /// undefined as soon as a link is null or undefined.
+    ///
+    /// Names are separated by `.`, and brackets hold either an index or a
+    /// quoted name: `v.path("items[0].label")`, `v.path("headers['x-id']")`.
+    /// A malformed path, e.g. with an empty name as in `a..b` or an unclosed
+    /// bracket, reads as undefined without reading any property.
+    pub fn path(&self, path: &str) -> Val {
+        if path_keys(path).any(|k| k.is_none()) {
+            return Val::undefined();
+        }
+        let mut cur: Option<Val> = None;
+        for key in path_keys(path).flatten() {
+            let v = cur.as_ref().unwrap_or(self);
+            if v.is_null() || v.is_undefined() {
+                return Val::undefined();
+            }
+            cur = Some(v.get(key));
+        }
+        cur.unwrap_or_else(|| self.clone())
+    }
+}
+
+/// Splits `path` into its keys, yielding `None` for a malformed one and
+/// stopping there
+fn path_keys(mut rest: &str) -> impl Iterator<Item = Option<PropKey<'_>>> {
+    let mut first = true;
+    core::iter::from_fn(move || {
+        if rest.is_empty() {
+            return None;
+        }
+        let key = if let Some(inner) = rest.strip_prefix('[') {
+            inner.find(']').filter(|&end| end > 0).map(|end| {
+                let key = &inner[..end];
+                rest = &inner[end + 1..];
+                let quoted = ['"', '\'']
+                    .into_iter()
+                    .find_map(|q| key.strip_prefix(q)?.strip_suffix(q));
+                match quoted {
+                    Some(name) => PropKey::Str(name),
+                    None => key.parse().map_or(PropKey::Str(key), PropKey::Index),
+                }
+            })
+        } else {
+            // Names after the first follow a `.`
+            let name = if first {
+                Some(rest)
+            } else {
+                rest.strip_prefix('.')
+            };
+            name.and_then(|name| {
+                let end = name.find(['.', '[']).unwrap_or(name.len());
+                let (key, tail) = name.split_at(end);
+                rest = tail;
+                (!key.is_empty()).then_some(PropKey::Str(key))
+            })
+        };
+        first = false;
+        if key.is_none() {
+            rest = "";
+        }
+        Some(key)
+    })
+}
+
+/// Returns the JS string `name`, created on first use and kept for the rest
+/// of the program.
+///
+/// Unlike the automatic cache, which only keeps recently used names,
+/// interned names are never released. Passing the result by reference as a
+/// key costs no conversion at all:
+///
+/// ```ignore
+/// let text = emlite::intern("textContent");
+/// for node in nodes {
+///     node.set(text, "");
+/// }
+/// ```
+pub fn intern(name: &'static str) -> &'static Val {
+    let find = |s: &State| s.interned.iter().find(|(n, _)| *n == name).map(|&(_, v)| v);
+    if let Some(v) = with_state(|s| find(s)).flatten() {
+        return v;
+    }
+    let v: &'static Val = Box::leak(Box::new(Val::from(name)));
+    with_state(|s| {
+        find(s).unwrap_or_else(|| {
+            s.interned.push((name, v));
+            v
+        })
+    })
+    .unwrap_or(v)
+}
+
+struct State {
+    // Most recently used last
+    recent: Vec<(Box<str>, Handle)>,
+    interned: Vec<(&'static str, &'static Val)>,
+}
+
+impl State {
+    const fn new() -> Self {
+        State {
+            recent: Vec::new(),
+            interned: Vec::new(),
+        }
+    }
+
+    /// Returns the cached handle for `name`, along with the least recently
+    /// used one if it had to make room
+    fn lookup(&mut self, name: &str) -> (Handle, Option<Handle>) {
+        if let Some(i) = self.recent.iter().rposition(|(n, _)| **n == *name) {
+            let entry = self.recent.remove(i);
+            let h = entry.1;
+            self.recent.push(entry);
+            return (h, None);
+        }
+        let h = unsafe { emlite_val_make_str_unified(name) };
+        let evicted = (self.recent.len() == CAPACITY).then(|| self.recent.remove(0).1);
+        self.recent.push((name.into(), h));
+        (h, evicted)
+    }
+}
+
+fn with_name<R>(name: &str, f: impl FnOnce(Handle) -> R) -> R {
+    if cfg!(not(feature = "no-key-cache"))
+        && name.len() <= MAX_LEN
+        && let Some((h, evicted)) = with_state(|s| s.lookup(name))
+    {
+        // Released outside the cache, in case it runs a finalizer
+        if let Some(e) = evicted {
+            unsafe { emlite_val_dec_ref(e) };
+        }
+        return f(h);
+    }
+    let key = Val::from(name);
+    f(key.as_handle())
+}
+
+// Runs `f` on the cache, or returns `None` if it's already in use further up
+// the stack.
+#[cfg(not(all(not(target_arch = "wasm32"), feature = "mock-host")))]
+fn with_state<R>(f: impl FnOnce(&mut State) -> R) -> Option<R> {
+    use core::cell::UnsafeCell;
+    use core::sync::atomic::{AtomicBool, Ordering};
+
+    struct Global {
+        busy: AtomicBool,
+        state: UnsafeCell<State>,
+    }
+
+    // Access is serialized by `busy`
+    unsafe impl Sync for Global {}
+
+    static STATE: Global = Global {
+        busy: AtomicBool::new(false),
+        state: UnsafeCell::new(State::new()),
+    };
+
+    if STATE.busy.swap(true, Ordering::Acquire) {
+        return None;
+    }
+    let r = f(unsafe { &mut *STATE.state.get() });
+    STATE.busy.store(false, Ordering::Release);
+    Some(r)
+}
+
+// The mock host is per thread, so is its cache
+#[cfg(all(not(target_arch = "wasm32"), feature = "mock-host"))]
+std::thread_local! {
+    static STATE: core::cell::RefCell<State> = const { core::cell::RefCell::new(State::new()) };
+}
+
+#[cfg(all(not(target_arch = "wasm32"), feature = "mock-host"))]
+fn with_state<R>(f: impl FnOnce(&mut State) -> R) -> Option<R> {
+    STATE.with(|s| s.try_borrow_mut().ok().map(|mut s| f(&mut s)))
+}
+
+/// Forgets the cached handles, which belong to a discarded handle table
+#[cfg(all(not(target_arch = "wasm32"), feature = "mock-host"))]
+pub(crate) fn reset() {
+    STATE.with(|s| *s.borrow_mut() = State::new());
+}
+
+/// Returns the handles the automatic cache holds a reference to
+#[cfg(all(not(target_arch = "wasm32"), feature = "mock-host"))]
+pub(crate) fn cached_handles() -> Vec<Handle> {
+    STATE.with(|s| s.borrow().recent.iter().map(|&(_, h)| h).collect())
+}
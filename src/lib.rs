@@ -3,29 +3,99 @@
 #![allow(unused_imports)]
 #![allow(clippy::missing_safety_doc)]
 extern crate alloc;
-
+#[cfg(feature = "std")]
+extern crate std;
+
+mod cache;
+mod cast;
+mod class;
+mod closure;
+mod collections;
 pub mod common;
-
-#[cfg(not(all(target_os = "wasi", target_env = "p2", feature = "wasip2-component")))]
+mod convert;
+mod error;
+mod executor;
+mod function;
+mod future;
+mod iter;
+pub mod json;
+mod key;
+#[cfg(feature = "serde")]
+pub mod serde_val;
+mod snippet;
+mod string;
+mod symbol;
+mod typed_array;
+
+#[cfg(not(any(
+    all(target_os = "wasi", target_env = "p2", feature = "wasip2-component"),
+    all(not(target_arch = "wasm32"), feature = "mock-host")
+)))]
 pub mod env;
-#[cfg(not(all(target_os = "wasi", target_env = "p2", feature = "wasip2-component")))]
+#[cfg(not(any(
+    all(target_os = "wasi", target_env = "p2", feature = "wasip2-component"),
+    all(not(target_arch = "wasm32"), feature = "mock-host")
+)))]
+pub(crate) use crate::env as sys;
+#[cfg(not(any(
+    all(target_os = "wasi", target_env = "p2", feature = "wasip2-component"),
+    all(not(target_arch = "wasm32"), feature = "mock-host")
+)))]
 use crate::env::*;
 
+#[cfg(all(not(target_arch = "wasm32"), feature = "mock-host"))]
+pub mod mockenv;
+#[cfg(all(not(target_arch = "wasm32"), feature = "mock-host"))]
+pub(crate) use crate::mockenv as sys;
+#[cfg(all(not(target_arch = "wasm32"), feature = "mock-host"))]
+use crate::mockenv::*;
+
 #[cfg(all(target_os = "wasi", target_env = "p2", feature = "wasip2-component"))]
 pub mod wasip2env;
 #[cfg(all(target_os = "wasi", target_env = "p2", feature = "wasip2-component"))]
+pub(crate) use crate::wasip2env as sys;
+#[cfg(all(target_os = "wasi", target_env = "p2", feature = "wasip2-component"))]
 use crate::wasip2env::*;
 
 use crate::common::{EMLITE_TARGET, Handle};
 #[cfg(all(target_os = "wasi", target_env = "p2", feature = "wasip2-component"))]
 use alloc::alloc::{Layout, alloc};
+use alloc::borrow::Cow;
 use alloc::boxed::Box;
 use alloc::format;
+use alloc::rc::Rc;
 use alloc::string::String;
 use alloc::string::ToString;
 use alloc::vec::Vec;
 use core::ffi::CStr;
 
+pub use crate::cast::JsCast;
+pub use crate::closure::{Closure, ClosureReturn, IntoClosure};
+pub use crate::collections::VecElement;
+pub use crate::convert::{ConversionError, TryFromVal};
+pub use crate::error::{JsError, JsException};
+pub use crate::executor::{JoinHandle, YieldNow, spawn_local, yield_now};
+pub use crate::function::JsFunction;
+pub use crate::future::{JsFuture, future_to_promise};
+pub use crate::iter::JsIterator;
+pub use crate::key::{PropKey, PropertyKey, intern};
+#[cfg(feature = "serde")]
+pub use crate::serde_val::{from_val, to_val};
+pub use crate::snippet::JsSnippet;
+pub use crate::string::JsString;
+pub use crate::symbol::WellKnownSymbol;
+pub use crate::typed_array::{TypedArrayElement, TypedArrayKind};
+#[cfg(feature = "derive")]
+pub use emlite_derive::{FromVal, IntoVal, JsCast};
+
+#[doc(hidden)]
+pub mod __private {
+    pub use crate::cache::CachedHandle;
+    pub use crate::cast::{cached_global, instanceof_global};
+    pub use alloc::format;
+    pub use alloc::string::String;
+}
+
 #[repr(u32)]
 pub enum EmlitePredefHandles {
     Null = 0,
@@ -45,11 +115,32 @@ macro_rules! eval {
     }};
     ($src: literal $(, $arg:expr)* $(,)?) => {{
         $crate::Val::global("eval").invoke(
-            &[$crate::Val::from(&format!($src, $( $arg ),*)) ]
+            &[$crate::Val::from(&$crate::__private::format!($src, $( $arg ),*)) ]
         )
     }};
 }
 
+/// Compiles a JS function body on first use, returning it as a `&'static`
+/// [`JsSnippet`]. Later uses reuse the compiled function, and arguments are
+/// passed as values rather than spliced into the source.
+///
+/// ```no_run
+/// use emlite::{argv, js};
+/// let sum: i32 = js!(|a, b| "return a + b").call(&argv![1, 2]);
+/// let now: f64 = js!(|| "return Date.now()").call(&[]);
+/// ```
+#[macro_export]
+macro_rules! js {
+    (|| $body:literal) => {
+        $crate::js!(| | $body)
+    };
+    (|$($param:ident),* $(,)?| $body:literal) => {{
+        static SNIPPET: $crate::JsSnippet =
+            $crate::JsSnippet::new(&[$(::core::stringify!($param)),*], $body);
+        &SNIPPET
+    }};
+}
+
 /// A helper macro which packs values into a slice of Val
 #[macro_export]
 macro_rules! argv {
@@ -70,11 +161,32 @@ pub fn init() {
 
 /// A wrapper around a javascript handle
 #[derive(Debug)]
+#[repr(transparent)]
 pub struct Val {
     inner: Handle,
 }
 
 impl Val {
+    /// Wraps a raw handle, taking over the reference it carries: the handle is
+    /// released when the returned Val is dropped
+    #[inline(always)]
+    pub const fn take_ownership(h: Handle) -> Val {
+        Val { inner: h }
+    }
+
+    /// Returns the raw handle without touching its refcount. It stays valid
+    /// only as long as `self` does.
+    #[inline(always)]
+    pub const fn as_handle(&self) -> Handle {
+        self.inner
+    }
+
+    /// Gives up the Val's reference, returning its raw handle for the caller
+    /// (typically the host) to release
+    pub fn into_handle(self) -> Handle {
+        core::mem::ManuallyDrop::new(self).inner
+    }
+
     /// Returns the globalThis object
     pub const fn global_this() -> Val {
         Val {
@@ -83,8 +195,11 @@ impl Val {
     }
 
     /// Gets the property `prop`
-    pub fn get<T: Into<Val>>(&self, prop: T) -> Val {
-        let h = unsafe { emlite_val_get(self.as_handle(), prop.into().as_handle()) };
+    pub fn get<K: PropertyKey>(&self, prop: K) -> Val {
+        let h = prop.with_key(|k| match k {
+            PropKey::Index(i) => unsafe { emlite_val_get_index(self.as_handle(), i) },
+            k => k.with_handle(|k| unsafe { emlite_val_get(self.as_handle(), k) }),
+        });
         Val::take_ownership(h)
     }
 
@@ -134,19 +249,19 @@ impl Val {
     }
 
     /// Set the underlying js object property `prop` to `val`
-    pub fn set<K: Into<Val>, V: Into<Val>>(&self, prop: K, val: V) {
-        unsafe {
-            emlite_val_set(
-                self.as_handle(),
-                prop.into().as_handle(),
-                val.into().as_handle(),
-            )
-        };
+    pub fn set<K: PropertyKey, V: Into<Val>>(&self, prop: K, val: V) {
+        let val = val.into();
+        prop.with_key(|k| match k {
+            PropKey::Index(i) => unsafe {
+                emlite_val_set_index(self.as_handle(), i, val.as_handle())
+            },
+            k => k.with_handle(|k| unsafe { emlite_val_set(self.as_handle(), k, val.as_handle()) }),
+        });
     }
 
     /// Checks whether a property `prop` exists
-    pub fn has<T: Into<Val>>(&self, prop: T) -> bool {
-        unsafe { emlite_val_has(self.as_handle(), prop.into().as_handle()) }
+    pub fn has<K: PropertyKey>(&self, prop: K) -> bool {
+        prop.with_key(|k| k.with_handle(|k| unsafe { emlite_val_has(self.as_handle(), k) }))
     }
 
     /// Checks whether a non-inherited property `prop` exists
@@ -156,12 +271,17 @@ impl Val {
 
     /// Gets the typeof the underlying js object
     pub fn type_of(&self) -> String {
+        String::from(self.typeof_str())
+    }
+
+    /// Like [`Val::type_of`], without allocating
+    pub(crate) fn typeof_str(&self) -> &'static str {
         unsafe { emlite_val_typeof_unified(self.as_handle()) }
     }
 
     /// Gets the element at index `idx`. Assumes the underlying js type is indexable
-    pub fn at<T: Into<Val>>(&self, idx: T) -> Val {
-        Val::take_ownership(unsafe { emlite_val_get(self.as_handle(), idx.into().as_handle()) })
+    pub fn at<K: PropertyKey>(&self, idx: K) -> Val {
+        self.get(idx)
     }
 
     /// Converts the underlying js array to a Vec of V
@@ -169,58 +289,69 @@ impl Val {
         let len = self.get("length").as_::<usize>();
         let mut v: Vec<V> = Vec::with_capacity(len);
         for i in 0..len {
-            v.push(self.at::<i32>(i as _).as_::<V>());
+            v.push(self.get(i).as_::<V>());
         }
         v
     }
 
+    /// Views `args` as the handles they wrap, to pass to the host in place
+    fn handles(args: &[Val]) -> &[Handle] {
+        // Sound since Val is a transparent wrapper around a Handle
+        unsafe { core::slice::from_raw_parts(args.as_ptr() as *const Handle, args.len()) }
+    }
+
     /// Calls the method `f` with `args`, can return an undefined js value
     pub fn call(&self, f: &str, args: &[Val]) -> Val {
-        unsafe {
-            let arr = Val::take_ownership(emlite_val_new_array());
-            for arg in args {
-                emlite_val_push(arr.as_handle(), arg.as_handle());
-            }
-            Val::take_ownership(emlite_val_obj_call_unified(
-                self.as_handle(),
-                f,
-                arr.as_handle(),
-            ))
-        }
+        let h = unsafe { emlite_val_obj_call_unified(self.as_handle(), f, Val::handles(args)) };
+        Val::take_ownership(h)
     }
 
     /// Calls the object's constructor with `args` constructing a new object
     pub fn new(&self, args: &[Val]) -> Val {
-        unsafe {
-            let arr = Val::take_ownership(emlite_val_new_array());
-            for arg in args {
-                emlite_val_push(arr.as_handle(), arg.as_handle());
-            }
-            Val::take_ownership(emlite_val_construct_new(self.as_handle(), arr.as_handle()))
-        }
+        let h = unsafe { emlite_val_construct_new_unified(self.as_handle(), Val::handles(args)) };
+        Val::take_ownership(h)
     }
 
     /// Invokes the function object with `args`, can return an undefined js value
     pub fn invoke(&self, args: &[Val]) -> Val {
-        unsafe {
-            let arr = Val::take_ownership(emlite_val_new_array());
-            for arg in args {
-                emlite_val_push(arr.as_handle(), arg.as_handle());
-            }
-            Val::take_ownership(emlite_val_func_call(self.as_handle(), arr.as_handle()))
-        }
+        let h = unsafe { emlite_val_func_call_unified(self.as_handle(), Val::handles(args)) };
+        Val::take_ownership(h)
+    }
+
+    /// Calls the method `f` with `args`, reporting a thrown exception as `Err`
+    pub fn try_call(&self, f: &str, args: &[Val]) -> Result<Val, JsException> {
+        unsafe { emlite_val_obj_call_try_unified(self.as_handle(), f, Val::handles(args)) }
+            .map(Val::take_ownership)
+            .map_err(|e| JsException::new(Val::take_ownership(e)))
+    }
+
+    /// Calls the object's constructor with `args`, reporting a thrown exception as `Err`
+    pub fn try_new(&self, args: &[Val]) -> Result<Val, JsException> {
+        unsafe { emlite_val_construct_new_try_unified(self.as_handle(), Val::handles(args)) }
+            .map(Val::take_ownership)
+            .map_err(|e| JsException::new(Val::take_ownership(e)))
+    }
+
+    /// Invokes the function object with `args`, reporting a thrown exception as `Err`
+    pub fn try_invoke(&self, args: &[Val]) -> Result<Val, JsException> {
+        unsafe { emlite_val_func_call_try_unified(self.as_handle(), Val::handles(args)) }
+            .map(Val::take_ownership)
+            .map_err(|e| JsException::new(Val::take_ownership(e)))
     }
 
     /// Creates a JS function from a function pointer `f` and `data` handle
     /// by packing them into a shared `EmliteCbPack` and returning the JS
     /// function Val. Works across languages in the same module.
+    ///
+    /// `data` is pinned for the lifetime of the JS function. When the host
+    /// finalizes the function it calls `f` once with a null `argv` handle and
+    /// then releases `data`; `f` should free whatever `data` refers to.
     pub fn make_fn_raw(f: extern "C" fn(Handle, Handle) -> Handle, data: Handle) -> Val {
         let idx = unsafe { emlite_register_callback_unified(f) };
+        // Pin user data for the lifetime of the JS function
+        unsafe { emlite_val_inc_ref(data) };
         #[cfg(all(target_os = "wasi", target_env = "p2", feature = "wasip2-component"))]
         unsafe {
-            // Pin user data for the lifetime of the JS function
-            emlite_val_inc_ref(data);
-
             // Allocate pack using module allocator so C can free it
             #[repr(C)]
             struct Pack {
@@ -246,28 +377,15 @@ impl Val {
         }
     }
 
-    /// Creates a js function from a Rust closure and returns a Val
-    pub fn make_fn<F: FnMut(&[Val]) -> Val>(cb: F) -> Val {
-        extern "C" fn shim(args: Handle, data: Handle) -> Handle {
-            let v = Val::take_ownership(args);
-            let vals: Vec<Val> = v.to_vec();
-            // data is a handle to a BigInt that encodes the pointer-sized value
-            let ptr_u = unsafe { emlite_val_get_value_biguint(data) } as usize;
-            let a = ptr_u as *mut Box<dyn FnMut(&[Val]) -> Val>;
-            let f: &mut (dyn FnMut(&[Val]) -> Val) = unsafe { &mut **a };
-            f(&vals).as_handle()
-        }
-        #[allow(clippy::type_complexity)]
-        let a: *mut Box<dyn FnMut(&[Val]) -> Val> = Box::into_raw(Box::new(Box::new(cb)));
-        // Store pointer-sized value as BigInt inside a Val
-        let data = Val::from(a as usize);
-        unsafe {
-            emlite_val_inc_ref(data.as_handle());
-        }
-        Self::make_fn_raw(shim, data.as_handle())
+    /// Creates a js function from a Rust closure and returns a Val.
+    /// The closure lives until the host finalizes the function, see [`Closure`]
+    /// for explicit control over its lifetime.
+    pub fn make_fn<F: FnMut(&[Val]) -> Val + 'static>(cb: F) -> Val {
+        Closure::new(cb).forget()
     }
 
     /// Awaits the invoked function object
+    #[deprecated(note = "returns another Promise without awaiting; `.await` the Val instead")]
     pub fn await_(&self) -> Val {
         eval!(
             r#"
@@ -281,11 +399,9 @@ impl Val {
         )
     }
 
-    /// Decrements the refcount of the underlying handle
+    /// Decrements the refcount of the underlying handle, same as dropping `v`
     pub fn delete(v: Val) {
-        unsafe {
-            emlite_val_dec_ref(v.as_handle());
-        }
+        drop(v)
     }
 
     /// Throws a js object represented by Val
@@ -312,6 +428,14 @@ impl Val {
         unsafe { emlite_val_is_string(self.as_handle()) }
     }
 
+    pub fn is_symbol(&self) -> bool {
+        self.typeof_str() == "symbol"
+    }
+
+    pub fn is_bigint(&self) -> bool {
+        self.typeof_str() == "bigint"
+    }
+
     pub fn is_null(&self) -> bool {
         self.as_handle() == EmlitePredefHandles::Null as u32
     }
@@ -341,14 +465,49 @@ impl Val {
         Val::from(utf16)
     }
 
+    /// Returns the string's contents, or `None` if this isn't a string
+    pub fn as_string(&self) -> Option<String> {
+        let mut s = String::new();
+        self.read_string_into(&mut s).then_some(s)
+    }
+
+    /// Replaces `buf`'s contents with this string, reusing its allocation.
+    ///
+    /// Returns false, leaving `buf` empty, if this isn't a string. Reading many
+    /// strings through one buffer avoids an allocation per read.
+    pub fn read_string_into(&self, buf: &mut String) -> bool {
+        if !self.is_string() {
+            buf.clear();
+            return false;
+        }
+        unsafe { emlite_val_get_value_string_into_unified(self.as_handle(), buf) }
+    }
+
     /// Extracts UTF-16 data as Option<Vec<u16>>
     pub fn to_utf16(&self) -> Option<Vec<u16>> {
-        self.as_::<Option<Vec<u16>>>()
+        let mut units = Vec::new();
+        self.read_utf16_into(&mut units).then_some(units)
+    }
+
+    /// Replaces `buf`'s contents with this string's UTF-16 code units, reusing
+    /// its allocation. Unpaired surrogates are kept as is.
+    ///
+    /// Returns false, leaving `buf` empty, if this isn't a string.
+    pub fn read_utf16_into(&self, buf: &mut Vec<u16>) -> bool {
+        if !self.is_string() {
+            buf.clear();
+            return false;
+        }
+        unsafe { emlite_val_get_value_string_utf16_into_unified(self.as_handle(), buf) }
     }
 
     /// Extracts UTF-16 data, returning error if null or if self is error
     pub fn to_utf16_result(&self) -> Result<Vec<u16>, Val> {
-        self.as_::<Result<Vec<u16>, Val>>()
+        if self.is_error() {
+            return Err(self.clone());
+        }
+        self.to_utf16()
+            .ok_or_else(|| Val::global("Error").new(&["Null UTF-16 string".into()]))
     }
 
     /// Converts UTF-16 Vec<u16> to String, if possible
@@ -479,13 +638,47 @@ impl From<&[u16]> for Val {
 
 impl From<Vec<u16>> for Val {
     fn from(s: Vec<u16>) -> Self {
-        Val::take_ownership(unsafe { emlite_val_make_str_utf16_unified(&s) })
+        Val::from(s.as_slice())
     }
 }
 
 impl From<&Vec<u16>> for Val {
     fn from(s: &Vec<u16>) -> Self {
-        Val::take_ownership(unsafe { emlite_val_make_str_utf16_unified(s) })
+        Val::from(s.as_slice())
+    }
+}
+
+impl From<char> for Val {
+    fn from(c: char) -> Self {
+        Val::from(c.encode_utf8(&mut [0; 4]) as &str)
+    }
+}
+
+impl From<Cow<'_, str>> for Val {
+    fn from(s: Cow<'_, str>) -> Self {
+        Val::from(&*s)
+    }
+}
+
+impl From<Box<str>> for Val {
+    fn from(s: Box<str>) -> Self {
+        Val::from(&*s)
+    }
+}
+
+impl From<Rc<str>> for Val {
+    fn from(s: Rc<str>) -> Self {
+        Val::from(&*s)
+    }
+}
+
+impl<T: IntoVal> From<Option<T>> for Val {
+    /// `None` converts to `undefined`
+    fn from(v: Option<T>) -> Self {
+        match v {
+            Some(v) => v.into_val(),
+            None => Val::undefined(),
+        }
     }
 }
 
@@ -504,9 +697,9 @@ impl Drop for Val {
 impl Clone for Val {
     fn clone(&self) -> Val {
         unsafe {
-            emlite_val_inc_ref(self.as_handle());
+            emlite_val_inc_ref(self.inner);
         }
-        Val::take_ownership(self.as_handle())
+        Val { inner: self.inner }
     }
 }
 
@@ -542,11 +735,6 @@ impl Console {
     pub fn info(&self, args: &[Val]) {
         self.val.call("info", args);
     }
-
-    /// Returns the underlying handle of the console
-    pub fn as_handle(&self) -> Handle {
-        self.val.as_handle()
-    }
 }
 
 impl Deref for Console {
@@ -565,10 +753,12 @@ impl DerefMut for Console {
 
 impl From<Console> for Val {
     fn from(val: Console) -> Self {
-        Val::take_ownership(val.as_handle())
+        val.val
     }
 }
 
+impl VecElement for Console {}
+
 use core::cmp::Ordering;
 use core::ops::Not;
 
@@ -624,557 +814,328 @@ impl AsMut<Val> for Val {
     }
 }
 
-pub trait FromVal: Sized {
-    /// Creates a Val object from another
-    fn from_val(v: &Val) -> Self;
-    /// Takes the ownership of a handle
-    fn take_ownership(v: Handle) -> Self;
+/// Gives access to the raw JS handle behind a value.
+///
+/// The handle is borrowed: its refcount isn't touched, and it stays valid only
+/// as long as `self` does.
+pub trait AsHandle {
     /// Returns the raw js handle
     fn as_handle(&self) -> Handle;
 }
 
-impl FromVal for Val {
-    fn from_val(v: &Val) -> Self {
-        unsafe {
-            emlite_val_inc_ref(v.inner);
-        }
-        Val {
-            inner: v.as_handle(),
-        }
-    }
-    fn take_ownership(v: Handle) -> Self {
-        Val { inner: v }
-    }
+impl AsHandle for Val {
     #[inline(always)]
     fn as_handle(&self) -> Handle {
         self.inner
     }
 }
 
-impl FromVal for Result<Val, Val> {
-    fn from_val(v: &Val) -> Self {
-        unsafe {
-            emlite_val_inc_ref(v.inner);
-        }
-        if v.is_error() {
-            Err(v.clone())
-        } else {
-            Ok(v.clone())
-        }
+impl AsHandle for Console {
+    fn as_handle(&self) -> Handle {
+        self.val.as_handle()
+    }
+}
+
+impl<T: AsHandle + ?Sized> AsHandle for &T {
+    fn as_handle(&self) -> Handle {
+        (**self).as_handle()
     }
-    fn take_ownership(v: Handle) -> Self {
-        let temp = Val::take_ownership(v);
-        if temp.is_error() { Err(temp) } else { Ok(temp) }
+}
+
+impl<T: AsHandle> AsHandle for Option<T> {
+    /// `None` is `undefined`
+    fn as_handle(&self) -> Handle {
+        match self {
+            Some(v) => v.as_handle(),
+            None => EmlitePredefHandles::Undefined as u32,
+        }
     }
-    #[inline(always)]
+}
+
+impl<T: AsHandle, E: AsHandle> AsHandle for Result<T, E> {
     fn as_handle(&self) -> Handle {
         match self {
-            Ok(ok) => ok.as_handle(),
+            Ok(v) => v.as_handle(),
             Err(e) => e.as_handle(),
         }
     }
 }
 
-impl FromVal for bool {
+/// A conversion into a JS value, implemented for everything `Val` can be
+/// created `From`.
+pub trait IntoVal {
+    /// Converts into a new Val, which owns its own reference
+    fn into_val(self) -> Val;
+}
+
+impl<T: Into<Val>> IntoVal for T {
+    #[inline]
+    fn into_val(self) -> Val {
+        self.into()
+    }
+}
+
+/// A conversion out of a JS value, used by [`Val::as_`].
+///
+/// Conversions are lenient, the way the host's getters coerce: `as_::<u8>()`
+/// on `"x"` still yields a number. See [`TryFromVal`] for checked ones.
+///
+/// `from_val` borrows its argument, so the caller keeps its reference: types
+/// holding on to the value (like `Val`) take a reference of their own, and
+/// plain data copies what it needs and holds none.
+pub trait FromVal: Sized {
+    /// Converts a borrowed value
+    fn from_val(v: &Val) -> Self;
+    /// Converts a handle, taking over the reference it carries. The default
+    /// releases it once converted.
+    fn take_ownership(h: Handle) -> Self {
+        Self::from_val(&Val::take_ownership(h))
+    }
+    /// Converts a value other than null or undefined for `Option<Self>`,
+    /// returning `None` if it has the wrong JS type. The default accepts
+    /// anything.
+    #[doc(hidden)]
+    fn option_from_val(v: &Val) -> Option<Self> {
+        Some(Self::from_val(v))
+    }
+    /// Converts a value to a `Vec` of this type, which backs `FromVal for
+    /// Vec<T>`, returning `None` if it isn't a sequence. The default reads an
+    /// array, or any other object with a length.
+    #[doc(hidden)]
+    fn vec_from_val(v: &Val) -> Option<Vec<Self>> {
+        crate::iter::is_object(v).then(|| v.to_vec())
+    }
+}
+
+impl FromVal for Val {
     fn from_val(v: &Val) -> Self {
-        unsafe { !emlite_val_not_unified(v.as_handle()) }
+        v.clone()
     }
-    fn take_ownership(v: Handle) -> Self {
-        Self::from_val(&Val::take_ownership(v))
+    fn take_ownership(h: Handle) -> Self {
+        Val::take_ownership(h)
     }
-    fn as_handle(&self) -> Handle {
-        if *self {
-            EmlitePredefHandles::True as u32
+}
+
+impl FromVal for Result<Val, Val> {
+    fn from_val(v: &Val) -> Self {
+        if v.is_error() {
+            Err(v.clone())
         } else {
-            EmlitePredefHandles::False as u32
+            Ok(v.clone())
         }
     }
+    fn take_ownership(h: Handle) -> Self {
+        let v = Val::take_ownership(h);
+        if v.is_error() { Err(v) } else { Ok(v) }
+    }
 }
 
-impl FromVal for Option<bool> {
+impl FromVal for bool {
     fn from_val(v: &Val) -> Self {
-        unsafe {
-            if v.is_error() || v.is_null() || v.is_undefined() {
-                None
-            } else {
-                Some(!emlite_val_not_unified(v.as_handle()))
-            }
-        }
+        unsafe { !emlite_val_not_unified(v.as_handle()) }
     }
-    fn take_ownership(v: Handle) -> Self {
-        let temp = Val::take_ownership(v);
-        if temp.is_error() || temp.is_null() || temp.is_undefined() {
-            None
-        } else {
-            unsafe { Some(!emlite_val_not_unified(v)) }
-        }
-    }
-    fn as_handle(&self) -> Handle {
-        match self {
-            Some(ok) => {
-                if *ok {
-                    EmlitePredefHandles::True as u32
-                } else {
-                    EmlitePredefHandles::False as u32
-                }
-            }
-            None => EmlitePredefHandles::Undefined as u32,
-        }
+    fn option_from_val(v: &Val) -> Option<Self> {
+        v.is_bool().then(|| bool::from_val(v))
     }
 }
 
 impl FromVal for Result<bool, Val> {
     fn from_val(v: &Val) -> Self {
-        unsafe {
-            if v.is_error() {
-                Err(v.clone())
-            } else {
-                Ok(!emlite_val_not_unified(v.as_handle()))
-            }
-        }
-    }
-    fn take_ownership(v: Handle) -> Self {
-        let temp = Val::take_ownership(v);
-        if temp.is_error() {
-            Err(temp)
+        if v.is_error() {
+            Err(v.clone())
         } else {
-            unsafe { Ok(!emlite_val_not_unified(v)) }
-        }
-    }
-    fn as_handle(&self) -> Handle {
-        match self {
-            Ok(ok) => {
-                if *ok {
-                    EmlitePredefHandles::True as u32
-                } else {
-                    EmlitePredefHandles::False as u32
-                }
-            }
-            Err(e) => e.as_handle(),
+            Ok(bool::from_val(v))
         }
     }
 }
 
-macro_rules! impl_int {
-    ($($t:ty),*) => {$(
-        impl FromVal for $t {
-            fn from_val(v: &Val) -> Self {
-                unsafe {
-                    emlite_val_get_value_int(v.as_handle()) as Self
-                }
-            }
-            fn take_ownership(v: Handle) -> Self {
-                unsafe { emlite_val_get_value_int(v) as Self }
-            }
-            fn as_handle(&self) -> Handle {
-                0
-            }
-        }
-        impl FromVal for Option<$t> {
-            fn from_val(v: &Val) -> Self {
-                unsafe {
-                    if !v.is_number() {
-                        None
-                    } else {
-                        Some(emlite_val_get_value_int(v.as_handle()) as $t)
-                    }
-                }
-            }
-            fn take_ownership(v: Handle) -> Self {
-                let temp = Val::take_ownership(v);
-                if !temp.is_number() {
-                    None
-                } else {
-                    unsafe { Some(emlite_val_get_value_int(v) as $t) }
-                }
-            }
-            fn as_handle(&self) -> Handle {
-                0
-            }
-        }
-        impl FromVal for Result<$t, Val> {
-            fn from_val(v: &Val) -> Self {
-                unsafe {
-                    if v.is_error() {
-                        Err(v.clone())
-                    } else {
-                        Ok(emlite_val_get_value_int(v.as_handle()) as $t)
-                    }
-                }
-            }
-            fn take_ownership(v: Handle) -> Self {
-                let temp = Val::take_ownership(v);
-                if temp.is_error() {
-                    Err(temp)
-                } else {
-                    unsafe { Ok(emlite_val_get_value_int(v) as $t) }
-                }
-            }
-            fn as_handle(&self) -> Handle {
-                0
-            }
-        }
-    )*}
+fn get_value_int(v: &Val) -> i32 {
+    unsafe { emlite_val_get_value_int(v.as_handle()) as _ }
 }
 
-macro_rules! impl_uint {
-    ($($t:ty),*) => {$(
-        impl FromVal for $t {
-            fn from_val(v: &Val) -> Self {
-                unsafe {
-                    emlite_val_get_value_uint(v.as_handle()) as Self
-                }
-            }
-            fn take_ownership(v: Handle) -> Self {
-                unsafe { emlite_val_get_value_uint(v) as Self }
-            }
-            fn as_handle(&self) -> Handle {
-                0
-            }
-        }
-        impl FromVal for Option<$t> {
-            fn from_val(v: &Val) -> Self {
-                unsafe {
-                    if !v.is_number() {
-                        None
-                    } else {
-                        Some(emlite_val_get_value_uint(v.as_handle()) as $t)
-                    }
-                }
-            }
-            fn take_ownership(v: Handle) -> Self {
-                let temp = Val::take_ownership(v);
-                if !temp.is_number() {
-                    None
-                } else {
-                    unsafe { Some(emlite_val_get_value_uint(v) as $t) }
-                }
-            }
-            fn as_handle(&self) -> Handle {
-                0
-            }
-        }
-        impl FromVal for Result<$t, Val> {
-            fn from_val(v: &Val) -> Self {
-                unsafe {
-                    if v.is_error() {
-                        Err(v.clone())
-                    } else {
-                        Ok(emlite_val_get_value_uint(v.as_handle()) as $t)
-                    }
-                }
-            }
-            fn take_ownership(v: Handle) -> Self {
-                let temp = Val::take_ownership(v);
-                if temp.is_error() {
-                    Err(temp)
-                } else {
-                    unsafe { Ok(emlite_val_get_value_uint(v) as $t) }
-                }
-            }
-            fn as_handle(&self) -> Handle {
-                0
-            }
-        }
-    )*}
+fn get_value_uint(v: &Val) -> u32 {
+    unsafe { emlite_val_get_value_uint(v.as_handle()) as _ }
 }
 
-macro_rules! impl_bigint {
-    ($($t:ty),*) => {$(
-        impl FromVal for $t {
-            fn from_val(v: &Val) -> Self {
-                unsafe {
-                    emlite_val_get_value_bigint(v.as_handle()) as Self
-                }
-            }
-            fn take_ownership(v: Handle) -> Self {
-                unsafe { emlite_val_get_value_bigint(v) as Self }
-            }
-            fn as_handle(&self) -> Handle {
-                0
-            }
-        }
-        impl FromVal for Option<$t> {
-            fn from_val(v: &Val) -> Self {
-                unsafe {
-                    if !v.is_number() {
-                        None
-                    } else {
-                        Some(emlite_val_get_value_bigint(v.as_handle()) as $t)
-                    }
-                }
-            }
-            fn take_ownership(v: Handle) -> Self {
-                let temp = Val::take_ownership(v);
-                if !temp.is_number() {
-                    None
-                } else {
-                    unsafe { Some(emlite_val_get_value_bigint(v) as $t) }
-                }
-            }
-            fn as_handle(&self) -> Handle {
-                0
-            }
-        }
-        impl FromVal for Result<$t, Val> {
-            fn from_val(v: &Val) -> Self {
-                unsafe {
-                    if v.is_error() {
-                        Err(v.clone())
-                    } else {
-                        Ok(emlite_val_get_value_bigint(v.as_handle()) as $t)
-                    }
-                }
-            }
-            fn take_ownership(v: Handle) -> Self {
-                let temp = Val::take_ownership(v);
-                if temp.is_error() {
-                    Err(temp)
-                } else {
-                    unsafe { Ok(emlite_val_get_value_bigint(v) as $t) }
-                }
-            }
-            fn as_handle(&self) -> Handle {
-                0
-            }
-        }
-    )*}
+fn get_value_double(v: &Val) -> f64 {
+    unsafe { emlite_val_get_value_double(v.as_handle()) }
 }
 
-macro_rules! impl_biguint {
-    ($($t:ty),*) => {$(
-        impl FromVal for $t {
-            fn from_val(v: &Val) -> Self {
-                unsafe {
-                    emlite_val_get_value_biguint(v.as_handle()) as Self
-                }
-            }
-            fn take_ownership(v: Handle) -> Self {
-                unsafe { emlite_val_get_value_biguint(v) as Self }
-            }
-            fn as_handle(&self) -> Handle {
-                0
-            }
-        }
-        impl FromVal for Option<$t> {
-            fn from_val(v: &Val) -> Self {
-                unsafe {
-                    if !v.is_number() {
-                        None
-                    } else {
-                        Some(emlite_val_get_value_biguint(v.as_handle()) as $t)
-                    }
-                }
-            }
-            fn take_ownership(v: Handle) -> Self {
-                let temp = Val::take_ownership(v);
-                if !temp.is_number() {
-                    None
-                } else {
-                    unsafe { Some(emlite_val_get_value_biguint(v) as $t) }
-                }
-            }
-            fn as_handle(&self) -> Handle {
-                0
-            }
+/// Reads a Number or BigInt as a 64-bit integer, the lenient conversion behind
+/// `as_::<i64>()`: Numbers saturate and BigInts wrap
+fn get_value_i64(v: &Val) -> i64 {
+    unsafe {
+        if v.is_number() {
+            emlite_val_get_value_double(v.as_handle()) as i64
+        } else {
+            emlite_val_get_value_bigint(v.as_handle())
         }
-        impl FromVal for Result<$t, Val> {
-            fn from_val(v: &Val) -> Self {
-                unsafe {
-                    if v.is_error() {
-                        Err(v.clone())
-                    } else {
-                        Ok(emlite_val_get_value_biguint(v.as_handle()) as $t)
-                    }
-                }
-            }
-            fn take_ownership(v: Handle) -> Self {
-                let temp = Val::take_ownership(v);
-                if temp.is_error() {
-                    Err(temp)
-                } else {
-                    unsafe { Ok(emlite_val_get_value_biguint(v) as $t) }
-                }
-            }
-            fn as_handle(&self) -> Handle {
-                0
-            }
+    }
+}
+
+/// The unsigned counterpart of [`get_value_i64`]
+fn get_value_u64(v: &Val) -> u64 {
+    unsafe {
+        if v.is_number() {
+            emlite_val_get_value_double(v.as_handle()) as u64
+        } else {
+            emlite_val_get_value_biguint(v.as_handle())
         }
-    )*}
+    }
 }
 
-impl_int!(i8, i16, i32);
-impl_uint!(u8, u16, u32);
-impl_bigint!(i64, isize);
-impl_biguint!(u64, usize);
+fn is_number(v: &Val) -> bool {
+    v.is_number()
+}
+
+fn is_number_or_bigint(v: &Val) -> bool {
+    v.is_number() || v.is_bigint()
+}
 
-macro_rules! impl_float {
-    ($($t:ty),*) => {$(
+/// Implements `FromVal` for numbers read with `$get`, along with their
+/// `Option`s (`None` unless `$accepts` the value) and `Result`s
+macro_rules! impl_number {
+    ($get:ident, $accepts:ident: $($t:ty),*) => {$(
         impl FromVal for $t {
             fn from_val(v: &Val) -> Self {
-                unsafe { emlite_val_get_value_double(v.as_handle()) as Self }
-            }
-            fn take_ownership(v: Handle) -> Self {
-                unsafe { emlite_val_get_value_double(v) as Self }
-            }
-            fn as_handle(&self) -> Handle {
-                0
-            }
-        }
-        impl FromVal for Option<$t> {
-            fn from_val(v: &Val) -> Self {
-                unsafe {
-                    if !v.is_number() {
-                        None
-                    } else {
-                        Some(emlite_val_get_value_double(v.as_handle()) as $t)
-                    }
-                }
-            }
-            fn take_ownership(v: Handle) -> Self {
-                let temp = Val::take_ownership(v);
-                if !temp.is_number() {
-                    None
-                } else {
-                    unsafe { Some(emlite_val_get_value_double(v) as $t) }
-                }
+                $get(v) as Self
             }
-            fn as_handle(&self) -> Handle {
-                0
+            fn option_from_val(v: &Val) -> Option<Self> {
+                $accepts(v).then(|| $get(v) as Self)
             }
         }
         impl FromVal for Result<$t, Val> {
             fn from_val(v: &Val) -> Self {
-                unsafe {
-                    if v.is_error() {
-                        Err(v.clone())
-                    } else {
-                        Ok(emlite_val_get_value_double(v.as_handle()) as $t)
-                    }
-                }
-            }
-            fn take_ownership(v: Handle) -> Self {
-                let temp = Val::take_ownership(v);
-                if temp.is_error() {
-                    Err(temp)
+                if v.is_error() {
+                    Err(v.clone())
                 } else {
-                    unsafe { Ok(emlite_val_get_value_double(v) as $t) }
+                    Ok($get(v) as $t)
                 }
             }
-            fn as_handle(&self) -> Handle {
-                0
-            }
         }
     )*}
 }
 
-impl_float!(f32, f64);
+impl_number!(get_value_int, is_number: i8, i16, i32);
+impl_number!(get_value_uint, is_number: u8, u32);
+impl_number!(get_value_i64, is_number_or_bigint: i64, isize);
+impl_number!(get_value_u64, is_number_or_bigint: u64, usize);
+impl_number!(get_value_double, is_number: f32, f64);
 
-impl FromVal for () {
-    fn from_val(_v: &Val) -> Self {}
-    fn take_ownership(_v: Handle) -> Self {}
-    fn as_handle(&self) -> Handle {
-        EmlitePredefHandles::Undefined as u32
+impl FromVal for u16 {
+    fn from_val(v: &Val) -> Self {
+        get_value_uint(v) as u16
+    }
+    fn option_from_val(v: &Val) -> Option<Self> {
+        is_number(v).then(|| get_value_uint(v) as u16)
+    }
+    /// Strings read as their UTF-16 code units, since that's what a
+    /// `Vec<u16>` converts to
+    fn vec_from_val(v: &Val) -> Option<Vec<Self>> {
+        v.to_utf16()
+            .or_else(|| crate::iter::is_object(v).then(|| v.to_vec()))
     }
 }
 
-impl FromVal for Option<String> {
+impl FromVal for Result<u16, Val> {
     fn from_val(v: &Val) -> Self {
-        unsafe {
-            if !v.is_string() {
-                return None;
-            }
-            emlite_val_get_value_string_unified(v.as_handle())
+        if v.is_error() {
+            Err(v.clone())
+        } else {
+            Ok(get_value_uint(v) as u16)
         }
     }
-    fn take_ownership(v: Handle) -> Self {
-        unsafe {
-            if !emlite_val_is_string(v) {
-                return None;
-            }
-            emlite_val_get_value_string_unified(v)
-        }
+}
+
+impl FromVal for () {
+    fn from_val(_v: &Val) -> Self {}
+}
+
+impl FromVal for String {
+    /// Non-string values convert to an empty string; use [`Val::as_string`] to
+    /// tell them apart
+    fn from_val(v: &Val) -> Self {
+        v.as_string().unwrap_or_default()
     }
-    fn as_handle(&self) -> Handle {
-        0
+    fn option_from_val(v: &Val) -> Option<Self> {
+        v.as_string()
     }
 }
 
-impl FromVal for Result<String, Val> {
+impl FromVal for char {
+    /// The first character of a string, or `'\0'` for empty strings and
+    /// non-string values
     fn from_val(v: &Val) -> Self {
-        unsafe {
-            if v.is_error() {
-                Err(v.clone())
-            } else if !v.is_string() {
-                Err(Val::global("Error").new(&["Expected string".into()]))
-            } else {
-                match emlite_val_get_value_string_unified(v.as_handle()) {
-                    Some(s) => Ok(s),
-                    None => Err(Val::global("Error").new(&["Null string".into()])),
-                }
-            }
-        }
+        v.as_string()
+            .and_then(|s| s.chars().next())
+            .unwrap_or_default()
     }
-    fn take_ownership(v: Handle) -> Self {
-        unsafe {
-            let temp = Val::take_ownership(v);
-            if temp.is_error() {
-                Err(temp)
-            } else if !temp.is_string() {
-                Err(Val::global("Error").new(&["Expected string".into()]))
-            } else {
-                match emlite_val_get_value_string_unified(v) {
-                    Some(s) => Ok(s),
-                    None => Err(Val::global("Error").new(&["Null string".into()])),
-                }
-            }
-        }
+    fn option_from_val(v: &Val) -> Option<Self> {
+        v.as_string().map(|s| s.chars().next().unwrap_or_default())
     }
-    fn as_handle(&self) -> Handle {
-        0
+}
+
+impl FromVal for Box<str> {
+    fn from_val(v: &Val) -> Self {
+        String::from_val(v).into()
+    }
+    fn option_from_val(v: &Val) -> Option<Self> {
+        v.as_string().map(Into::into)
     }
 }
 
-impl FromVal for Option<Vec<u16>> {
+impl FromVal for Rc<str> {
     fn from_val(v: &Val) -> Self {
-        unsafe { emlite_val_get_value_string_utf16_unified(v.as_handle()) }
+        String::from_val(v).into()
     }
-    fn take_ownership(v: Handle) -> Self {
-        unsafe { emlite_val_get_value_string_utf16_unified(v) }
+    fn option_from_val(v: &Val) -> Option<Self> {
+        v.as_string().map(Into::into)
     }
-    fn as_handle(&self) -> Handle {
-        0
+}
+
+impl FromVal for Cow<'_, str> {
+    fn from_val(v: &Val) -> Self {
+        Cow::Owned(String::from_val(v))
+    }
+    fn option_from_val(v: &Val) -> Option<Self> {
+        v.as_string().map(Cow::Owned)
     }
 }
 
-impl FromVal for Result<Vec<u16>, Val> {
+impl<T: FromVal> FromVal for Option<T> {
+    /// `null` and `undefined` convert to `None`, as do values of the wrong JS
+    /// type for numbers and strings: `as_::<Option<i32>>()` on `"abc"` is
+    /// `None`
     fn from_val(v: &Val) -> Self {
-        unsafe {
-            if v.is_error() {
-                Err(v.clone())
-            } else {
-                match emlite_val_get_value_string_utf16_unified(v.as_handle()) {
-                    Some(vec) => Ok(vec),
-                    None => Err(Val::global("Error").new(&["Null UTF-16 string".into()])),
-                }
-            }
+        if v.is_null() || v.is_undefined() {
+            None
+        } else {
+            T::option_from_val(v)
         }
     }
-    fn take_ownership(v: Handle) -> Self {
-        unsafe {
-            let temp = Val::take_ownership(v);
-            if temp.is_error() {
-                Err(temp)
-            } else {
-                match emlite_val_get_value_string_utf16_unified(v) {
-                    Some(vec) => Ok(vec),
-                    None => Err(Val::global("Error").new(&["Null UTF-16 string".into()])),
-                }
-            }
+}
+
+impl FromVal for Result<String, Val> {
+    fn from_val(v: &Val) -> Self {
+        if v.is_error() {
+            Err(v.clone())
+        } else if !v.is_string() {
+            Err(Val::global("Error").new(&["Expected string".into()]))
+        } else {
+            v.as_string()
+                .ok_or_else(|| Val::global("Error").new(&["Null string".into()]))
         }
     }
-    fn as_handle(&self) -> Handle {
-        0
+}
+
+impl FromVal for Result<Vec<u16>, Val> {
+    fn from_val(v: &Val) -> Self {
+        if v.is_error() {
+            Err(v.clone())
+        } else {
+            v.to_utf16()
+                .ok_or_else(|| Val::global("Error").new(&["Expected string".into()]))
+        }
     }
 }
 
@@ -1198,18 +1159,4 @@ where
             Ok(T::from_val(v))
         }
     }
-    fn take_ownership(handle: Handle) -> Self {
-        let temp = Val::take_ownership(handle);
-        if temp.is_error() {
-            Err(E::from_js_error(&temp))
-        } else {
-            Ok(T::take_ownership(handle))
-        }
-    }
-    fn as_handle(&self) -> Handle {
-        match self {
-            Ok(ok) => ok.as_handle(),
-            Err(_) => 0, // Errors don't have meaningful handles in this context
-        }
-    }
 }
@@ -0,0 +1,3524 @@
+//! An in-process mock of the emlite JS host.
+//!
+//! This backend is selected on non-wasm targets when the `mock-host` feature is
+//! enabled. It implements every `emlite_val_*` import against a small pure-Rust
+//! object model so that code built on [`Val`](crate::Val) can be unit-tested
+//! with a plain `cargo test`, without a browser or node.
+//!
+//! The model covers objects, arrays, strings (stored as UTF-16 like JS), numbers,
+//! BigInts, functions backed by Rust closures or registered callbacks, and a
+//! refcounted handle table that honors [`EmlitePredefHandles`]. Each thread gets
+//! its own host, so tests running in parallel don't observe each other.
+//!
+//! `eval` is not available; it throws a `SyntaxError` like a host with a strict
+//! CSP would.
+
+extern crate std;
+
+use crate::EmlitePredefHandles;
+use crate::common::{EMLITE_TARGET, Handle, TYPEOF_NAMES, read_into};
+
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::rc::Rc;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cell::RefCell;
+use core::ffi::{c_char, c_double, c_int, c_longlong, c_uint, c_ulonglong, c_void};
+use std::collections::HashMap;
+use std::println;
+
+/// A JS string, stored as UTF-16 code units so lone surrogates survive.
+pub type JsStr = Rc<[u16]>;
+
+/// A JS value as seen by the mock host.
+#[derive(Clone, Debug)]
+pub enum Value {
+    Undefined,
+    Null,
+    Bool(bool),
+    Number(f64),
+    BigInt(i128),
+    String(JsStr),
+    Symbol(Sym),
+    Object(Obj),
+}
+
+/// A JS symbol, compared by identity.
+#[derive(Clone, Debug)]
+pub struct Sym(Rc<Option<JsStr>>);
+
+impl Sym {
+    fn new(description: Option<JsStr>) -> Sym {
+        Sym(Rc::new(description))
+    }
+
+    fn addr(&self) -> usize {
+        Rc::as_ptr(&self.0) as usize
+    }
+
+    fn description(&self) -> Option<&JsStr> {
+        self.0.as_ref().as_ref()
+    }
+}
+
+impl PartialEq for Sym {
+    fn eq(&self, other: &Sym) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Sym {}
+
+/// A reference to a heap object of the mock host.
+#[derive(Clone)]
+pub struct Obj(Rc<RefCell<ObjData>>);
+
+impl core::fmt::Debug for Obj {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Obj({:p})", Rc::as_ptr(&self.0))
+    }
+}
+
+impl Obj {
+    fn new(proto: Option<Obj>, kind: Kind) -> Obj {
+        Obj(Rc::new(RefCell::new(ObjData {
+            proto,
+            props: Vec::new(),
+            kind,
+        })))
+    }
+
+    fn ptr_eq(&self, other: &Obj) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+
+    fn addr(&self) -> usize {
+        Rc::as_ptr(&self.0) as *const u8 as usize
+    }
+
+    fn proto(&self) -> Option<Obj> {
+        self.0.borrow().proto.clone()
+    }
+
+    fn own(&self, key: &PropKey) -> Option<Value> {
+        let data = self.0.borrow();
+        match &data.kind {
+            Kind::Array(items) => {
+                if let Some(i) = key.as_index() {
+                    return items.get(i).cloned();
+                }
+                if key.is_str("length") {
+                    return Some(Value::Number(items.len() as f64));
+                }
+            }
+            Kind::TypedArray(view) => {
+                if let Some(i) = key.as_index() {
+                    return view.get(i);
+                }
+                // Accessors on the prototype in JS, plain reads here
+                let n = |n: usize| Some(Value::Number(n as f64));
+                if key.is_str("length") {
+                    return n(view.len);
+                }
+                if key.is_str("byteLength") {
+                    return n(view.len * view.ty.size());
+                }
+                if key.is_str("byteOffset") {
+                    return n(view.offset);
+                }
+                if key.is_str("buffer") {
+                    return Some(Value::Object(view.buffer.clone()));
+                }
+            }
+            Kind::ArrayBuffer(buffer) if key.is_str("byteLength") => {
+                return Some(Value::Number(buffer.len() as f64));
+            }
+            Kind::Map(entries) if key.is_str("size") => {
+                return Some(Value::Number(entries.len() as f64));
+            }
+            Kind::Set(items) if key.is_str("size") => {
+                return Some(Value::Number(items.len() as f64));
+            }
+            _ => {}
+        }
+        data.props
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.clone())
+    }
+
+    fn define(&self, key: PropKey, val: Value) {
+        let mut data = self.0.borrow_mut();
+        if let Kind::Array(items) = &mut data.kind {
+            if let Some(i) = key.as_index() {
+                if i >= items.len() {
+                    items.resize(i + 1, Value::Undefined);
+                }
+                items[i] = val;
+                return;
+            }
+            if key.is_str("length") {
+                let len = to_number(&val).unwrap_or(f64::NAN);
+                if len >= 0.0 && len.fract() == 0.0 {
+                    items.resize(len as usize, Value::Undefined);
+                }
+                return;
+            }
+        }
+        if let Kind::TypedArray(view) = &data.kind
+            && let Some(i) = key.as_index()
+        {
+            // Out of range writes are dropped, as in JS
+            view.set(i, &val);
+            return;
+        }
+        match data.props.iter_mut().find(|(k, _)| *k == key) {
+            Some(slot) => slot.1 = val,
+            None => data.props.push((key, val)),
+        }
+    }
+
+    fn remove(&self, key: &PropKey) {
+        let mut data = self.0.borrow_mut();
+        if let Kind::Array(items) = &mut data.kind
+            && let Some(i) = key.as_index()
+        {
+            // Holes aren't modelled, deleting an element leaves undefined
+            if let Some(item) = items.get_mut(i) {
+                *item = Value::Undefined;
+            }
+            return;
+        }
+        data.props.retain(|(k, _)| k != key);
+    }
+
+    fn own_keys(&self) -> Vec<PropKey> {
+        let data = self.0.borrow();
+        let mut keys = Vec::new();
+        let len = match &data.kind {
+            Kind::Array(items) => items.len(),
+            Kind::TypedArray(view) => view.len,
+            _ => 0,
+        };
+        keys.extend((0..len).map(|i| PropKey::from_str(&i.to_string())));
+        keys.extend(data.props.iter().map(|(k, _)| k.clone()));
+        keys
+    }
+
+    fn is_callable(&self) -> bool {
+        matches!(self.0.borrow().kind, Kind::Function(_))
+    }
+}
+
+struct ObjData {
+    proto: Option<Obj>,
+    props: Vec<(PropKey, Value)>,
+    kind: Kind,
+}
+
+enum Kind {
+    Ordinary,
+    Array(Vec<Value>),
+    Error,
+    Function(Func),
+    Promise(PromiseState),
+    Map(Vec<(Value, Value)>),
+    Set(Vec<Value>),
+    ArrayBuffer(Buffer),
+    TypedArray(View),
+}
+
+/// Element types of the typed array constructors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ElemType {
+    Int8,
+    Uint8,
+    Uint8Clamped,
+    Int16,
+    Uint16,
+    Int32,
+    Uint32,
+    Float32,
+    Float64,
+    BigInt64,
+    BigUint64,
+}
+
+impl ElemType {
+    const ALL: [ElemType; 11] = [
+        ElemType::Int8,
+        ElemType::Uint8,
+        ElemType::Uint8Clamped,
+        ElemType::Int16,
+        ElemType::Uint16,
+        ElemType::Int32,
+        ElemType::Uint32,
+        ElemType::Float32,
+        ElemType::Float64,
+        ElemType::BigInt64,
+        ElemType::BigUint64,
+    ];
+
+    /// Returns the name of the global constructor, e.g. `"Uint8Array"`
+    pub fn name(self) -> &'static str {
+        match self {
+            ElemType::Int8 => "Int8Array",
+            ElemType::Uint8 => "Uint8Array",
+            ElemType::Uint8Clamped => "Uint8ClampedArray",
+            ElemType::Int16 => "Int16Array",
+            ElemType::Uint16 => "Uint16Array",
+            ElemType::Int32 => "Int32Array",
+            ElemType::Uint32 => "Uint32Array",
+            ElemType::Float32 => "Float32Array",
+            ElemType::Float64 => "Float64Array",
+            ElemType::BigInt64 => "BigInt64Array",
+            ElemType::BigUint64 => "BigUint64Array",
+        }
+    }
+
+    /// Returns the element size in bytes
+    pub fn size(self) -> usize {
+        match self {
+            ElemType::Int8 | ElemType::Uint8 | ElemType::Uint8Clamped => 1,
+            ElemType::Int16 | ElemType::Uint16 => 2,
+            ElemType::Int32 | ElemType::Uint32 | ElemType::Float32 => 4,
+            ElemType::Float64 | ElemType::BigInt64 | ElemType::BigUint64 => 8,
+        }
+    }
+
+    fn decode(self, b: &[u8]) -> Value {
+        macro_rules! le {
+            ($t:ty) => {
+                <$t>::from_le_bytes(b.try_into().unwrap())
+            };
+        }
+        match self {
+            ElemType::Int8 => Value::Number(b[0] as i8 as f64),
+            ElemType::Uint8 | ElemType::Uint8Clamped => Value::Number(b[0] as f64),
+            ElemType::Int16 => Value::Number(le!(i16) as f64),
+            ElemType::Uint16 => Value::Number(le!(u16) as f64),
+            ElemType::Int32 => Value::Number(le!(i32) as f64),
+            ElemType::Uint32 => Value::Number(le!(u32) as f64),
+            ElemType::Float32 => Value::Number(le!(f32) as f64),
+            ElemType::Float64 => Value::Number(le!(f64)),
+            ElemType::BigInt64 => Value::BigInt(le!(i64) as i128),
+            ElemType::BigUint64 => Value::BigInt(le!(u64) as i128),
+        }
+    }
+
+    /// Stores `v`, converting Numbers and BigInts into one another rather
+    /// than throwing as JS does
+    fn encode(self, v: &Value, out: &mut [u8]) {
+        let int = || to_int32(v);
+        let num = || number_of(v).unwrap_or(f64::NAN);
+        let big = || match v {
+            Value::BigInt(b) => *b,
+            _ => num() as i128,
+        };
+        match self {
+            ElemType::Int8 | ElemType::Uint8 => out[0] = int() as u8,
+            ElemType::Uint8Clamped => {
+                let n = num();
+                out[0] = if n.is_nan() {
+                    0
+                } else {
+                    n.round_ties_even().clamp(0.0, 255.0) as u8
+                };
+            }
+            ElemType::Int16 | ElemType::Uint16 => {
+                out.copy_from_slice(&(int() as u16).to_le_bytes())
+            }
+            ElemType::Int32 | ElemType::Uint32 => {
+                out.copy_from_slice(&(int() as u32).to_le_bytes())
+            }
+            ElemType::Float32 => out.copy_from_slice(&(num() as f32).to_le_bytes()),
+            ElemType::Float64 => out.copy_from_slice(&num().to_le_bytes()),
+            ElemType::BigInt64 | ElemType::BigUint64 => {
+                out.copy_from_slice(&(big() as u64).to_le_bytes())
+            }
+        }
+    }
+}
+
+/// The storage of an `ArrayBuffer`: owned by the host, or borrowed from the
+/// Rust side for views over its memory, standing in for wasm linear memory.
+enum Buffer {
+    Owned(Vec<u8>),
+    External(*mut u8, usize),
+}
+
+impl Buffer {
+    fn len(&self) -> usize {
+        match self {
+            Buffer::Owned(bytes) => bytes.len(),
+            Buffer::External(_, len) => *len,
+        }
+    }
+
+    fn bytes(&mut self) -> &mut [u8] {
+        match self {
+            Buffer::Owned(bytes) => bytes,
+            // Valid for as long as the caller of the view promised
+            Buffer::External(ptr, len) => unsafe { core::slice::from_raw_parts_mut(*ptr, *len) },
+        }
+    }
+}
+
+impl From<Vec<u8>> for Buffer {
+    fn from(bytes: Vec<u8>) -> Self {
+        Buffer::Owned(bytes)
+    }
+}
+
+/// A typed array: a window of `len` elements into an `ArrayBuffer`.
+#[derive(Clone)]
+struct View {
+    ty: ElemType,
+    buffer: Obj,
+    offset: usize,
+    len: usize,
+}
+
+impl View {
+    fn with_bytes<R>(&self, f: impl FnOnce(&mut [u8]) -> R) -> R {
+        let mut data = self.buffer.0.borrow_mut();
+        let Kind::ArrayBuffer(buffer) = &mut data.kind else {
+            unreachable!("typed array over a non-buffer")
+        };
+        let end = self.offset + self.len * self.ty.size();
+        f(&mut buffer.bytes()[self.offset..end])
+    }
+
+    fn get(&self, i: usize) -> Option<Value> {
+        if i >= self.len {
+            return None;
+        }
+        let size = self.ty.size();
+        Some(self.with_bytes(|b| self.ty.decode(&b[i * size..(i + 1) * size])))
+    }
+
+    fn set(&self, i: usize, v: &Value) {
+        if i < self.len {
+            let size = self.ty.size();
+            self.with_bytes(|b| self.ty.encode(v, &mut b[i * size..(i + 1) * size]));
+        }
+    }
+}
+
+enum PromiseState {
+    Pending(Vec<Reaction>),
+    Fulfilled(Value),
+    Rejected(Value),
+}
+
+struct Reaction {
+    on_fulfilled: Value,
+    on_rejected: Value,
+    derived: Obj,
+}
+
+/// Signature of a natively implemented JS function: `(this, args) -> completion`.
+pub type NativeFn = Rc<dyn Fn(&Value, &[Value]) -> Result<Value, Value>>;
+
+#[derive(Clone)]
+enum Callable {
+    Native(NativeFn),
+    Callback(Rc<Callback>),
+}
+
+/// A function created through `emlite_val_make_callback`. Once the function
+/// object is unreachable the callback is finalized: it is called with a null
+/// `argv` handle and its pinned `data` handle is released.
+struct Callback {
+    fidx: Handle,
+    data: Handle,
+}
+
+impl Drop for Callback {
+    fn drop(&mut self) {
+        // Objects may die while the host is borrowed, so defer to `run_finalizers`
+        let _ = FINALIZERS.try_with(|q| q.borrow_mut().push((self.fidx, self.data)));
+    }
+}
+
+#[derive(Clone)]
+struct Func {
+    call: Callable,
+    construct: Option<NativeFn>,
+}
+
+/// A property key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PropKey {
+    Str(JsStr),
+    Symbol(Sym),
+}
+
+impl PropKey {
+    fn from_str(s: &str) -> PropKey {
+        PropKey::Str(js_str(s))
+    }
+
+    fn is_str(&self, s: &str) -> bool {
+        match self {
+            PropKey::Str(k) => k.iter().copied().eq(s.encode_utf16()),
+            PropKey::Symbol(_) => false,
+        }
+    }
+
+    fn as_index(&self) -> Option<usize> {
+        match self {
+            PropKey::Str(k) => {
+                if k.is_empty() || (k.len() > 1 && k[0] == b'0' as u16) {
+                    return None;
+                }
+                let mut n: u64 = 0;
+                for &c in k.iter() {
+                    if !(b'0' as u16..=b'9' as u16).contains(&c) {
+                        return None;
+                    }
+                    n = n * 10 + (c - b'0' as u16) as u64;
+                    if n >= u32::MAX as u64 {
+                        return None;
+                    }
+                }
+                Some(n as usize)
+            }
+            PropKey::Symbol(_) => None,
+        }
+    }
+
+    fn to_value(&self) -> Value {
+        match self {
+            PropKey::Str(s) => Value::String(s.clone()),
+            PropKey::Symbol(s) => Value::Symbol(s.clone()),
+        }
+    }
+}
+
+/// Identity used to dedupe handles, mirroring the host's value-to-handle map
+/// (SameValueZero semantics).
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum Identity {
+    Undefined,
+    Null,
+    Bool(bool),
+    Number(u64),
+    BigInt(i128),
+    String(JsStr),
+    Symbol(usize),
+    Object(usize),
+}
+
+fn identity(v: &Value) -> Identity {
+    match v {
+        Value::Undefined => Identity::Undefined,
+        Value::Null => Identity::Null,
+        Value::Bool(b) => Identity::Bool(*b),
+        Value::Number(n) => Identity::Number(if n.is_nan() {
+            f64::NAN.to_bits()
+        } else if *n == 0.0 {
+            0
+        } else {
+            n.to_bits()
+        }),
+        Value::BigInt(b) => Identity::BigInt(*b),
+        Value::String(s) => Identity::String(s.clone()),
+        Value::Symbol(s) => Identity::Symbol(s.addr()),
+        Value::Object(o) => Identity::Object(o.addr()),
+    }
+}
+
+struct Slot {
+    value: Value,
+    refs: u32,
+    pinned: bool,
+}
+
+#[derive(Clone)]
+struct Realm {
+    global: Obj,
+    console: Obj,
+    object_proto: Obj,
+    function_proto: Obj,
+    array_proto: Obj,
+    error_proto: Obj,
+    string_proto: Obj,
+    number_proto: Obj,
+    boolean_proto: Obj,
+    bigint_proto: Obj,
+    symbol_proto: Obj,
+    promise_proto: Obj,
+    iterator_proto: Obj,
+    iterator: Sym,
+}
+
+struct Host {
+    slots: HashMap<Handle, Slot>,
+    index: HashMap<Identity, Handle>,
+    next: Handle,
+    realm: Realm,
+}
+
+// Function pointer type for callbacks (match C ABI for indirect calls)
+type CallbackFn = extern "C" fn(Handle, Handle) -> Handle;
+
+std::thread_local! {
+    static HOST: RefCell<Option<Host>> = const { RefCell::new(None) };
+    static CALLBACKS: RefCell<Vec<CallbackFn>> = const { RefCell::new(Vec::new()) };
+    static CONSOLE: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    static FINALIZERS: RefCell<Vec<(Handle, Handle)>> = const { RefCell::new(Vec::new()) };
+    static FINALIZING: core::cell::Cell<bool> = const { core::cell::Cell::new(false) };
+    static MICROTASKS: RefCell<std::collections::VecDeque<Box<dyn FnOnce()>>> =
+        RefCell::new(std::collections::VecDeque::new());
+    static SOURCES: RefCell<Vec<(String, NativeFn)>> = const { RefCell::new(Vec::new()) };
+    static CACHED: RefCell<HashMap<usize, Handle>> = RefCell::new(HashMap::new());
+    static CALLBACK_EXCEPTION: core::cell::Cell<Option<Handle>> = const { core::cell::Cell::new(None) };
+}
+
+fn with_host<R>(f: impl FnOnce(&mut Host) -> R) -> R {
+    let r = HOST.with(|h| {
+        let mut h = h.borrow_mut();
+        let host = h.get_or_insert_with(Host::new);
+        f(host)
+    });
+    run_finalizers();
+    r
+}
+
+/// Finalizes callbacks whose function objects became unreachable. This runs
+/// automatically after each host call, much like a JS engine's
+/// FinalizationRegistry would at some later point.
+pub fn run_finalizers() {
+    if FINALIZING.with(|f| f.replace(true)) {
+        return;
+    }
+    while let Some((fidx, data)) = FINALIZERS.with(|q| q.borrow_mut().pop()) {
+        if let Some(cb) = CALLBACKS.with(|c| c.borrow().get(fidx as usize).copied()) {
+            cb(EmlitePredefHandles::Null as Handle, data);
+        }
+        with_host(|h| h.dec_ref(data));
+    }
+    FINALIZING.with(|f| f.set(false));
+}
+
+/// Drops pending finalizers whose `data` handles died with a reset host.
+fn discard_finalizers() {
+    FINALIZERS.with(|q| q.borrow_mut().clear());
+}
+
+fn realm() -> Realm {
+    with_host(|h| h.realm.clone())
+}
+
+impl Host {
+    fn new() -> Host {
+        let realm = Realm::new();
+        let mut host = Host {
+            slots: HashMap::new(),
+            index: HashMap::new(),
+            next: EmlitePredefHandles::Reserved as Handle,
+            realm,
+        };
+        let predef = [
+            (EmlitePredefHandles::Null, Value::Null),
+            (EmlitePredefHandles::Undefined, Value::Undefined),
+            (EmlitePredefHandles::False, Value::Bool(false)),
+            (EmlitePredefHandles::True, Value::Bool(true)),
+            (
+                EmlitePredefHandles::GlobalThis,
+                Value::Object(host.realm.global.clone()),
+            ),
+            (
+                EmlitePredefHandles::Console,
+                Value::Object(host.realm.console.clone()),
+            ),
+        ];
+        for (h, value) in predef {
+            let h = h as Handle;
+            host.index.insert(identity(&value), h);
+            host.slots.insert(
+                h,
+                Slot {
+                    value,
+                    refs: 1,
+                    pinned: true,
+                },
+            );
+        }
+        host
+    }
+
+    fn add(&mut self, value: Value) -> Handle {
+        let id = identity(&value);
+        if let Some(&h) = self.index.get(&id) {
+            let slot = self.slots.get_mut(&h).unwrap();
+            if !slot.pinned {
+                slot.refs += 1;
+            }
+            return h;
+        }
+        let h = self.next;
+        self.next += 1;
+        self.index.insert(id, h);
+        self.slots.insert(
+            h,
+            Slot {
+                value,
+                refs: 1,
+                pinned: false,
+            },
+        );
+        h
+    }
+
+    fn get(&self, h: Handle) -> Value {
+        self.slots
+            .get(&h)
+            .map(|s| s.value.clone())
+            .unwrap_or(Value::Undefined)
+    }
+
+    fn inc_ref(&mut self, h: Handle) {
+        if let Some(slot) = self.slots.get_mut(&h)
+            && !slot.pinned
+        {
+            slot.refs += 1;
+        }
+    }
+
+    fn dec_ref(&mut self, h: Handle) {
+        let Some(slot) = self.slots.get_mut(&h) else {
+            return;
+        };
+        if slot.pinned {
+            return;
+        }
+        slot.refs -= 1;
+        if slot.refs == 0 {
+            let slot = self.slots.remove(&h).unwrap();
+            self.index.remove(&identity(&slot.value));
+        }
+    }
+
+    /// Releases every non-predefined handle, returning the released values
+    fn reset(&mut self) -> Vec<Value> {
+        let dead = self
+            .slots
+            .extract_if(|_, s| !s.pinned)
+            .map(|(_, s)| s.value)
+            .collect();
+        let slots = &self.slots;
+        self.index.retain(|_, h| slots.contains_key(h));
+        dead
+    }
+}
+
+fn js_str(s: &str) -> JsStr {
+    s.encode_utf16().collect::<Vec<u16>>().into()
+}
+
+fn str_val(s: &str) -> Value {
+    Value::String(js_str(s))
+}
+
+fn to_js_str(v: &Value) -> JsStr {
+    match v {
+        Value::String(s) => s.clone(),
+        v => js_str(&to_rust_string(v)),
+    }
+}
+
+fn native(f: impl Fn(&Value, &[Value]) -> Result<Value, Value> + 'static) -> NativeFn {
+    Rc::new(f)
+}
+
+fn arg(args: &[Value], i: usize) -> Value {
+    args.get(i).cloned().unwrap_or(Value::Undefined)
+}
+
+impl Realm {
+    fn new() -> Realm {
+        let object_proto = Obj::new(None, Kind::Ordinary);
+        let function_proto = Obj::new(
+            Some(object_proto.clone()),
+            Kind::Function(Func {
+                call: Callable::Native(native(|_, _| Ok(Value::Undefined))),
+                construct: None,
+            }),
+        );
+        let proto = |kind| Obj::new(Some(object_proto.clone()), kind);
+        let realm = Realm {
+            global: proto(Kind::Ordinary),
+            console: proto(Kind::Ordinary),
+            array_proto: proto(Kind::Array(Vec::new())),
+            error_proto: proto(Kind::Ordinary),
+            string_proto: proto(Kind::Ordinary),
+            number_proto: proto(Kind::Ordinary),
+            boolean_proto: proto(Kind::Ordinary),
+            bigint_proto: proto(Kind::Ordinary),
+            symbol_proto: proto(Kind::Ordinary),
+            promise_proto: proto(Kind::Ordinary),
+            iterator_proto: proto(Kind::Ordinary),
+            iterator: Sym::new(Some(js_str("Symbol.iterator"))),
+            object_proto,
+            function_proto,
+        };
+        realm.install();
+        realm
+    }
+
+    fn function(&self, name: &str, call: NativeFn, construct: Option<NativeFn>) -> Obj {
+        let f = Obj::new(
+            Some(self.function_proto.clone()),
+            Kind::Function(Func {
+                call: Callable::Native(call),
+                construct,
+            }),
+        );
+        f.define(PropKey::from_str("name"), str_val(name));
+        f
+    }
+
+    fn method(
+        &self,
+        target: &Obj,
+        name: &str,
+        f: impl Fn(&Value, &[Value]) -> Result<Value, Value> + 'static,
+    ) {
+        let func = self.function(name, native(f), None);
+        target.define(PropKey::from_str(name), Value::Object(func));
+    }
+
+    fn constructor(&self, name: &str, proto: &Obj, call: NativeFn, construct: NativeFn) -> Obj {
+        let ctor = self.function(name, call, Some(construct));
+        ctor.define(PropKey::from_str("prototype"), Value::Object(proto.clone()));
+        proto.define(
+            PropKey::from_str("constructor"),
+            Value::Object(ctor.clone()),
+        );
+        self.global
+            .define(PropKey::from_str(name), Value::Object(ctor.clone()));
+        ctor
+    }
+
+    fn install(&self) {
+        let g = &self.global;
+        g.define(PropKey::from_str("globalThis"), Value::Object(g.clone()));
+        g.define(
+            PropKey::from_str("console"),
+            Value::Object(self.console.clone()),
+        );
+        g.define(PropKey::from_str("undefined"), Value::Undefined);
+        g.define(PropKey::from_str("NaN"), Value::Number(f64::NAN));
+        g.define(PropKey::from_str("Infinity"), Value::Number(f64::INFINITY));
+
+        for name in ["log", "info", "warn", "error", "debug"] {
+            self.method(&self.console, name, |_, args| {
+                let line = args.iter().map(display).collect::<Vec<_>>().join(" ");
+                println!("{line}");
+                CONSOLE.with(|c| c.borrow_mut().push(line));
+                Ok(Value::Undefined)
+            });
+        }
+        self.method(&self.console, "clear", |_, _| Ok(Value::Undefined));
+
+        // Object
+        let object_proto = self.object_proto.clone();
+        let object = self.constructor(
+            "Object",
+            &self.object_proto,
+            native(|_, args| Ok(to_object(&arg(args, 0)))),
+            native(|_, args| Ok(to_object(&arg(args, 0)))),
+        );
+        self.method(&object, "keys", |_, args| {
+            Ok(new_array(
+                own_entries(&arg(args, 0))
+                    .into_iter()
+                    .map(|(k, _)| k)
+                    .collect(),
+            ))
+        });
+        self.method(&object, "values", |_, args| {
+            Ok(new_array(
+                own_entries(&arg(args, 0))
+                    .into_iter()
+                    .map(|(_, v)| v)
+                    .collect(),
+            ))
+        });
+        self.method(&object, "create", |_, args| match arg(args, 0) {
+            Value::Object(proto) => Ok(Value::Object(Obj::new(Some(proto), Kind::Ordinary))),
+            Value::Null => Ok(Value::Object(Obj::new(None, Kind::Ordinary))),
+            _ => Err(new_error(
+                "TypeError",
+                "Object prototype may only be an Object or null",
+            )),
+        });
+        self.method(&object, "getPrototypeOf", |_, args| {
+            Ok(match to_object(&arg(args, 0)) {
+                Value::Object(o) => o.proto().map_or(Value::Null, Value::Object),
+                _ => Value::Null,
+            })
+        });
+        self.method(&object, "entries", |_, args| {
+            Ok(new_array(
+                own_entries(&arg(args, 0))
+                    .into_iter()
+                    .map(|(k, v)| new_array(vec![k, v]))
+                    .collect(),
+            ))
+        });
+        self.method(&object_proto, "hasOwnProperty", |this, args| {
+            let key = to_property_key(&arg(args, 0));
+            Ok(Value::Bool(match this {
+                Value::Object(o) => o.own(&key).is_some(),
+                _ => false,
+            }))
+        });
+        self.method(&object_proto, "toString", |this, _| {
+            Ok(str_val(&format!("[object {}]", class_name(this))))
+        });
+
+        // Function
+        let function_proto = self.function_proto.clone();
+        let eval_unsupported = native(|_, _| {
+            Err(new_error(
+                "SyntaxError",
+                "code generation from strings is not supported by the mock host",
+            ))
+        });
+        let compile = native(|_, args| {
+            let (body, params) = match args.split_last() {
+                Some((body, params)) => (to_rust_string(body), params),
+                None => (String::new(), args),
+            };
+            let params = params.iter().map(to_rust_string).collect::<Vec<_>>();
+            let source = function_source(&params.join(","), &body);
+            let f = SOURCES.with(|s| {
+                s.borrow()
+                    .iter()
+                    .find(|(src, _)| *src == source)
+                    .map(|(_, f)| f.clone())
+            });
+            match f {
+                Some(f) => Ok(Value::Object(realm().function("anonymous", f, None))),
+                None => Err(new_error(
+                    "SyntaxError",
+                    &format!("the mock host can't compile {source}, see define_function_source"),
+                )),
+            }
+        });
+        self.constructor("Function", &function_proto, compile.clone(), compile);
+        self.method(&function_proto, "call", |this, args| {
+            call_value(this, &arg(args, 0), args.get(1..).unwrap_or(&[]))
+        });
+        self.method(&function_proto, "apply", |this, args| {
+            let list = match arg(args, 1) {
+                Value::Object(o) => array_items(&o),
+                _ => Vec::new(),
+            };
+            call_value(this, &arg(args, 0), &list)
+        });
+        self.method(&function_proto, "bind", |this, args| {
+            if callable(this).is_none() {
+                return Err(new_error("TypeError", "Bind must be called on a function"));
+            }
+            let (target, bound_this) = (this.clone(), arg(args, 0));
+            let bound = args.get(1..).unwrap_or(&[]).to_vec();
+            let f = native(move |_, args| {
+                let args = [bound.as_slice(), args].concat();
+                call_value(&target, &bound_this, &args)
+            });
+            Ok(Value::Object(realm().function("bound", f, None)))
+        });
+        let eval = self.function("eval", eval_unsupported, None);
+        g.define(PropKey::from_str("eval"), Value::Object(eval));
+
+        // Array
+        let array_proto = self.array_proto.clone();
+        let make_array = native(|_, args| {
+            if let [Value::Number(n)] = args {
+                let items = vec![Value::Undefined; *n as usize];
+                return Ok(new_array(items));
+            }
+            Ok(new_array(args.to_vec()))
+        });
+        let array = self.constructor("Array", &array_proto, make_array.clone(), make_array);
+        self.method(&array, "isArray", |_, args| {
+            Ok(Value::Bool(
+                matches!(arg(args, 0), Value::Object(o) if is_array(&o)),
+            ))
+        });
+        self.method(&array_proto, "push", |this, args| {
+            let Value::Object(o) = this else {
+                return Err(new_error("TypeError", "push called on non-object"));
+            };
+            let mut data = o.0.borrow_mut();
+            match &mut data.kind {
+                Kind::Array(items) => {
+                    items.extend(args.iter().cloned());
+                    Ok(Value::Number(items.len() as f64))
+                }
+                _ => Err(new_error("TypeError", "push called on non-array")),
+            }
+        });
+        self.method(&array_proto, "pop", |this, _| {
+            let Value::Object(o) = this else {
+                return Ok(Value::Undefined);
+            };
+            let mut data = o.0.borrow_mut();
+            match &mut data.kind {
+                Kind::Array(items) => Ok(items.pop().unwrap_or(Value::Undefined)),
+                _ => Ok(Value::Undefined),
+            }
+        });
+        self.method(&array_proto, "at", |this, args| {
+            let Value::Object(o) = this else {
+                return Ok(Value::Undefined);
+            };
+            let items = array_items(o);
+            let mut i = to_number(&arg(args, 0))? as i64;
+            if i < 0 {
+                i += items.len() as i64;
+            }
+            Ok(usize::try_from(i)
+                .ok()
+                .and_then(|i| items.get(i).cloned())
+                .unwrap_or(Value::Undefined))
+        });
+        self.method(&array_proto, "indexOf", |this, args| {
+            let Value::Object(o) = this else {
+                return Ok(Value::Number(-1.0));
+            };
+            let needle = arg(args, 0);
+            Ok(Value::Number(
+                array_items(o)
+                    .iter()
+                    .position(|v| strictly_equals(v, &needle))
+                    .map_or(-1.0, |i| i as f64),
+            ))
+        });
+        self.method(&array_proto, "join", |this, args| {
+            let Value::Object(o) = this else {
+                return Ok(str_val(""));
+            };
+            let sep = match arg(args, 0) {
+                Value::Undefined => String::from(","),
+                v => to_rust_string(&v),
+            };
+            Ok(str_val(&join(o, &sep)))
+        });
+        self.method(&array_proto, "slice", |this, args| {
+            let Value::Object(o) = this else {
+                return Ok(new_array(Vec::new()));
+            };
+            let items = array_items(o);
+            let (start, end) = relative_range(args, items.len())?;
+            Ok(new_array(items[start..end].to_vec()))
+        });
+
+        self.method(&array, "from", |_, args| {
+            let items = iterable_items(&arg(args, 0))?;
+            let map = arg(args, 1);
+            if matches!(map, Value::Undefined) {
+                return Ok(new_array(items));
+            }
+            let mapped = items
+                .into_iter()
+                .enumerate()
+                .map(|(i, v)| call_value(&map, &Value::Undefined, &[v, Value::Number(i as f64)]))
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(new_array(mapped))
+        });
+
+        // Map, keyed by SameValueZero
+        let map_proto = Obj::new(Some(self.object_proto.clone()), Kind::Ordinary);
+        let make_map = {
+            let proto = map_proto.clone();
+            native(move |_, args| {
+                let map = Obj::new(Some(proto.clone()), Kind::Map(Vec::new()));
+                let init = match arg(args, 0) {
+                    Value::Undefined | Value::Null => Vec::new(),
+                    v => iterable_items(&v)?,
+                };
+                for entry in init {
+                    let Value::Object(pair) = entry else {
+                        return Err(new_error(
+                            "TypeError",
+                            "iterator value is not an entry object",
+                        ));
+                    };
+                    let pair = array_items(&pair);
+                    map_set(&map, arg(&pair, 0), arg(&pair, 1));
+                }
+                Ok(Value::Object(map))
+            })
+        };
+        self.constructor(
+            "Map",
+            &map_proto,
+            native(|_, _| Err(new_error("TypeError", "Constructor Map requires 'new'"))),
+            make_map,
+        );
+        self.method(&map_proto, "get", |this, args| {
+            let key = identity(&arg(args, 0));
+            Ok(map_entries(this)?
+                .into_iter()
+                .find(|(k, _)| identity(k) == key)
+                .map_or(Value::Undefined, |(_, v)| v))
+        });
+        self.method(&map_proto, "has", |this, args| {
+            let key = identity(&arg(args, 0));
+            Ok(Value::Bool(
+                map_entries(this)?.iter().any(|(k, _)| identity(k) == key),
+            ))
+        });
+        self.method(&map_proto, "set", |this, args| {
+            map_entries(this)?;
+            if let Value::Object(o) = this {
+                map_set(o, arg(args, 0), arg(args, 1));
+            }
+            Ok(this.clone())
+        });
+        self.method(&map_proto, "delete", |this, args| {
+            map_entries(this)?;
+            let key = identity(&arg(args, 0));
+            let Value::Object(o) = this else {
+                return Ok(Value::Bool(false));
+            };
+            let mut data = o.0.borrow_mut();
+            let Kind::Map(entries) = &mut data.kind else {
+                return Ok(Value::Bool(false));
+            };
+            let len = entries.len();
+            entries.retain(|(k, _)| identity(k) != key);
+            Ok(Value::Bool(entries.len() != len))
+        });
+        self.method(&map_proto, "clear", |this, _| {
+            map_entries(this)?;
+            if let Value::Object(o) = this
+                && let Kind::Map(entries) = &mut o.0.borrow_mut().kind
+            {
+                entries.clear();
+            }
+            Ok(Value::Undefined)
+        });
+        self.method(&map_proto, "forEach", |this, args| {
+            let f = arg(args, 0);
+            for (k, v) in map_entries(this)? {
+                call_value(&f, &arg(args, 1), &[v, k, this.clone()])?;
+            }
+            Ok(Value::Undefined)
+        });
+
+        // Set, keyed by SameValueZero
+        let set_proto = Obj::new(Some(self.object_proto.clone()), Kind::Ordinary);
+        let make_set = {
+            let proto = set_proto.clone();
+            native(move |_, args| {
+                let set = Obj::new(Some(proto.clone()), Kind::Set(Vec::new()));
+                let init = match arg(args, 0) {
+                    Value::Undefined | Value::Null => Vec::new(),
+                    v => iterable_items(&v)?,
+                };
+                for item in init {
+                    set_add(&set, item);
+                }
+                Ok(Value::Object(set))
+            })
+        };
+        self.constructor(
+            "Set",
+            &set_proto,
+            native(|_, _| Err(new_error("TypeError", "Constructor Set requires 'new'"))),
+            make_set,
+        );
+        self.method(&set_proto, "has", |this, args| {
+            let key = identity(&arg(args, 0));
+            Ok(Value::Bool(
+                set_items(this)?.iter().any(|v| identity(v) == key),
+            ))
+        });
+        self.method(&set_proto, "add", |this, args| {
+            set_items(this)?;
+            if let Value::Object(o) = this {
+                set_add(o, arg(args, 0));
+            }
+            Ok(this.clone())
+        });
+        self.method(&set_proto, "delete", |this, args| {
+            set_items(this)?;
+            let key = identity(&arg(args, 0));
+            let Value::Object(o) = this else {
+                return Ok(Value::Bool(false));
+            };
+            let mut data = o.0.borrow_mut();
+            let Kind::Set(items) = &mut data.kind else {
+                return Ok(Value::Bool(false));
+            };
+            let len = items.len();
+            items.retain(|v| identity(v) != key);
+            Ok(Value::Bool(items.len() != len))
+        });
+        self.method(&set_proto, "clear", |this, _| {
+            set_items(this)?;
+            if let Value::Object(o) = this
+                && let Kind::Set(items) = &mut o.0.borrow_mut().kind
+            {
+                items.clear();
+            }
+            Ok(Value::Undefined)
+        });
+        self.method(&set_proto, "forEach", |this, args| {
+            let f = arg(args, 0);
+            for v in set_items(this)? {
+                call_value(&f, &arg(args, 1), &[v.clone(), v, this.clone()])?;
+            }
+            Ok(Value::Undefined)
+        });
+
+        // ArrayBuffer and typed arrays
+        let buffer_proto = Obj::new(Some(self.object_proto.clone()), Kind::Ordinary);
+        let make_buffer = {
+            let proto = buffer_proto.clone();
+            native(move |_, args| {
+                let len = to_index(&arg(args, 0))?;
+                Ok(Value::Object(Obj::new(
+                    Some(proto.clone()),
+                    Kind::ArrayBuffer(vec![0; len].into()),
+                )))
+            })
+        };
+        let array_buffer = self.constructor(
+            "ArrayBuffer",
+            &buffer_proto,
+            native(|_, _| {
+                Err(new_error(
+                    "TypeError",
+                    "Constructor ArrayBuffer requires 'new'",
+                ))
+            }),
+            make_buffer,
+        );
+        self.method(&array_buffer, "isView", |_, args| {
+            Ok(Value::Bool(matches!(
+                arg(args, 0),
+                Value::Object(o) if matches!(o.0.borrow().kind, Kind::TypedArray(_))
+            )))
+        });
+        let typed_proto = Obj::new(Some(self.object_proto.clone()), Kind::Ordinary);
+        for ty in ElemType::ALL {
+            let proto = Obj::new(Some(typed_proto.clone()), Kind::Ordinary);
+            let make = {
+                let (proto, buffer_proto) = (proto.clone(), buffer_proto.clone());
+                native(move |_, args| new_view(ty, &proto, &buffer_proto, args))
+            };
+            let ctor = self.constructor(
+                ty.name(),
+                &proto,
+                native(move |_, _| {
+                    Err(new_error(
+                        "TypeError",
+                        &format!("Constructor {} requires 'new'", ty.name()),
+                    ))
+                }),
+                make,
+            );
+            let bytes = Value::Number(ty.size() as f64);
+            ctor.define(PropKey::from_str("BYTES_PER_ELEMENT"), bytes.clone());
+            proto.define(PropKey::from_str("BYTES_PER_ELEMENT"), bytes);
+        }
+        self.method(&typed_proto, "set", |this, args| {
+            let view = this_view(this)?;
+            let src = iterable_items(&arg(args, 0))?;
+            let offset = to_index(&arg(args, 1))?;
+            if offset + src.len() > view.len {
+                return Err(new_error("RangeError", "offset is out of bounds"));
+            }
+            for (i, v) in src.iter().enumerate() {
+                view.set(offset + i, v);
+            }
+            Ok(Value::Undefined)
+        });
+        self.method(&typed_proto, "subarray", |this, args| {
+            let view = this_view(this)?;
+            let (start, end) = relative_range(args, view.len)?;
+            let Value::Object(o) = this else {
+                unreachable!()
+            };
+            let sub = View {
+                offset: view.offset + start * view.ty.size(),
+                len: end - start,
+                ..view
+            };
+            Ok(Value::Object(Obj::new(o.proto(), Kind::TypedArray(sub))))
+        });
+        self.method(&typed_proto, "slice", |this, args| {
+            let view = this_view(this)?;
+            let (start, end) = relative_range(args, view.len)?;
+            let size = view.ty.size();
+            let bytes = view.with_bytes(|b| b[start * size..end * size].to_vec());
+            let Value::Object(o) = this else {
+                unreachable!()
+            };
+            let len = bytes.len() / size;
+            let buffer = Obj::new(view.buffer.proto(), Kind::ArrayBuffer(bytes.into()));
+            let copy = View {
+                ty: view.ty,
+                buffer,
+                offset: 0,
+                len,
+            };
+            Ok(Value::Object(Obj::new(o.proto(), Kind::TypedArray(copy))))
+        });
+
+        // Errors
+        let error_proto = self.error_proto.clone();
+        for name in [
+            "Error",
+            "TypeError",
+            "RangeError",
+            "SyntaxError",
+            "ReferenceError",
+        ] {
+            let proto = if name == "Error" {
+                error_proto.clone()
+            } else {
+                Obj::new(Some(error_proto.clone()), Kind::Ordinary)
+            };
+            proto.define(PropKey::from_str("name"), str_val(name));
+            proto.define(PropKey::from_str("message"), str_val(""));
+            let make = {
+                let proto = proto.clone();
+                native(move |_, args| {
+                    let message = match arg(args, 0) {
+                        Value::Undefined => None,
+                        v => Some(to_rust_string(&v)),
+                    };
+                    Ok(error_object(&proto, message.as_deref()))
+                })
+            };
+            self.constructor(name, &proto, make.clone(), make);
+        }
+        self.method(&error_proto, "toString", |this, _| {
+            Ok(str_val(&display(this)))
+        });
+
+        // Primitive wrappers and converters
+        self.constructor(
+            "Number",
+            &self.number_proto,
+            native(|_, args| Ok(Value::Number(number_of(&arg(args, 0))?))),
+            native(|_, args| Ok(Value::Number(number_of(&arg(args, 0))?))),
+        );
+        self.constructor(
+            "String",
+            &self.string_proto,
+            native(|_, args| Ok(str_val(&to_rust_string(&arg(args, 0))))),
+            native(|_, args| Ok(str_val(&to_rust_string(&arg(args, 0))))),
+        );
+        self.constructor(
+            "Boolean",
+            &self.boolean_proto,
+            native(|_, args| Ok(Value::Bool(truthy(&arg(args, 0))))),
+            native(|_, args| Ok(Value::Bool(truthy(&arg(args, 0))))),
+        );
+        self.constructor(
+            "BigInt",
+            &self.bigint_proto,
+            native(|_, args| match arg(args, 0) {
+                Value::BigInt(b) => Ok(Value::BigInt(b)),
+                Value::Bool(b) => Ok(Value::BigInt(b as i128)),
+                Value::Number(n) if n.fract() == 0.0 && n.is_finite() => {
+                    Ok(Value::BigInt(n as i128))
+                }
+                v => Err(new_error(
+                    "RangeError",
+                    &format!("cannot convert {} to a BigInt", display(&v)),
+                )),
+            }),
+            native(|_, _| Err(new_error("TypeError", "BigInt is not a constructor"))),
+        );
+        let symbol = self.constructor(
+            "Symbol",
+            &self.symbol_proto,
+            native(|_, args| {
+                Ok(Value::Symbol(Sym::new(match arg(args, 0) {
+                    Value::Undefined => None,
+                    v => Some(js_str(&to_rust_string(&v))),
+                })))
+            }),
+            native(|_, _| Err(new_error("TypeError", "Symbol is not a constructor"))),
+        );
+        symbol.define(
+            PropKey::from_str("iterator"),
+            Value::Symbol(self.iterator.clone()),
+        );
+        for name in [
+            "asyncIterator",
+            "hasInstance",
+            "isConcatSpreadable",
+            "match",
+            "matchAll",
+            "replace",
+            "search",
+            "species",
+            "split",
+            "toPrimitive",
+            "toStringTag",
+            "unscopables",
+        ] {
+            let s = Sym::new(Some(js_str(&format!("Symbol.{name}"))));
+            symbol.define(PropKey::from_str(name), Value::Symbol(s));
+        }
+        let registry: Rc<RefCell<Vec<(JsStr, Sym)>>> = Rc::default();
+        let symbols = registry.clone();
+        self.method(&symbol, "for", move |_, args| {
+            let key = js_str(&to_rust_string(&arg(args, 0)));
+            let mut symbols = symbols.borrow_mut();
+            if let Some((_, s)) = symbols.iter().find(|(k, _)| *k == key) {
+                return Ok(Value::Symbol(s.clone()));
+            }
+            let s = Sym::new(Some(key.clone()));
+            symbols.push((key, s.clone()));
+            Ok(Value::Symbol(s))
+        });
+        self.method(&symbol, "keyFor", move |_, args| {
+            let Value::Symbol(s) = arg(args, 0) else {
+                return Err(new_error("TypeError", "argument is not a symbol"));
+            };
+            Ok(registry
+                .borrow()
+                .iter()
+                .find(|(_, r)| *r == s)
+                .map_or(Value::Undefined, |(k, _)| Value::String(k.clone())))
+        });
+        self.method(&self.symbol_proto, "toString", |this, _| {
+            Ok(str_val(&to_rust_string(this)))
+        });
+
+        // Iteration over the built-in iterables
+        let iterator = PropKey::Symbol(self.iterator.clone());
+        let return_this = self.function(
+            "[Symbol.iterator]",
+            native(|this, _| Ok(this.clone())),
+            None,
+        );
+        self.iterator_proto
+            .define(iterator.clone(), Value::Object(return_this));
+        let values = self.function(
+            "[Symbol.iterator]",
+            native(|this, _| Ok(new_iterator(iterable_items(this)?))),
+            None,
+        );
+        for proto in [
+            &array_proto,
+            &typed_proto,
+            &map_proto,
+            &set_proto,
+            &self.string_proto,
+        ] {
+            proto.define(iterator.clone(), Value::Object(values.clone()));
+        }
+        set_proto.define(PropKey::from_str("values"), Value::Object(values));
+
+        // JSON
+        let json = Obj::new(Some(self.object_proto.clone()), Kind::Ordinary);
+        g.define(PropKey::from_str("JSON"), Value::Object(json.clone()));
+        self.method(&json, "stringify", |_, args| {
+            let replacer = arg(args, 1);
+            let gap = match arg(args, 2) {
+                Value::Number(n) => " ".repeat(n.clamp(0.0, 10.0) as usize),
+                Value::String(s) => String::from_utf16_lossy(&s[..s.len().min(10)]),
+                _ => String::new(),
+            };
+            let mut writer = JsonWriter {
+                replacer: callable(&replacer).is_some().then_some(replacer),
+                gap,
+                indent: String::new(),
+                stack: Vec::new(),
+            };
+            let holder = new_object();
+            set(&holder, &str_val(""), arg(args, 0))?;
+            Ok(writer
+                .property(&holder, str_val(""))?
+                .map_or(Value::Undefined, |s| str_val(&s)))
+        });
+        self.method(&json, "parse", |_, args| {
+            let text = match arg(args, 0) {
+                Value::String(s) => s,
+                v => js_str(&to_rust_string(&v)),
+            };
+            let mut parser = JsonParser { src: &text, pos: 0 };
+            let v = parser.value()?;
+            if parser.pos != text.len() {
+                return Err(parser.error());
+            }
+            let reviver = arg(args, 1);
+            if callable(&reviver).is_none() {
+                return Ok(v);
+            }
+            let root = new_object();
+            set(&root, &str_val(""), v)?;
+            json_revive(&root, str_val(""), &reviver)
+        });
+
+        // Promises and microtasks
+        let promise_proto = self.promise_proto.clone();
+        let promise = self.constructor(
+            "Promise",
+            &promise_proto,
+            native(|_, _| {
+                Err(new_error(
+                    "TypeError",
+                    "Promise constructor cannot be invoked without 'new'",
+                ))
+            }),
+            native(|_, args| {
+                let executor = arg(args, 0);
+                if callable(&executor).is_none() {
+                    return Err(new_error("TypeError", "Promise resolver is not a function"));
+                }
+                let p = new_promise();
+                let (resolve, reject) = resolving_functions(&p);
+                if let Err(e) = call_value(&executor, &Value::Undefined, &[resolve, reject]) {
+                    reject_promise(&p, e);
+                }
+                Ok(Value::Object(p))
+            }),
+        );
+        self.method(&promise, "resolve", |_, args| {
+            let v = arg(args, 0);
+            if let Value::Object(o) = &v
+                && is_promise(o)
+            {
+                return Ok(v);
+            }
+            let p = new_promise();
+            resolve_promise(&p, v);
+            Ok(Value::Object(p))
+        });
+        self.method(&promise, "reject", |_, args| {
+            let p = new_promise();
+            reject_promise(&p, arg(args, 0));
+            Ok(Value::Object(p))
+        });
+        self.method(&promise_proto, "then", |this, args| {
+            promise_then(this, arg(args, 0), arg(args, 1))
+        });
+        self.method(&promise_proto, "catch", |this, args| {
+            promise_then(this, Value::Undefined, arg(args, 0))
+        });
+        self.method(g, "queueMicrotask", |_, args| {
+            let f = arg(args, 0);
+            if callable(&f).is_none() {
+                return Err(new_error("TypeError", "callback is not a function"));
+            }
+            enqueue_microtask(move || {
+                if let Err(e) = call_value(&f, &Value::Undefined, &[]) {
+                    println!("Uncaught {}", display(&e));
+                }
+            });
+            Ok(Value::Undefined)
+        });
+
+        self.method(&self.string_proto, "toString", |this, _| Ok(this.clone()));
+        self.method(&self.string_proto, "charCodeAt", |this, args| {
+            let s = to_js_str(this);
+            let i = to_number(&arg(args, 0))?;
+            let i = if i.is_nan() { 0.0 } else { i.trunc() };
+            Ok(Value::Number(if i < 0.0 {
+                f64::NAN
+            } else {
+                s.get(i as usize).map_or(f64::NAN, |&c| c as f64)
+            }))
+        });
+        self.method(&self.string_proto, "slice", |this, args| {
+            let s = to_js_str(this);
+            let (start, end) = relative_range(args, s.len())?;
+            Ok(Value::String(Rc::from(&s[start..end])))
+        });
+        self.method(&self.string_proto, "concat", |this, args| {
+            let mut s = to_js_str(this).to_vec();
+            for a in args {
+                s.extend_from_slice(&to_js_str(a));
+            }
+            Ok(Value::String(s.into()))
+        });
+        self.method(&self.string_proto, "startsWith", |this, args| {
+            let s = to_js_str(this);
+            Ok(Value::Bool(s.starts_with(&to_js_str(&arg(args, 0)))))
+        });
+        self.method(&self.string_proto, "indexOf", |this, args| {
+            let s = to_js_str(this);
+            let needle = to_js_str(&arg(args, 0));
+            let from = (to_number(&arg(args, 1))? as usize).min(s.len());
+            Ok(Value::Number(
+                (from..=s.len() - needle.len().min(s.len()))
+                    .find(|&i| s[i..].starts_with(&needle))
+                    .map_or(-1.0, |i| i as f64),
+            ))
+        });
+        self.method(&self.number_proto, "toString", |this, _| {
+            Ok(str_val(&to_rust_string(this)))
+        });
+    }
+}
+
+/// Collects the elements of an array, typed array, Map (as `[key, value]`
+/// pairs), Set, string or array-like object, the subset of iterables the mock
+/// knows.
+fn iterable_items(v: &Value) -> Result<Vec<Value>, Value> {
+    match v {
+        Value::String(s) => Ok(char::decode_utf16(s.iter().copied())
+            .map(|c| str_val(&c.unwrap_or(char::REPLACEMENT_CHARACTER).to_string()))
+            .collect()),
+        Value::Object(o) => {
+            let entries = match &o.0.borrow().kind {
+                Kind::Map(entries) => Some(entries.clone()),
+                _ => None,
+            };
+            if let Some(entries) = entries {
+                return Ok(entries
+                    .into_iter()
+                    .map(|(k, v)| new_array(vec![k, v]))
+                    .collect());
+            }
+            if let Kind::Set(items) = &o.0.borrow().kind {
+                return Ok(items.clone());
+            }
+            if matches!(o.0.borrow().kind, Kind::Ordinary) {
+                let method = lookup(Some(o.clone()), &PropKey::Symbol(realm().iterator));
+                if let Some(method) = method.filter(|m| callable(m).is_some()) {
+                    return drain_iterator(&call_value(&method, v, &[])?);
+                }
+            }
+            Ok(array_items(o))
+        }
+        Value::Undefined | Value::Null => Err(new_error(
+            "TypeError",
+            &format!("{} is not iterable", to_rust_string(v)),
+        )),
+        _ => Ok(Vec::new()),
+    }
+}
+
+/// Runs an iterator object to completion.
+fn drain_iterator(iter: &Value) -> Result<Vec<Value>, Value> {
+    let next = get(iter, &str_val("next"))?;
+    let mut items = Vec::new();
+    loop {
+        let result = call_value(&next, iter, &[])?;
+        if !matches!(result, Value::Object(_)) {
+            return Err(new_error(
+                "TypeError",
+                &format!("Iterator result {} is not an object", display(&result)),
+            ));
+        }
+        if truthy(&get_property(&result, "done")) {
+            return Ok(items);
+        }
+        items.push(get_property(&result, "value"));
+    }
+}
+
+/// Serialization state of `JSON.stringify`.
+struct JsonWriter {
+    replacer: Option<Value>,
+    gap: String,
+    indent: String,
+    stack: Vec<usize>,
+}
+
+impl JsonWriter {
+    /// Serializes `holder[key]`, or returns `None` if it has no JSON form.
+    fn property(&mut self, holder: &Value, key: Value) -> Result<Option<String>, Value> {
+        let mut value = get(holder, &key)?;
+        if matches!(value, Value::Object(_) | Value::BigInt(_)) {
+            let to_json = get(&value, &str_val("toJSON"))?;
+            if callable(&to_json).is_some() {
+                value = call_value(&to_json, &value, core::slice::from_ref(&key))?;
+            }
+        }
+        if let Some(replacer) = &self.replacer {
+            value = call_value(replacer, holder, &[key, value])?;
+        }
+        Ok(Some(match &value {
+            Value::Null => String::from("null"),
+            Value::Bool(b) => b.to_string(),
+            Value::Number(n) if n.is_finite() => number_to_string(*n),
+            Value::Number(_) => String::from("null"),
+            Value::String(s) => json_quote(s),
+            Value::BigInt(_) => {
+                return Err(new_error(
+                    "TypeError",
+                    "Do not know how to serialize a BigInt",
+                ));
+            }
+            Value::Undefined | Value::Symbol(_) => return Ok(None),
+            Value::Object(o) if o.is_callable() => return Ok(None),
+            Value::Object(o) => self.container(o, &value)?,
+        }))
+    }
+
+    fn container(&mut self, o: &Obj, value: &Value) -> Result<String, Value> {
+        if self.stack.contains(&o.addr()) {
+            return Err(new_error(
+                "TypeError",
+                "Converting circular structure to JSON",
+            ));
+        }
+        self.stack.push(o.addr());
+        let stepback = self.indent.clone();
+        self.indent.push_str(&self.gap.clone());
+        let array = is_array(o);
+        let mut parts = Vec::new();
+        if array {
+            for i in 0..array_items(o).len() {
+                let item = self.property(value, str_val(&i.to_string()))?;
+                parts.push(item.unwrap_or_else(|| String::from("null")));
+            }
+        } else {
+            for (k, _) in own_entries(value) {
+                let Value::String(name) = &k else {
+                    unreachable!()
+                };
+                let name = json_quote(name);
+                if let Some(v) = self.property(value, k)? {
+                    let colon = if self.gap.is_empty() { ":" } else { ": " };
+                    parts.push(format!("{name}{colon}{v}"));
+                }
+            }
+        }
+        let (open, close) = if array { ('[', ']') } else { ('{', '}') };
+        let out = if parts.is_empty() {
+            format!("{open}{close}")
+        } else if self.gap.is_empty() {
+            format!("{open}{}{close}", parts.join(","))
+        } else {
+            let sep = format!(",\n{}", self.indent);
+            format!(
+                "{open}\n{}{}\n{stepback}{close}",
+                self.indent,
+                parts.join(&sep)
+            )
+        };
+        self.stack.pop();
+        self.indent = stepback;
+        Ok(out)
+    }
+}
+
+fn json_quote(s: &[u16]) -> String {
+    let mut out = String::from("\"");
+    for c in char::decode_utf16(s.iter().copied()) {
+        match c {
+            Ok('"') => out.push_str("\\\""),
+            Ok('\\') => out.push_str("\\\\"),
+            Ok('\n') => out.push_str("\\n"),
+            Ok('\r') => out.push_str("\\r"),
+            Ok('\t') => out.push_str("\\t"),
+            Ok('\u{8}') => out.push_str("\\b"),
+            Ok('\u{c}') => out.push_str("\\f"),
+            Ok(c) if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            Ok(c) => out.push(c),
+            Err(e) => out.push_str(&format!("\\u{:04x}", e.unpaired_surrogate())),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// A recursive-descent parser for `JSON.parse`.
+struct JsonParser<'a> {
+    src: &'a [u16],
+    pos: usize,
+}
+
+impl JsonParser<'_> {
+    fn error(&self) -> Value {
+        match self.src.get(self.pos) {
+            Some(&c) => new_error(
+                "SyntaxError",
+                &format!(
+                    "Unexpected token '{}' in JSON at position {}",
+                    String::from_utf16_lossy(&[c]),
+                    self.pos
+                ),
+            ),
+            None => new_error("SyntaxError", "Unexpected end of JSON input"),
+        }
+    }
+
+    /// The current code unit, if it's ASCII
+    fn byte(&self) -> Option<u8> {
+        self.src
+            .get(self.pos)
+            .and_then(|&c| u8::try_from(c).ok())
+            .filter(u8::is_ascii)
+    }
+
+    fn eat(&mut self, b: u8) -> Result<(), Value> {
+        if self.byte() != Some(b) {
+            return Err(self.error());
+        }
+        self.pos += 1;
+        Ok(())
+    }
+
+    fn skip_ws(&mut self) {
+        while matches!(self.byte(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.pos += 1;
+        }
+    }
+
+    /// Parses a value and the whitespace around it
+    fn value(&mut self) -> Result<Value, Value> {
+        self.skip_ws();
+        let v = match self.byte() {
+            Some(b'{') => self.object()?,
+            Some(b'[') => self.array()?,
+            Some(b'"') => Value::String(self.string()?),
+            Some(b't') => self.literal("true", Value::Bool(true))?,
+            Some(b'f') => self.literal("false", Value::Bool(false))?,
+            Some(b'n') => self.literal("null", Value::Null)?,
+            Some(b'-' | b'0'..=b'9') => self.number()?,
+            _ => return Err(self.error()),
+        };
+        self.skip_ws();
+        Ok(v)
+    }
+
+    fn literal(&mut self, word: &str, v: Value) -> Result<Value, Value> {
+        for b in word.bytes() {
+            self.eat(b)?;
+        }
+        Ok(v)
+    }
+
+    fn object(&mut self) -> Result<Value, Value> {
+        self.eat(b'{')?;
+        let obj = new_object();
+        let Value::Object(o) = &obj else {
+            unreachable!()
+        };
+        self.skip_ws();
+        if self.byte() == Some(b'}') {
+            self.pos += 1;
+            return Ok(obj);
+        }
+        loop {
+            self.skip_ws();
+            if self.byte() != Some(b'"') {
+                return Err(self.error());
+            }
+            let key = self.string()?;
+            self.skip_ws();
+            self.eat(b':')?;
+            let v = self.value()?;
+            o.define(PropKey::Str(key), v);
+            match self.byte() {
+                Some(b',') => self.pos += 1,
+                Some(b'}') => {
+                    self.pos += 1;
+                    return Ok(obj);
+                }
+                _ => return Err(self.error()),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Value, Value> {
+        self.eat(b'[')?;
+        let mut items = Vec::new();
+        self.skip_ws();
+        if self.byte() == Some(b']') {
+            self.pos += 1;
+            return Ok(new_array(items));
+        }
+        loop {
+            items.push(self.value()?);
+            match self.byte() {
+                Some(b',') => self.pos += 1,
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(new_array(items));
+                }
+                _ => return Err(self.error()),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<JsStr, Value> {
+        self.eat(b'"')?;
+        let mut out = Vec::new();
+        loop {
+            let Some(&c) = self.src.get(self.pos) else {
+                return Err(self.error());
+            };
+            match u8::try_from(c) {
+                Ok(b'"') => {
+                    self.pos += 1;
+                    return Ok(out.into());
+                }
+                Ok(b'\\') => {
+                    self.pos += 1;
+                    let unit = match self.byte() {
+                        Some(b'"') => b'"' as u16,
+                        Some(b'\\') => b'\\' as u16,
+                        Some(b'/') => b'/' as u16,
+                        Some(b'b') => 8,
+                        Some(b'f') => 12,
+                        Some(b'n') => b'\n' as u16,
+                        Some(b'r') => b'\r' as u16,
+                        Some(b't') => b'\t' as u16,
+                        Some(b'u') => {
+                            let hex = self.src.get(self.pos + 1..self.pos + 5);
+                            let hex = hex.map(String::from_utf16_lossy);
+                            match hex.and_then(|h| u16::from_str_radix(&h, 16).ok()) {
+                                Some(unit) => {
+                                    self.pos += 4;
+                                    unit
+                                }
+                                None => return Err(self.error()),
+                            }
+                        }
+                        _ => return Err(self.error()),
+                    };
+                    out.push(unit);
+                    self.pos += 1;
+                }
+                Ok(0..0x20) => return Err(self.error()),
+                _ => {
+                    out.push(c);
+                    self.pos += 1;
+                }
+            }
+        }
+    }
+
+    fn digits(&mut self) -> Result<(), Value> {
+        if !matches!(self.byte(), Some(b'0'..=b'9')) {
+            return Err(self.error());
+        }
+        while matches!(self.byte(), Some(b'0'..=b'9')) {
+            self.pos += 1;
+        }
+        Ok(())
+    }
+
+    fn number(&mut self) -> Result<Value, Value> {
+        let start = self.pos;
+        if self.byte() == Some(b'-') {
+            self.pos += 1;
+        }
+        if self.byte() == Some(b'0') {
+            self.pos += 1;
+        } else {
+            self.digits()?;
+        }
+        if self.byte() == Some(b'.') {
+            self.pos += 1;
+            self.digits()?;
+        }
+        if matches!(self.byte(), Some(b'e' | b'E')) {
+            self.pos += 1;
+            if matches!(self.byte(), Some(b'+' | b'-')) {
+                self.pos += 1;
+            }
+            self.digits()?;
+        }
+        let text = String::from_utf16_lossy(&self.src[start..self.pos]);
+        Ok(Value::Number(text.parse().unwrap_or(f64::NAN)))
+    }
+}
+
+/// Applies a `JSON.parse` reviver to `holder[key]`, children first.
+fn json_revive(holder: &Value, key: Value, reviver: &Value) -> Result<Value, Value> {
+    let val = get(holder, &key)?;
+    if let Value::Object(o) = &val {
+        let keys: Vec<Value> = if is_array(o) {
+            (0..array_items(o).len())
+                .map(|i| str_val(&i.to_string()))
+                .collect()
+        } else {
+            own_entries(&val).into_iter().map(|(k, _)| k).collect()
+        };
+        for k in keys {
+            match json_revive(&val, k.clone(), reviver)? {
+                Value::Undefined => o.remove(&to_property_key(&k)),
+                v => o.define(to_property_key(&k), v),
+            }
+        }
+    }
+    call_value(reviver, holder, &[key, val])
+}
+
+/// Creates an iterator object over a snapshot of `items`.
+fn new_iterator(items: Vec<Value>) -> Value {
+    let realm = realm();
+    let iter = Obj::new(Some(realm.iterator_proto.clone()), Kind::Ordinary);
+    let items = RefCell::new(items.into_iter());
+    realm.method(&iter, "next", move |_, _| {
+        let next = items.borrow_mut().next();
+        let result = new_object();
+        set(&result, &str_val("done"), Value::Bool(next.is_none()))?;
+        set(&result, &str_val("value"), next.unwrap_or(Value::Undefined))?;
+        Ok(result)
+    });
+    Value::Object(iter)
+}
+
+/// Collects the own enumerable string-keyed properties of `v`, as
+/// `Object.entries` does.
+fn own_entries(v: &Value) -> Vec<(Value, Value)> {
+    match v {
+        Value::Object(o) => o
+            .own_keys()
+            .into_iter()
+            .filter(|k| matches!(k, PropKey::Str(_)))
+            .map(|k| {
+                let val = o.own(&k).unwrap_or(Value::Undefined);
+                (k.to_value(), val)
+            })
+            .collect(),
+        Value::String(s) => (0..s.len())
+            .map(|i| (str_val(&i.to_string()), Value::String(Rc::from([s[i]]))))
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn map_entries(this: &Value) -> Result<Vec<(Value, Value)>, Value> {
+    if let Value::Object(o) = this
+        && let Kind::Map(entries) = &o.0.borrow().kind
+    {
+        return Ok(entries.clone());
+    }
+    Err(new_error(
+        "TypeError",
+        "Method Map.prototype called on incompatible receiver",
+    ))
+}
+
+fn set_items(this: &Value) -> Result<Vec<Value>, Value> {
+    if let Value::Object(o) = this
+        && let Kind::Set(items) = &o.0.borrow().kind
+    {
+        return Ok(items.clone());
+    }
+    Err(new_error(
+        "TypeError",
+        "Method Set.prototype called on incompatible receiver",
+    ))
+}
+
+fn set_add(set: &Obj, val: Value) {
+    let mut data = set.0.borrow_mut();
+    let Kind::Set(items) = &mut data.kind else {
+        return;
+    };
+    let id = identity(&val);
+    if !items.iter().any(|v| identity(v) == id) {
+        items.push(val);
+    }
+}
+
+fn map_set(map: &Obj, key: Value, val: Value) {
+    let mut data = map.0.borrow_mut();
+    let Kind::Map(entries) = &mut data.kind else {
+        return;
+    };
+    let id = identity(&key);
+    match entries.iter_mut().find(|(k, _)| identity(k) == id) {
+        Some(slot) => slot.1 = val,
+        None => entries.push((key, val)),
+    }
+}
+
+fn to_index(v: &Value) -> Result<usize, Value> {
+    let n = match v {
+        Value::Undefined => 0.0,
+        v => to_number(v)?,
+    };
+    let n = if n.is_nan() { 0.0 } else { n.trunc() };
+    if !(0.0..=9007199254740991.0).contains(&n) {
+        return Err(new_error("RangeError", "Invalid array length"));
+    }
+    Ok(n as usize)
+}
+
+/// Resolves the `(start, end)` arguments of `slice`-like methods
+fn relative_range(args: &[Value], len: usize) -> Result<(usize, usize), Value> {
+    let len = len as i64;
+    let clamp = |v: Value, default: i64| -> Result<usize, Value> {
+        let n = match v {
+            Value::Undefined => default,
+            v => to_number(&v)? as i64,
+        };
+        Ok((if n < 0 { (len + n).max(0) } else { n.min(len) }) as usize)
+    };
+    let start = clamp(arg(args, 0), 0)?;
+    let end = clamp(arg(args, 1), len)?;
+    Ok((start, end.max(start)))
+}
+
+fn this_view(this: &Value) -> Result<View, Value> {
+    if let Value::Object(o) = this
+        && let Kind::TypedArray(view) = &o.0.borrow().kind
+    {
+        return Ok(view.clone());
+    }
+    Err(new_error("TypeError", "this is not a typed array"))
+}
+
+/// `new <Type>Array(length | ArrayBuffer[, byteOffset[, length]] | iterable)`
+fn new_view(ty: ElemType, proto: &Obj, buffer_proto: &Obj, args: &[Value]) -> Result<Value, Value> {
+    let size = ty.size();
+    let view = match arg(args, 0) {
+        Value::Object(o) if matches!(o.0.borrow().kind, Kind::ArrayBuffer(_)) => {
+            let byte_len = match &o.0.borrow().kind {
+                Kind::ArrayBuffer(buffer) => buffer.len(),
+                _ => 0,
+            };
+            let offset = to_index(&arg(args, 1))?;
+            if offset % size != 0 || offset > byte_len {
+                return Err(new_error(
+                    "RangeError",
+                    &format!(
+                        "start offset of {} should be a multiple of {size}",
+                        ty.name()
+                    ),
+                ));
+            }
+            let len = match arg(args, 2) {
+                Value::Undefined if (byte_len - offset) % size != 0 => {
+                    return Err(new_error(
+                        "RangeError",
+                        &format!(
+                            "byte length of {} should be a multiple of {size}",
+                            ty.name()
+                        ),
+                    ));
+                }
+                Value::Undefined => (byte_len - offset) / size,
+                v => to_index(&v)?,
+            };
+            if offset + len * size > byte_len {
+                return Err(new_error("RangeError", "Invalid typed array length"));
+            }
+            View {
+                ty,
+                buffer: o,
+                offset,
+                len,
+            }
+        }
+        src @ Value::Object(_) => {
+            let items = iterable_items(&src)?;
+            let buffer = Obj::new(
+                Some(buffer_proto.clone()),
+                Kind::ArrayBuffer(vec![0; items.len() * size].into()),
+            );
+            let view = View {
+                ty,
+                buffer,
+                offset: 0,
+                len: items.len(),
+            };
+            for (i, v) in items.iter().enumerate() {
+                view.set(i, v);
+            }
+            view
+        }
+        v => {
+            let len = to_index(&v)?;
+            View {
+                ty,
+                buffer: Obj::new(
+                    Some(buffer_proto.clone()),
+                    Kind::ArrayBuffer(vec![0; len * size].into()),
+                ),
+                offset: 0,
+                len,
+            }
+        }
+    };
+    Ok(Value::Object(Obj::new(
+        Some(proto.clone()),
+        Kind::TypedArray(view),
+    )))
+}
+
+fn error_object(proto: &Obj, message: Option<&str>) -> Value {
+    let err = Obj::new(Some(proto.clone()), Kind::Error);
+    if let Some(message) = message {
+        err.define(PropKey::from_str("message"), str_val(message));
+    }
+    let name = to_rust_string(&get_property(&Value::Object(proto.clone()), "name"));
+    let stack = format!("{name}: {}\n    at <mock host>", message.unwrap_or(""));
+    err.define(PropKey::from_str("stack"), str_val(&stack));
+    Value::Object(err)
+}
+
+/// Creates a new error object of the global constructor `kind`, e.g. `"TypeError"`.
+pub fn new_error(kind: &str, message: &str) -> Value {
+    let realm = realm();
+    let proto = match realm.global.own(&PropKey::from_str(kind)) {
+        Some(Value::Object(ctor)) => match ctor.own(&PropKey::from_str("prototype")) {
+            Some(Value::Object(p)) => p,
+            _ => realm.error_proto.clone(),
+        },
+        _ => realm.error_proto.clone(),
+    };
+    error_object(&proto, Some(message))
+}
+
+fn enqueue_microtask(job: impl FnOnce() + 'static) {
+    MICROTASKS.with(|q| q.borrow_mut().push_back(Box::new(job)));
+}
+
+/// Runs queued microtasks (promise reactions and `queueMicrotask` callbacks)
+/// until the queue is empty, like a JS engine does once the stack unwinds.
+pub fn run_microtasks() {
+    while run_microtask() {}
+}
+
+/// Runs the oldest queued microtask, returning whether there was one.
+pub fn run_microtask() -> bool {
+    match MICROTASKS.with(|q| q.borrow_mut().pop_front()) {
+        Some(job) => {
+            job();
+            true
+        }
+        None => false,
+    }
+}
+
+/// Returns the number of queued microtasks.
+pub fn pending_microtasks() -> usize {
+    MICROTASKS.with(|q| q.borrow().len())
+}
+
+fn new_promise() -> Obj {
+    Obj::new(
+        Some(realm().promise_proto),
+        Kind::Promise(PromiseState::Pending(Vec::new())),
+    )
+}
+
+fn is_promise(o: &Obj) -> bool {
+    matches!(o.0.borrow().kind, Kind::Promise(_))
+}
+
+/// Returns the `resolve`/`reject` pair handed to a promise executor. Only the
+/// first call of either has an effect.
+fn resolving_functions(p: &Obj) -> (Value, Value) {
+    let done = Rc::new(core::cell::Cell::new(false));
+    let realm = realm();
+    let resolve = {
+        let (p, done) = (p.clone(), done.clone());
+        realm.function(
+            "resolve",
+            native(move |_, args| {
+                if !done.replace(true) {
+                    resolve_promise(&p, arg(args, 0));
+                }
+                Ok(Value::Undefined)
+            }),
+            None,
+        )
+    };
+    let reject = {
+        let p = p.clone();
+        realm.function(
+            "reject",
+            native(move |_, args| {
+                if !done.replace(true) {
+                    reject_promise(&p, arg(args, 0));
+                }
+                Ok(Value::Undefined)
+            }),
+            None,
+        )
+    };
+    (Value::Object(resolve), Value::Object(reject))
+}
+
+fn resolve_promise(p: &Obj, v: Value) {
+    if let Value::Object(o) = &v {
+        if o.ptr_eq(p) {
+            reject_promise(
+                p,
+                new_error("TypeError", "Chaining cycle detected for promise"),
+            );
+            return;
+        }
+        match get(&v, &str_val("then")) {
+            Ok(then) if callable(&then).is_some() => {
+                let (p, thenable) = (p.clone(), v.clone());
+                enqueue_microtask(move || {
+                    let (resolve, reject) = resolving_functions(&p);
+                    if let Err(e) = call_value(&then, &thenable, &[resolve, reject.clone()]) {
+                        let _ = call_value(&reject, &Value::Undefined, &[e]);
+                    }
+                });
+                return;
+            }
+            Ok(_) => {}
+            Err(e) => {
+                reject_promise(p, e);
+                return;
+            }
+        }
+    }
+    settle(p, Ok(v));
+}
+
+fn reject_promise(p: &Obj, e: Value) {
+    settle(p, Err(e));
+}
+
+fn settle(p: &Obj, result: Result<Value, Value>) {
+    let reactions = {
+        let mut data = p.0.borrow_mut();
+        let Kind::Promise(state) = &mut data.kind else {
+            return;
+        };
+        if !matches!(state, PromiseState::Pending(_)) {
+            return;
+        }
+        let settled = match &result {
+            Ok(v) => PromiseState::Fulfilled(v.clone()),
+            Err(e) => PromiseState::Rejected(e.clone()),
+        };
+        match core::mem::replace(state, settled) {
+            PromiseState::Pending(reactions) => reactions,
+            _ => Vec::new(),
+        }
+    };
+    for reaction in reactions {
+        schedule_reaction(reaction, result.clone());
+    }
+}
+
+fn schedule_reaction(reaction: Reaction, result: Result<Value, Value>) {
+    enqueue_microtask(move || {
+        let (handler, value) = match &result {
+            Ok(v) => (&reaction.on_fulfilled, v),
+            Err(e) => (&reaction.on_rejected, e),
+        };
+        if callable(handler).is_none() {
+            match result {
+                Ok(v) => resolve_promise(&reaction.derived, v),
+                Err(e) => reject_promise(&reaction.derived, e),
+            }
+            return;
+        }
+        match call_value(handler, &Value::Undefined, core::slice::from_ref(value)) {
+            Ok(v) => resolve_promise(&reaction.derived, v),
+            Err(e) => reject_promise(&reaction.derived, e),
+        }
+    });
+}
+
+fn promise_then(this: &Value, on_fulfilled: Value, on_rejected: Value) -> Result<Value, Value> {
+    let Value::Object(p) = this else {
+        return Err(new_error("TypeError", "then called on non-promise"));
+    };
+    let reaction = Reaction {
+        on_fulfilled,
+        on_rejected,
+        derived: new_promise(),
+    };
+    let derived = Value::Object(reaction.derived.clone());
+    let mut data = p.0.borrow_mut();
+    let Kind::Promise(state) = &mut data.kind else {
+        return Err(new_error("TypeError", "then called on non-promise"));
+    };
+    match state {
+        PromiseState::Pending(reactions) => reactions.push(reaction),
+        PromiseState::Fulfilled(v) => {
+            let v = v.clone();
+            drop(data);
+            schedule_reaction(reaction, Ok(v));
+        }
+        PromiseState::Rejected(e) => {
+            let e = e.clone();
+            drop(data);
+            schedule_reaction(reaction, Err(e));
+        }
+    }
+    Ok(derived)
+}
+
+/// Creates a new plain object.
+pub fn new_object() -> Value {
+    Value::Object(Obj::new(Some(realm().object_proto), Kind::Ordinary))
+}
+
+/// Creates a new array holding `items`.
+pub fn new_array(items: Vec<Value>) -> Value {
+    Value::Object(Obj::new(Some(realm().array_proto), Kind::Array(items)))
+}
+
+/// Creates a JS function backed by a Rust closure receiving `(this, args)`.
+pub fn new_function(
+    name: &str,
+    f: impl Fn(&Value, &[Value]) -> Result<Value, Value> + 'static,
+) -> Value {
+    Value::Object(realm().function(name, native(f), None))
+}
+
+fn is_array(o: &Obj) -> bool {
+    matches!(o.0.borrow().kind, Kind::Array(_))
+}
+
+fn array_items(o: &Obj) -> Vec<Value> {
+    match &o.0.borrow().kind {
+        Kind::Array(items) => items.clone(),
+        _ => {
+            let len =
+                to_number(&get_property(&Value::Object(o.clone()), "length")).unwrap_or(f64::NAN);
+            let len = if len.is_finite() && len > 0.0 {
+                len as usize
+            } else {
+                0
+            };
+            (0..len)
+                .map(|i| {
+                    o.own(&PropKey::from_str(&i.to_string()))
+                        .unwrap_or(Value::Undefined)
+                })
+                .collect()
+        }
+    }
+}
+
+fn join(o: &Obj, sep: &str) -> String {
+    array_items(o)
+        .iter()
+        .map(|v| match v {
+            Value::Undefined | Value::Null => String::new(),
+            v => to_rust_string(v),
+        })
+        .collect::<Vec<_>>()
+        .join(sep)
+}
+
+fn class_name(v: &Value) -> &'static str {
+    match v {
+        Value::Undefined => "Undefined",
+        Value::Null => "Null",
+        Value::Bool(_) => "Boolean",
+        Value::Number(_) => "Number",
+        Value::BigInt(_) => "BigInt",
+        Value::String(_) => "String",
+        Value::Symbol(_) => "Symbol",
+        Value::Object(o) => match o.0.borrow().kind {
+            Kind::Array(_) => "Array",
+            Kind::Error => "Error",
+            Kind::Function(_) => "Function",
+            Kind::Promise(_) => "Promise",
+            Kind::Map(_) => "Map",
+            Kind::Set(_) => "Set",
+            Kind::ArrayBuffer(_) => "ArrayBuffer",
+            Kind::TypedArray(ref view) => view.ty.name(),
+            Kind::Ordinary => "Object",
+        },
+    }
+}
+
+/// Formats a number the way `Number.prototype.toString` does for common values.
+fn number_to_string(n: f64) -> String {
+    if n.is_nan() {
+        String::from("NaN")
+    } else if n.is_infinite() {
+        String::from(if n > 0.0 { "Infinity" } else { "-Infinity" })
+    } else if n == 0.0 {
+        String::from("0")
+    } else if n.fract() == 0.0 && n.abs() < 1e21 {
+        format!("{}", n as i128)
+    } else {
+        format!("{n}")
+    }
+}
+
+/// JS's ToNumber, which throws for BigInts and symbols
+fn to_number(v: &Value) -> Result<f64, Value> {
+    Ok(match v {
+        Value::Undefined => f64::NAN,
+        Value::Null => 0.0,
+        Value::Bool(b) => *b as u8 as f64,
+        Value::Number(n) => *n,
+        Value::BigInt(_) => {
+            return Err(new_error(
+                "TypeError",
+                "Cannot convert a BigInt value to a number",
+            ));
+        }
+        Value::Symbol(_) => {
+            return Err(new_error(
+                "TypeError",
+                "Cannot convert a Symbol value to a number",
+            ));
+        }
+        Value::String(s) => string_to_number(&String::from_utf16_lossy(s)),
+        Value::Object(_) => match to_primitive(v) {
+            Value::Object(_) => f64::NAN,
+            p => to_number(&p)?,
+        },
+    })
+}
+
+/// Converts like `Number(v)`, which unlike ToNumber accepts BigInts
+fn number_of(v: &Value) -> Result<f64, Value> {
+    match v {
+        Value::BigInt(b) => Ok(*b as f64),
+        v => to_number(v),
+    }
+}
+
+fn string_to_number(s: &str) -> f64 {
+    let s = s.trim();
+    if s.is_empty() {
+        return 0.0;
+    }
+    match s {
+        "Infinity" | "+Infinity" => return f64::INFINITY,
+        "-Infinity" => return f64::NEG_INFINITY,
+        _ => {}
+    }
+    if let Some(hex) = s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        return u64::from_str_radix(hex, 16).map_or(f64::NAN, |n| n as f64);
+    }
+    if s.bytes()
+        .any(|c| c.is_ascii_alphabetic() && c != b'e' && c != b'E')
+    {
+        return f64::NAN;
+    }
+    s.parse::<f64>().unwrap_or(f64::NAN)
+}
+
+fn to_primitive(v: &Value) -> Value {
+    match v {
+        Value::Object(o) => match &o.0.borrow().kind {
+            Kind::Array(_) | Kind::TypedArray(_) | Kind::Error | Kind::Function(_) => {
+                str_val(&to_rust_string(v))
+            }
+            _ => str_val(&format!("[object {}]", class_name(v))),
+        },
+        v => v.clone(),
+    }
+}
+
+/// Converts like `Number(v) | 0`
+fn to_int32(v: &Value) -> i32 {
+    let n = number_of(v).unwrap_or(f64::NAN);
+    if !n.is_finite() {
+        return 0;
+    }
+    (n.trunc().rem_euclid(4294967296.0) as u64) as u32 as i32
+}
+
+fn to_rust_string(v: &Value) -> String {
+    match v {
+        Value::Undefined => String::from("undefined"),
+        Value::Null => String::from("null"),
+        Value::Bool(b) => b.to_string(),
+        Value::Number(n) => number_to_string(*n),
+        Value::BigInt(b) => b.to_string(),
+        Value::String(s) => String::from_utf16_lossy(s),
+        Value::Symbol(s) => format!(
+            "Symbol({})",
+            s.description()
+                .map_or_else(String::new, |d| String::from_utf16_lossy(d))
+        ),
+        Value::Object(o) => {
+            let kind = match &o.0.borrow().kind {
+                Kind::Array(_) | Kind::TypedArray(_) => 0,
+                Kind::Error => 1,
+                Kind::Function(_) => 2,
+                _ => 3,
+            };
+            match kind {
+                0 => join(o, ","),
+                1 => {
+                    let name = to_rust_string(&get_property(v, "name"));
+                    let message = to_rust_string(&get_property(v, "message"));
+                    if message.is_empty() {
+                        name
+                    } else {
+                        format!("{name}: {message}")
+                    }
+                }
+                2 => String::from("function () { [native code] }"),
+                _ => format!("[object {}]", class_name(v)),
+            }
+        }
+    }
+}
+
+fn to_property_key(v: &Value) -> PropKey {
+    match v {
+        Value::String(s) => PropKey::Str(s.clone()),
+        Value::Symbol(s) => PropKey::Symbol(s.clone()),
+        v => PropKey::from_str(&to_rust_string(v)),
+    }
+}
+
+fn to_object(v: &Value) -> Value {
+    match v {
+        Value::Undefined | Value::Null => new_object(),
+        v => v.clone(),
+    }
+}
+
+fn display(v: &Value) -> String {
+    match v {
+        Value::BigInt(b) => format!("{b}n"),
+        Value::Object(o) if is_array(o) => {
+            let items = array_items(o)
+                .iter()
+                .map(|v| match v {
+                    Value::String(s) => format!("'{}'", String::from_utf16_lossy(s)),
+                    v => display(v),
+                })
+                .collect::<Vec<_>>();
+            format!("[ {} ]", items.join(", "))
+        }
+        v => to_rust_string(v),
+    }
+}
+
+fn truthy(v: &Value) -> bool {
+    match v {
+        Value::Undefined | Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => !(n.is_nan() || *n == 0.0),
+        Value::BigInt(b) => *b != 0,
+        Value::String(s) => !s.is_empty(),
+        Value::Symbol(_) | Value::Object(_) => true,
+    }
+}
+
+fn type_of(v: &Value) -> &'static str {
+    match v {
+        Value::Undefined => "undefined",
+        Value::Null => "object",
+        Value::Bool(_) => "boolean",
+        Value::Number(_) => "number",
+        Value::BigInt(_) => "bigint",
+        Value::String(_) => "string",
+        Value::Symbol(_) => "symbol",
+        Value::Object(o) if o.is_callable() => "function",
+        Value::Object(_) => "object",
+    }
+}
+
+fn strictly_equals(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Undefined, Value::Undefined) | (Value::Null, Value::Null) => true,
+        (Value::Bool(a), Value::Bool(b)) => a == b,
+        (Value::Number(a), Value::Number(b)) => a == b,
+        (Value::BigInt(a), Value::BigInt(b)) => a == b,
+        (Value::String(a), Value::String(b)) => a == b,
+        (Value::Symbol(a), Value::Symbol(b)) => a == b,
+        (Value::Object(a), Value::Object(b)) => a.ptr_eq(b),
+        _ => false,
+    }
+}
+
+fn loosely_equals(a: &Value, b: &Value) -> bool {
+    match (a, b) {
+        (Value::Undefined | Value::Null, Value::Undefined | Value::Null) => true,
+        (Value::Undefined | Value::Null, _) | (_, Value::Undefined | Value::Null) => false,
+        (Value::Object(_), Value::Object(_)) => strictly_equals(a, b),
+        (Value::BigInt(x), Value::Number(y)) | (Value::Number(y), Value::BigInt(x)) => {
+            *x as f64 == *y
+        }
+        (Value::Object(_), p) | (p, Value::Object(_)) => {
+            let prim = to_primitive(if matches!(a, Value::Object(_)) { a } else { b });
+            loosely_equals(&prim, p)
+        }
+        _ if core::mem::discriminant(a) == core::mem::discriminant(b) => strictly_equals(a, b),
+        _ => matches!((number_of(a), number_of(b)), (Ok(x), Ok(y)) if x == y),
+    }
+}
+
+fn compare(a: &Value, b: &Value) -> Option<core::cmp::Ordering> {
+    let (a, b) = (to_primitive(a), to_primitive(b));
+    match (&a, &b) {
+        (Value::String(x), Value::String(y)) => Some(x.cmp(y)),
+        (Value::BigInt(x), Value::BigInt(y)) => Some(x.cmp(y)),
+        _ => number_of(&a).ok()?.partial_cmp(&number_of(&b).ok()?),
+    }
+}
+
+fn instance_of(v: &Value, ctor: &Value) -> Result<bool, Value> {
+    let Value::Object(c) = ctor else {
+        return Err(new_error(
+            "TypeError",
+            "Right-hand side of 'instanceof' is not callable",
+        ));
+    };
+    if !c.is_callable() {
+        return Err(new_error(
+            "TypeError",
+            "Right-hand side of 'instanceof' is not callable",
+        ));
+    }
+    let Value::Object(target) = get_property(ctor, "prototype") else {
+        return Ok(false);
+    };
+    let Value::Object(o) = v else {
+        return Ok(false);
+    };
+    let mut cur = o.proto();
+    while let Some(p) = cur {
+        if p.ptr_eq(&target) {
+            return Ok(true);
+        }
+        cur = p.proto();
+    }
+    Ok(false)
+}
+
+fn lookup(start: Option<Obj>, key: &PropKey) -> Option<Value> {
+    let mut cur = start;
+    while let Some(o) = cur {
+        if let Some(v) = o.own(key) {
+            return Some(v);
+        }
+        cur = o.proto();
+    }
+    None
+}
+
+fn get(target: &Value, key: &Value) -> Result<Value, Value> {
+    let key = to_property_key(key);
+    let realm = realm();
+    let start = match target {
+        Value::Undefined | Value::Null => {
+            return Err(new_error(
+                "TypeError",
+                &format!(
+                    "Cannot read properties of {} (reading '{}')",
+                    to_rust_string(target),
+                    to_rust_string(&key.to_value())
+                ),
+            ));
+        }
+        Value::String(s) => {
+            if key.is_str("length") {
+                return Ok(Value::Number(s.len() as f64));
+            }
+            if let Some(i) = key.as_index() {
+                return Ok(s
+                    .get(i)
+                    .map(|c| Value::String(Rc::from([*c])))
+                    .unwrap_or(Value::Undefined));
+            }
+            realm.string_proto
+        }
+        Value::Bool(_) => realm.boolean_proto,
+        Value::Number(_) => realm.number_proto,
+        Value::BigInt(_) => realm.bigint_proto,
+        Value::Symbol(s) => {
+            // An accessor on the prototype in JS
+            if key.is_str("description") {
+                return Ok(s
+                    .description()
+                    .map_or(Value::Undefined, |d| Value::String(d.clone())));
+            }
+            realm.symbol_proto
+        }
+        Value::Object(o) => o.clone(),
+    };
+    Ok(lookup(Some(start), &key).unwrap_or(Value::Undefined))
+}
+
+fn get_property(target: &Value, name: &str) -> Value {
+    get(target, &str_val(name)).unwrap_or(Value::Undefined)
+}
+
+fn set(target: &Value, key: &Value, val: Value) -> Result<(), Value> {
+    match target {
+        Value::Undefined | Value::Null => Err(new_error(
+            "TypeError",
+            &format!(
+                "Cannot set properties of {} (setting '{}')",
+                to_rust_string(target),
+                to_rust_string(key)
+            ),
+        )),
+        Value::Object(o) => {
+            o.define(to_property_key(key), val);
+            Ok(())
+        }
+        _ => Ok(()),
+    }
+}
+
+fn has(target: &Value, key: &Value) -> Result<bool, Value> {
+    let Value::Object(o) = target else {
+        return Err(new_error(
+            "TypeError",
+            "Cannot use 'in' operator to search for a key in a primitive",
+        ));
+    };
+    Ok(lookup(Some(o.clone()), &to_property_key(key)).is_some())
+}
+
+fn callable(f: &Value) -> Option<Func> {
+    match f {
+        Value::Object(o) => match &o.0.borrow().kind {
+            Kind::Function(func) => Some(func.clone()),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Calls `f` with `this` and `args`, returning the completion value.
+pub fn call_value(f: &Value, this: &Value, args: &[Value]) -> Result<Value, Value> {
+    let Some(func) = callable(f) else {
+        return Err(new_error(
+            "TypeError",
+            &format!("{} is not a function", display(f)),
+        ));
+    };
+    match func.call {
+        Callable::Native(native) => native(this, args),
+        Callable::Callback(callback) => {
+            let Callback { fidx, data } = *callback;
+            let cb = CALLBACKS.with(|c| c.borrow().get(fidx as usize).copied());
+            let Some(cb) = cb else {
+                return Err(new_error("ReferenceError", "unregistered callback"));
+            };
+            // The callee takes ownership of the argv handle and hands back an
+            // owned handle for its return value.
+            let argv = to_handle(new_array(args.to_vec()));
+            let ret = take_handle(cb(argv, data));
+            match CALLBACK_EXCEPTION.take() {
+                Some(e) => Err(take_handle(e)),
+                None => Ok(ret),
+            }
+        }
+    }
+}
+
+/// Constructs a new object from the constructor `f` with `args`.
+pub fn construct_value(f: &Value, args: &[Value]) -> Result<Value, Value> {
+    let Some(func) = callable(f) else {
+        return Err(new_error(
+            "TypeError",
+            &format!("{} is not a constructor", display(f)),
+        ));
+    };
+    let proto = match get_property(f, "prototype") {
+        Value::Object(p) => p,
+        _ => realm().object_proto,
+    };
+    let this = Value::Object(Obj::new(Some(proto), Kind::Ordinary));
+    let ret = match func.construct {
+        Some(construct) => construct(&this, args)?,
+        None => call_value(f, &this, args)?,
+    };
+    Ok(match ret {
+        Value::Object(_) => ret,
+        _ => this,
+    })
+}
+
+/// Returns a new (or deduplicated) owned handle for `v`.
+pub fn to_handle(v: Value) -> Handle {
+    with_host(|h| h.add(v))
+}
+
+/// Returns the value behind `h` without touching its refcount.
+pub fn value_of(h: Handle) -> Value {
+    with_host(|host| host.get(h))
+}
+
+/// Reads the value behind `h` and releases one reference to it.
+pub fn take_handle(h: Handle) -> Value {
+    with_host(|host| {
+        let v = host.get(h);
+        host.dec_ref(h);
+        v
+    })
+}
+
+/// Returns the refcount of `h`, or `None` if it's not live. Predefined handles
+/// report `u32::MAX`.
+pub fn ref_count(h: Handle) -> Option<u32> {
+    with_host(|host| {
+        host.slots
+            .get(&h)
+            .map(|s| if s.pinned { u32::MAX } else { s.refs })
+    })
+}
+
+/// Returns the number of live handles, predefined ones and the property
+/// names cached by `get`, `set` and `has` excluded.
+pub fn live_handles() -> usize {
+    let cached = crate::key::cached_handles();
+    with_host(|host| {
+        host.slots
+            .iter()
+            .filter(|(h, s)| {
+                !s.pinned && s.refs as usize > cached.iter().filter(|c| c == h).count()
+            })
+            .count()
+    })
+}
+
+fn function_source(params: &str, body: &str) -> String {
+    format!("function anonymous({params}) {{{body}}}")
+}
+
+/// Lets `new Function(...params, body)` compile the given source on this
+/// thread. The mock can't run JS, so `f` is called with `(this, args)` in
+/// place of the body.
+pub fn define_function_source(
+    params: &[&str],
+    body: &str,
+    f: impl Fn(&Value, &[Value]) -> Result<Value, Value> + 'static,
+) {
+    let source = function_source(&params.join(","), body);
+    SOURCES.with(|s| s.borrow_mut().push((source, native(f))));
+}
+
+/// Returns the handle cached under `key` for this thread's host, creating it
+/// with `init` on first use. Nothing is cached if `init` fails.
+pub(crate) fn cached_handle<E>(
+    key: usize,
+    init: impl FnOnce() -> Result<Handle, E>,
+) -> Result<Handle, E> {
+    if let Some(h) = CACHED.with(|c| c.borrow().get(&key).copied()) {
+        return Ok(h);
+    }
+    let h = init()?;
+    CACHED.with(|c| c.borrow_mut().insert(key, h));
+    Ok(h)
+}
+
+/// Drains the lines logged through `console` on this thread.
+pub fn take_console_output() -> Vec<String> {
+    CONSOLE.with(|c| core::mem::take(&mut *c.borrow_mut()))
+}
+
+fn completion(r: Result<Value, Value>) -> Handle {
+    to_handle(r.unwrap_or_else(|e| e))
+}
+
+unsafe fn completion_try(r: Result<Value, Value>, threw: *mut bool) -> Handle {
+    unsafe { *threw = r.is_err() };
+    completion(r)
+}
+
+/// Reads `argc` argument handles from `args`, which may dangle when empty
+unsafe fn arg_values(args: *const Handle, argc: usize) -> Vec<Value> {
+    if argc == 0 {
+        return Vec::new();
+    }
+    let args = unsafe { core::slice::from_raw_parts(args, argc) };
+    args.iter().map(|&h| value_of(h)).collect()
+}
+
+fn argv_values(argv: Handle) -> Vec<Value> {
+    match value_of(argv) {
+        Value::Object(o) => array_items(&o),
+        _ => Vec::new(),
+    }
+}
+
+unsafe fn str_from_raw(s: *const c_char, len: usize) -> JsStr {
+    if s.is_null() || len == 0 {
+        return Rc::from([]);
+    }
+    let bytes = unsafe { core::slice::from_raw_parts(s as *const u8, len) };
+    js_str(&String::from_utf8_lossy(bytes))
+}
+
+pub unsafe fn emlite_init_handle_table() {
+    let old = HOST.with(|h| h.borrow_mut().replace(Host::new()));
+    drop(old);
+    discard_finalizers();
+    CACHED.with(|c| c.borrow_mut().clear());
+    crate::key::reset();
+}
+
+pub unsafe fn emlite_val_new_array() -> Handle {
+    to_handle(new_array(Vec::new()))
+}
+
+pub unsafe fn emlite_val_new_object() -> Handle {
+    to_handle(new_object())
+}
+
+pub unsafe fn emlite_val_typeof_tag(val: Handle) -> u32 {
+    let t = type_of(&value_of(val));
+    TYPEOF_NAMES.iter().position(|&n| n == t).unwrap_or(0) as u32
+}
+
+pub unsafe fn emlite_val_construct_new(ctor: Handle, argv: Handle) -> Handle {
+    completion(construct_value(&value_of(ctor), &argv_values(argv)))
+}
+
+pub unsafe fn emlite_val_func_call(func: Handle, argv: Handle) -> Handle {
+    completion(call_value(
+        &value_of(func),
+        &Value::Undefined,
+        &argv_values(argv),
+    ))
+}
+
+pub unsafe fn emlite_val_push(arr: Handle, v: Handle) {
+    if let Value::Object(o) = value_of(arr)
+        && let Kind::Array(items) = &mut o.0.borrow_mut().kind
+    {
+        items.push(value_of(v));
+    }
+}
+
+pub unsafe fn emlite_val_make_bool(t: bool) -> Handle {
+    to_handle(Value::Bool(t))
+}
+
+pub unsafe fn emlite_val_make_int(t: c_int) -> Handle {
+    to_handle(Value::Number(t as f64))
+}
+
+pub unsafe fn emlite_val_make_uint(t: c_uint) -> Handle {
+    to_handle(Value::Number(t as f64))
+}
+
+pub unsafe fn emlite_val_make_bigint(t: c_longlong) -> Handle {
+    to_handle(Value::BigInt(t as i128))
+}
+
+pub unsafe fn emlite_val_make_biguint(t: c_ulonglong) -> Handle {
+    to_handle(Value::BigInt(t as i128))
+}
+
+pub unsafe fn emlite_val_make_double(t: c_double) -> Handle {
+    to_handle(Value::Number(t))
+}
+
+pub unsafe fn emlite_val_make_str(s: *const c_char, len: usize) -> Handle {
+    to_handle(Value::String(unsafe { str_from_raw(s, len) }))
+}
+
+pub unsafe fn emlite_val_make_str_utf16(s: *const u16, len: usize) -> Handle {
+    let units: JsStr = if s.is_null() || len == 0 {
+        Rc::from([])
+    } else {
+        Rc::from(unsafe { core::slice::from_raw_parts(s, len) })
+    };
+    to_handle(Value::String(units))
+}
+
+pub unsafe fn emlite_val_get_value_bool(val: Handle) -> bool {
+    truthy(&value_of(val))
+}
+
+pub unsafe fn emlite_val_get_value_int(val: Handle) -> c_int {
+    to_int32(&value_of(val))
+}
+
+pub unsafe fn emlite_val_get_value_uint(val: Handle) -> c_uint {
+    to_int32(&value_of(val)) as c_uint
+}
+
+pub unsafe fn emlite_val_get_value_bigint(val: Handle) -> c_longlong {
+    match value_of(val) {
+        Value::BigInt(b) => b as i64,
+        v => number_of(&v).unwrap_or(f64::NAN) as i64,
+    }
+}
+
+pub unsafe fn emlite_val_get_value_biguint(val: Handle) -> c_ulonglong {
+    match value_of(val) {
+        Value::BigInt(b) => b as u64,
+        v => number_of(&v).unwrap_or(f64::NAN) as u64,
+    }
+}
+
+/// Converts like the host glue's `Number(v)`, so BigInts read as their value
+/// rather than throwing
+pub unsafe fn emlite_val_get_value_double(val: Handle) -> c_double {
+    number_of(&value_of(val)).unwrap_or(f64::NAN)
+}
+
+/// Copies the string as UTF-8 into `buf`, following the host's length protocol.
+pub unsafe fn emlite_val_get_value_string_into(val: Handle, buf: *mut c_char, cap: usize) -> usize {
+    match value_of(val) {
+        Value::String(s) => {
+            let bytes = String::from_utf16_lossy(&s).into_bytes();
+            unsafe { copy_units(&bytes, buf as *mut u8, cap) }
+        }
+        _ => usize::MAX,
+    }
+}
+
+/// Copies the string's UTF-16 code units into `buf`, following the host's length protocol.
+pub unsafe fn emlite_val_get_value_string_utf16_into(
+    val: Handle,
+    buf: *mut u16,
+    cap: usize,
+) -> usize {
+    match value_of(val) {
+        Value::String(s) => unsafe { copy_units(&s, buf, cap) },
+        _ => usize::MAX,
+    }
+}
+
+unsafe fn copy_units<T: Copy>(src: &[T], dst: *mut T, cap: usize) -> usize {
+    if src.len() <= cap {
+        unsafe { core::ptr::copy_nonoverlapping(src.as_ptr(), dst, src.len()) };
+    }
+    src.len()
+}
+
+pub unsafe fn emlite_val_get(val: Handle, idx: Handle) -> Handle {
+    completion(get(&value_of(val), &value_of(idx)))
+}
+
+pub unsafe fn emlite_val_set(val: Handle, idx: Handle, v: Handle) {
+    let _ = set(&value_of(val), &value_of(idx), value_of(v));
+}
+
+pub unsafe fn emlite_val_get_index(val: Handle, idx: u32) -> Handle {
+    completion(get(&value_of(val), &Value::Number(idx as f64)))
+}
+
+pub unsafe fn emlite_val_set_index(val: Handle, idx: u32, v: Handle) {
+    let _ = set(&value_of(val), &Value::Number(idx as f64), value_of(v));
+}
+
+pub unsafe fn emlite_val_has(val: Handle, idx: Handle) -> bool {
+    has(&value_of(val), &value_of(idx)).unwrap_or(false)
+}
+
+pub unsafe fn emlite_val_is_string(val: Handle) -> bool {
+    matches!(value_of(val), Value::String(_))
+}
+
+pub unsafe fn emlite_val_is_number(val: Handle) -> bool {
+    matches!(value_of(val), Value::Number(_))
+}
+
+pub unsafe fn emlite_val_is_bool(val: Handle) -> bool {
+    matches!(value_of(val), Value::Bool(_))
+}
+
+pub unsafe fn emlite_val_not(val: Handle) -> bool {
+    !truthy(&value_of(val))
+}
+
+pub unsafe fn emlite_val_gt(arg1: Handle, arg2: Handle) -> bool {
+    compare(&value_of(arg1), &value_of(arg2)).is_some_and(|o| o.is_gt())
+}
+
+pub unsafe fn emlite_val_gte(arg1: Handle, arg2: Handle) -> bool {
+    compare(&value_of(arg1), &value_of(arg2)).is_some_and(|o| o.is_ge())
+}
+
+pub unsafe fn emlite_val_lt(arg1: Handle, arg2: Handle) -> bool {
+    compare(&value_of(arg1), &value_of(arg2)).is_some_and(|o| o.is_lt())
+}
+
+pub unsafe fn emlite_val_lte(arg1: Handle, arg2: Handle) -> bool {
+    compare(&value_of(arg1), &value_of(arg2)).is_some_and(|o| o.is_le())
+}
+
+pub unsafe fn emlite_val_equals(arg1: Handle, arg2: Handle) -> bool {
+    loosely_equals(&value_of(arg1), &value_of(arg2))
+}
+
+pub unsafe fn emlite_val_strictly_equals(arg1: Handle, arg2: Handle) -> bool {
+    strictly_equals(&value_of(arg1), &value_of(arg2))
+}
+
+pub unsafe fn emlite_val_instanceof(arg1: Handle, arg2: Handle) -> bool {
+    instance_of(&value_of(arg1), &value_of(arg2)).unwrap_or(false)
+}
+
+pub unsafe fn emlite_val_inc_ref(val: Handle) {
+    with_host(|h| h.inc_ref(val));
+}
+
+pub unsafe fn emlite_val_dec_ref(val: Handle) {
+    with_host(|h| h.dec_ref(val));
+}
+
+pub unsafe fn emlite_val_throw(val: Handle) -> ! {
+    panic!("Uncaught {}", display(&value_of(val)));
+}
+
+pub unsafe fn emlite_val_obj_call(
+    obj: Handle,
+    name: *const c_char,
+    len: usize,
+    argv: Handle,
+) -> Handle {
+    let this = value_of(obj);
+    let name = Value::String(unsafe { str_from_raw(name, len) });
+    completion(get(&this, &name).and_then(|f| call_value(&f, &this, &argv_values(argv))))
+}
+
+pub unsafe fn emlite_val_obj_has_own_prop(obj: Handle, prop: *const c_char, len: usize) -> bool {
+    let key = PropKey::Str(unsafe { str_from_raw(prop, len) });
+    match value_of(obj) {
+        Value::Object(o) => o.own(&key).is_some(),
+        _ => false,
+    }
+}
+
+pub unsafe fn emlite_val_obj_call_args(
+    obj: Handle,
+    name: *const c_char,
+    len: usize,
+    args: *const Handle,
+    argc: usize,
+) -> Handle {
+    let this = value_of(obj);
+    let name = Value::String(unsafe { str_from_raw(name, len) });
+    let args = unsafe { arg_values(args, argc) };
+    completion(get(&this, &name).and_then(|f| call_value(&f, &this, &args)))
+}
+
+pub unsafe fn emlite_val_construct_new_args(
+    ctor: Handle,
+    args: *const Handle,
+    argc: usize,
+) -> Handle {
+    completion(construct_value(&value_of(ctor), &unsafe {
+        arg_values(args, argc)
+    }))
+}
+
+pub unsafe fn emlite_val_func_call_args(func: Handle, args: *const Handle, argc: usize) -> Handle {
+    let args = unsafe { arg_values(args, argc) };
+    completion(call_value(&value_of(func), &Value::Undefined, &args))
+}
+
+pub unsafe fn emlite_val_obj_call_args_try(
+    obj: Handle,
+    name: *const c_char,
+    len: usize,
+    args: *const Handle,
+    argc: usize,
+    threw: *mut bool,
+) -> Handle {
+    let this = value_of(obj);
+    let name = Value::String(unsafe { str_from_raw(name, len) });
+    let args = unsafe { arg_values(args, argc) };
+    let r = get(&this, &name).and_then(|f| call_value(&f, &this, &args));
+    unsafe { completion_try(r, threw) }
+}
+
+pub unsafe fn emlite_val_construct_new_args_try(
+    ctor: Handle,
+    args: *const Handle,
+    argc: usize,
+    threw: *mut bool,
+) -> Handle {
+    let r = construct_value(&value_of(ctor), &unsafe { arg_values(args, argc) });
+    unsafe { completion_try(r, threw) }
+}
+
+pub unsafe fn emlite_val_func_call_args_try(
+    func: Handle,
+    args: *const Handle,
+    argc: usize,
+    threw: *mut bool,
+) -> Handle {
+    let args = unsafe { arg_values(args, argc) };
+    let r = call_value(&value_of(func), &Value::Undefined, &args);
+    unsafe { completion_try(r, threw) }
+}
+
+pub unsafe fn emlite_val_make_callback(id: Handle, data: Handle) -> Handle {
+    let realm = realm();
+    let f = Obj::new(
+        Some(realm.function_proto),
+        Kind::Function(Func {
+            call: Callable::Callback(Rc::new(Callback { fidx: id, data })),
+            construct: None,
+        }),
+    );
+    to_handle(Value::Object(f))
+}
+
+/// Creates a typed array of `ty` over `buffer`, with the global constructors'
+/// prototypes.
+fn typed_array_over(ty: ElemType, buffer: Buffer, len: usize) -> Value {
+    let proto_of = |name: &str| match get_property(&Value::Object(realm().global), name) {
+        ctor @ Value::Object(_) => match get_property(&ctor, "prototype") {
+            Value::Object(p) => Some(p),
+            _ => None,
+        },
+        _ => None,
+    };
+    let buffer = Obj::new(proto_of("ArrayBuffer"), Kind::ArrayBuffer(buffer));
+    let view = View {
+        ty,
+        buffer,
+        offset: 0,
+        len,
+    };
+    Value::Object(Obj::new(proto_of(ty.name()), Kind::TypedArray(view)))
+}
+
+fn elem_type(kind: c_uint) -> ElemType {
+    ElemType::ALL[kind as usize]
+}
+
+pub unsafe fn emlite_val_make_typed_array(kind: c_uint, data: *const c_void, len: usize) -> Handle {
+    let ty = elem_type(kind);
+    let bytes = if len == 0 {
+        Vec::new()
+    } else {
+        unsafe { core::slice::from_raw_parts(data as *const u8, len * ty.size()) }.to_vec()
+    };
+    to_handle(typed_array_over(ty, bytes.into(), len))
+}
+
+pub unsafe fn emlite_val_make_typed_array_view(
+    kind: c_uint,
+    data: *mut c_void,
+    len: usize,
+) -> Handle {
+    let ty = elem_type(kind);
+    let buffer = Buffer::External(data as *mut u8, len * ty.size());
+    to_handle(typed_array_over(ty, buffer, len))
+}
+
+pub unsafe fn emlite_val_typed_array_len(val: Handle, kind: c_uint) -> usize {
+    let Value::Object(o) = value_of(val) else {
+        return 0;
+    };
+    let data = o.0.borrow();
+    match &data.kind {
+        Kind::TypedArray(view) => view.len,
+        Kind::ArrayBuffer(buffer) => buffer.len() / elem_type(kind).size(),
+        _ => {
+            drop(data);
+            array_items(&o).len()
+        }
+    }
+}
+
+pub unsafe fn emlite_val_typed_array_copy(
+    val: Handle,
+    kind: c_uint,
+    data: *mut c_void,
+    len: usize,
+) -> usize {
+    let ty = elem_type(kind);
+    let size = ty.size();
+    if len == 0 {
+        return 0;
+    }
+    let dst = unsafe { core::slice::from_raw_parts_mut(data as *mut u8, len * size) };
+    let Value::Object(o) = value_of(val) else {
+        return 0;
+    };
+    let buffer = matches!(o.0.borrow().kind, Kind::ArrayBuffer(_));
+    if buffer {
+        // Raw bytes, reinterpreted as `ty` like `new <Type>Array(buffer)` would
+        let mut data = o.0.borrow_mut();
+        let Kind::ArrayBuffer(buffer) = &mut data.kind else {
+            unreachable!()
+        };
+        let n = (buffer.len() / size).min(len);
+        dst[..n * size].copy_from_slice(&buffer.bytes()[..n * size]);
+        return n;
+    }
+    let view = match &o.0.borrow().kind {
+        Kind::TypedArray(view) => Some(view.clone()),
+        _ => None,
+    };
+    let items = match view {
+        Some(view) => (0..view.len.min(len)).filter_map(|i| view.get(i)).collect(),
+        None => array_items(&o),
+    };
+    let n = items.len().min(len);
+    for (i, v) in items.iter().take(n).enumerate() {
+        ty.encode(v, &mut dst[i * size..(i + 1) * size]);
+    }
+    n
+}
+
+pub unsafe fn emlite_print_object_map() {
+    let mut entries = with_host(|h| {
+        h.slots
+            .iter()
+            .map(|(h, s)| (*h, s.refs, s.value.clone()))
+            .collect::<Vec<_>>()
+    });
+    entries.sort_by_key(|e| e.0);
+    for (h, refs, v) in entries {
+        println!("{h}: {} (refs: {refs})", display(&v));
+    }
+}
+
+pub unsafe fn emlite_reset_object_map() {
+    let dead = HOST.with(|h| h.borrow_mut().as_mut().map(Host::reset));
+    drop(dead);
+    discard_finalizers();
+}
+
+pub unsafe fn emlite_target() -> i32 {
+    EMLITE_TARGET
+}
+
+// Unified interface functions to abstract away wasip2 vs other target differences
+
+pub unsafe fn emlite_val_make_str_unified(s: &str) -> Handle {
+    to_handle(str_val(s))
+}
+
+pub unsafe fn emlite_val_make_str_utf16_unified(s: &[u16]) -> Handle {
+    to_handle(Value::String(Rc::from(s)))
+}
+
+pub unsafe fn emlite_val_obj_call_unified(obj: Handle, method: &str, args: &[Handle]) -> Handle {
+    unsafe {
+        emlite_val_obj_call_args(
+            obj,
+            method.as_ptr() as _,
+            method.len(),
+            args.as_ptr(),
+            args.len(),
+        )
+    }
+}
+
+pub unsafe fn emlite_val_construct_new_unified(ctor: Handle, args: &[Handle]) -> Handle {
+    unsafe { emlite_val_construct_new_args(ctor, args.as_ptr(), args.len()) }
+}
+
+pub unsafe fn emlite_val_func_call_unified(func: Handle, args: &[Handle]) -> Handle {
+    unsafe { emlite_val_func_call_args(func, args.as_ptr(), args.len()) }
+}
+
+pub unsafe fn emlite_val_obj_has_own_prop_unified(obj: Handle, prop: &str) -> bool {
+    unsafe { emlite_val_obj_has_own_prop(obj, prop.as_ptr() as _, prop.len()) }
+}
+
+pub unsafe fn emlite_val_obj_call_try_unified(
+    obj: Handle,
+    method: &str,
+    args: &[Handle],
+) -> Result<Handle, Handle> {
+    let mut threw = false;
+    let h = unsafe {
+        emlite_val_obj_call_args_try(
+            obj,
+            method.as_ptr() as _,
+            method.len(),
+            args.as_ptr(),
+            args.len(),
+            &mut threw,
+        )
+    };
+    if threw { Err(h) } else { Ok(h) }
+}
+
+pub unsafe fn emlite_val_construct_new_try_unified(
+    ctor: Handle,
+    args: &[Handle],
+) -> Result<Handle, Handle> {
+    let mut threw = false;
+    let h =
+        unsafe { emlite_val_construct_new_args_try(ctor, args.as_ptr(), args.len(), &mut threw) };
+    if threw { Err(h) } else { Ok(h) }
+}
+
+pub unsafe fn emlite_val_func_call_try_unified(
+    func: Handle,
+    args: &[Handle],
+) -> Result<Handle, Handle> {
+    let mut threw = false;
+    let h = unsafe { emlite_val_func_call_args_try(func, args.as_ptr(), args.len(), &mut threw) };
+    if threw { Err(h) } else { Ok(h) }
+}
+
+/// Makes the running callback throw `val`, taking ownership of it, once it
+/// returns.
+///
+/// Unwinding can't cross a callback's C ABI, so where a JS host throws right
+/// away, the mock returns and raises the exception when the callback does.
+pub unsafe fn emlite_val_throw_from_callback_unified(val: Handle) -> Handle {
+    if let Some(prev) = CALLBACK_EXCEPTION.replace(Some(val)) {
+        with_host(|h| h.dec_ref(prev));
+    }
+    EmlitePredefHandles::Undefined as Handle
+}
+
+pub unsafe fn emlite_val_typeof_unified(h: Handle) -> &'static str {
+    crate::common::typeof_name(unsafe { emlite_val_typeof_tag(h) })
+}
+
+pub unsafe fn emlite_val_get_value_string_into_unified(h: Handle, out: &mut String) -> bool {
+    let mut bytes = core::mem::take(out).into_bytes();
+    let ok = unsafe {
+        read_into(&mut bytes, |buf, cap| {
+            emlite_val_get_value_string_into(h, buf as _, cap)
+        })
+    };
+    *out = String::from_utf8(bytes).expect("mock host writes UTF-8");
+    ok
+}
+
+pub unsafe fn emlite_val_get_value_string_utf16_into_unified(
+    h: Handle,
+    out: &mut Vec<u16>,
+) -> bool {
+    unsafe {
+        read_into(out, |buf, cap| {
+            emlite_val_get_value_string_utf16_into(h, buf, cap)
+        })
+    }
+}
+
+pub unsafe fn emlite_val_make_typed_array_unified(kind: u32, data: &[u8], len: usize) -> Handle {
+    unsafe { emlite_val_make_typed_array(kind, data.as_ptr() as _, len) }
+}
+
+pub unsafe fn emlite_val_typed_array_len_unified(h: Handle, kind: u32) -> usize {
+    unsafe { emlite_val_typed_array_len(h, kind) }
+}
+
+pub unsafe fn emlite_val_typed_array_copy_unified(
+    h: Handle,
+    kind: u32,
+    dst: &mut [u8],
+    len: usize,
+) -> usize {
+    unsafe { emlite_val_typed_array_copy(h, kind, dst.as_mut_ptr() as _, len) }
+}
+
+pub unsafe fn emlite_val_not_unified(h: Handle) -> bool {
+    unsafe { emlite_val_not(h) }
+}
+
+pub unsafe fn emlite_register_callback_unified(f: CallbackFn) -> Handle {
+    CALLBACKS.with(|c| {
+        let mut c = c.borrow_mut();
+        match c.iter().position(|g| core::ptr::fn_addr_eq(*g, f)) {
+            Some(i) => i as Handle,
+            None => {
+                c.push(f);
+                (c.len() - 1) as Handle
+            }
+        }
+    })
+}
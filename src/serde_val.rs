@@ -0,0 +1,1076 @@
+//! Conversions between serde data and JS values.
+//!
+//! [`to_val`] and [`from_val`] cover the common case. The mapping is:
+//!
+//! | Rust (serde)               | JS                                         |
+//! |----------------------------|--------------------------------------------|
+//! | `bool`, strings, `char`    | boolean, string                            |
+//! | up to 32-bit ints, floats  | number                                     |
+//! | `i64`, `u64`               | number, or BigInt with [`Serializer::large_numbers_as_bigints`] |
+//! | `None`, `()`, unit structs | undefined                                  |
+//! | structs                    | plain objects                              |
+//! | sequences and tuples       | arrays                                     |
+//! | maps                       | plain objects, or `Map` with [`Serializer::maps_as_objects`] off |
+//! | bytes                      | `Uint8Array`                               |
+//! | enums                      | externally tagged: `"Unit"` or `{ Variant: content }` |
+//!
+//! Deserialization is lenient where JS is: numbers and BigInts both feed any
+//! numeric type that fits, null and undefined both read as `None`, objects and
+//! `Map`s both read as maps or structs, and `Uint8Array`s, `ArrayBuffer`s and
+//! arrays all read as bytes. Errors carry the property path at which they
+//! occurred, e.g. `invalid type: string "x", expected u32 at items[2].count`.
+
+use crate::Val;
+use crate::sys::*;
+use ::serde::de::{self, DeserializeOwned, IntoDeserializer, Visitor};
+use ::serde::ser::{self, Serialize};
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt;
+
+/// 2^53 - 1, the largest integer a JS number holds exactly
+const MAX_SAFE_INTEGER: u64 = (1 << 53) - 1;
+
+/// Serializes `value` into a new JS value with the default [`Serializer`]
+pub fn to_val<T: Serialize + ?Sized>(value: &T) -> Result<Val, Error> {
+    value.serialize(&Serializer::new())
+}
+
+/// Deserializes a `T` out of the JS value `val`
+pub fn from_val<T: DeserializeOwned>(val: &Val) -> Result<T, Error> {
+    T::deserialize(Deserializer::from(val))
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Segment {
+    Key(String),
+    Index(usize),
+}
+
+/// An error raised while converting between serde data and JS values.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Error {
+    msg: String,
+    // Innermost segment first, since paths are built while unwinding
+    path: Vec<Segment>,
+}
+
+impl Error {
+    fn new(msg: impl ToString) -> Error {
+        Error {
+            msg: msg.to_string(),
+            path: Vec::new(),
+        }
+    }
+
+    fn at_key(mut self, key: &str) -> Error {
+        self.path.push(Segment::Key(key.into()));
+        self
+    }
+
+    fn at_index(mut self, i: usize) -> Error {
+        self.path.push(Segment::Index(i));
+        self
+    }
+
+    /// Returns the message without the path
+    pub fn message(&self) -> &str {
+        &self.msg
+    }
+
+    /// Returns the property path of the failing value, e.g. `items[2].count`,
+    /// or an empty string for the root value
+    pub fn path(&self) -> String {
+        let mut out = String::new();
+        for seg in self.path.iter().rev() {
+            match seg {
+                Segment::Key(k) => {
+                    if !out.is_empty() {
+                        out.push('.');
+                    }
+                    out.push_str(k);
+                }
+                Segment::Index(i) => out.push_str(&format!("[{i}]")),
+            }
+        }
+        out
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.path.is_empty() {
+            f.write_str(&self.msg)
+        } else {
+            write!(f, "{} at {}", self.msg, self.path())
+        }
+    }
+}
+
+impl core::error::Error for Error {}
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error::new(msg)
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error::new(msg)
+    }
+}
+
+impl From<Error> for Val {
+    /// Converts to a JS `TypeError` carrying the message and path
+    fn from(e: Error) -> Self {
+        Val::global("TypeError").new(&[e.to_string().into()])
+    }
+}
+
+impl crate::VecElement for Error {}
+
+fn push(arr: &Val, v: Val) {
+    unsafe { emlite_val_push(arr.as_handle(), v.as_handle()) };
+}
+
+fn bytes_to_val(bytes: &[u8]) -> Val {
+    Val::from_typed_slice(bytes)
+}
+
+/// Serializes Rust values into JS values.
+///
+/// By default maps become plain objects, and 64-bit integers outside JS's
+/// safe integer range are an error rather than silently rounded.
+#[derive(Clone, Debug)]
+pub struct Serializer {
+    maps_as_objects: bool,
+    large_numbers_as_bigints: bool,
+}
+
+impl Default for Serializer {
+    fn default() -> Self {
+        Serializer::new()
+    }
+}
+
+impl Serializer {
+    /// Creates a serializer with the default settings
+    pub const fn new() -> Serializer {
+        Serializer {
+            maps_as_objects: true,
+            large_numbers_as_bigints: false,
+        }
+    }
+
+    /// Serialize maps as plain objects (the default) or as JS `Map`s. Object
+    /// keys must serialize to strings or numbers; `Map` keys can be anything.
+    pub const fn maps_as_objects(mut self, yes: bool) -> Serializer {
+        self.maps_as_objects = yes;
+        self
+    }
+
+    /// Serialize `i64`/`u64` (and `isize`/`usize`) as BigInts instead of numbers
+    pub const fn large_numbers_as_bigints(mut self, yes: bool) -> Serializer {
+        self.large_numbers_as_bigints = yes;
+        self
+    }
+}
+
+impl<'a> ser::Serializer for &'a Serializer {
+    type Ok = Val;
+    type Error = Error;
+    type SerializeSeq = SerializeArray<'a>;
+    type SerializeTuple = SerializeArray<'a>;
+    type SerializeTupleStruct = SerializeArray<'a>;
+    type SerializeTupleVariant = SerializeVariant<SerializeArray<'a>>;
+    type SerializeMap = SerializeMap<'a>;
+    type SerializeStruct = SerializeObject<'a>;
+    type SerializeStructVariant = SerializeVariant<SerializeObject<'a>>;
+
+    fn serialize_bool(self, v: bool) -> Result<Val, Error> {
+        Ok(v.into())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Val, Error> {
+        Ok(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Val, Error> {
+        Ok(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Val, Error> {
+        Ok(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Val, Error> {
+        if self.large_numbers_as_bigints {
+            Ok(v.into())
+        } else if v.unsigned_abs() <= MAX_SAFE_INTEGER {
+            Ok((v as f64).into())
+        } else {
+            Err(Error::new(format!(
+                "{v} can't be represented exactly as a JS number"
+            )))
+        }
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Val, Error> {
+        Ok(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Val, Error> {
+        Ok(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Val, Error> {
+        Ok(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Val, Error> {
+        if self.large_numbers_as_bigints {
+            Ok(v.into())
+        } else if v <= MAX_SAFE_INTEGER {
+            Ok((v as f64).into())
+        } else {
+            Err(Error::new(format!(
+                "{v} can't be represented exactly as a JS number"
+            )))
+        }
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Val, Error> {
+        Ok(v.into())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Val, Error> {
+        Ok(v.into())
+    }
+
+    fn serialize_char(self, v: char) -> Result<Val, Error> {
+        Ok((&*v.encode_utf8(&mut [0; 4])).into())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Val, Error> {
+        Ok(v.into())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Val, Error> {
+        Ok(bytes_to_val(v))
+    }
+
+    fn serialize_none(self) -> Result<Val, Error> {
+        Ok(Val::undefined())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Val, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Val, Error> {
+        Ok(Val::undefined())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Val, Error> {
+        Ok(Val::undefined())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<Val, Error> {
+        Ok(variant.into())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Val, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Val, Error> {
+        let obj = Val::object();
+        obj.set(
+            variant,
+            value.serialize(self).map_err(|e| e.at_key(variant))?,
+        );
+        Ok(obj)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Ok(SerializeArray {
+            ser: self,
+            arr: Val::array(),
+            len: 0,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Ok(SerializeVariant {
+            variant,
+            inner: self.serialize_seq(Some(len))?,
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        let target = if self.maps_as_objects {
+            Val::object()
+        } else {
+            Val::global("Map").new(&[])
+        };
+        Ok(SerializeMap {
+            ser: self,
+            target,
+            key: None,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Error> {
+        Ok(SerializeObject {
+            ser: self,
+            obj: Val::object(),
+        })
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Ok(SerializeVariant {
+            variant,
+            inner: self.serialize_struct(name, len)?,
+        })
+    }
+}
+
+/// Builds a JS array for sequences and tuples.
+pub struct SerializeArray<'a> {
+    ser: &'a Serializer,
+    arr: Val,
+    len: usize,
+}
+
+impl SerializeArray<'_> {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let v = value
+            .serialize(self.ser)
+            .map_err(|e| e.at_index(self.len))?;
+        push(&self.arr, v);
+        self.len += 1;
+        Ok(())
+    }
+}
+
+impl ser::SerializeSeq for SerializeArray<'_> {
+    type Ok = Val;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Val, Error> {
+        Ok(self.arr)
+    }
+}
+
+impl ser::SerializeTuple for SerializeArray<'_> {
+    type Ok = Val;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Val, Error> {
+        Ok(self.arr)
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeArray<'_> {
+    type Ok = Val;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Val, Error> {
+        Ok(self.arr)
+    }
+}
+
+/// Builds a plain object for structs.
+pub struct SerializeObject<'a> {
+    ser: &'a Serializer,
+    obj: Val,
+}
+
+impl ser::SerializeStruct for SerializeObject<'_> {
+    type Ok = Val;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        let v = value.serialize(self.ser).map_err(|e| e.at_key(key))?;
+        self.obj.set(key, v);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Val, Error> {
+        Ok(self.obj)
+    }
+}
+
+/// Builds a plain object or a `Map` for maps.
+pub struct SerializeMap<'a> {
+    ser: &'a Serializer,
+    target: Val,
+    key: Option<Val>,
+}
+
+impl ser::SerializeMap for SerializeMap<'_> {
+    type Ok = Val;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        let key = key.serialize(self.ser)?;
+        if self.ser.maps_as_objects
+            && !(key.is_string() || key.is_number() || key.typeof_str() == "bigint")
+        {
+            return Err(Error::new(format!(
+                "map keys must be strings or numbers to become object properties, got {}",
+                key.typeof_str()
+            )));
+        }
+        self.key = Some(key);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| Error::new("serialize_value called before serialize_key"))?;
+        let v = value
+            .serialize(self.ser)
+            .map_err(|e| e.at_key(&key_name(&key)))?;
+        if self.ser.maps_as_objects {
+            self.target.set(key, v);
+        } else {
+            self.target.call("set", &[key, v]);
+        }
+        Ok(())
+    }
+
+    fn end(self) -> Result<Val, Error> {
+        Ok(self.target)
+    }
+}
+
+/// Wraps the content of a tuple or struct variant as `{ Variant: content }`.
+pub struct SerializeVariant<S> {
+    variant: &'static str,
+    inner: S,
+}
+
+impl<S> SerializeVariant<S> {
+    fn wrap(variant: &'static str, content: Val) -> Val {
+        let obj = Val::object();
+        obj.set(variant, content);
+        obj
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeVariant<SerializeArray<'_>> {
+    type Ok = Val;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let variant = self.variant;
+        self.inner.push(value).map_err(|e| e.at_key(variant))
+    }
+
+    fn end(self) -> Result<Val, Error> {
+        Ok(Self::wrap(self.variant, self.inner.arr))
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeVariant<SerializeObject<'_>> {
+    type Ok = Val;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        let variant = self.variant;
+        ser::SerializeStruct::serialize_field(&mut self.inner, key, value)
+            .map_err(|e| e.at_key(variant))
+    }
+
+    fn end(self) -> Result<Val, Error> {
+        Ok(Self::wrap(self.variant, self.inner.obj))
+    }
+}
+
+/// What a JS value looks like to serde
+enum Shape {
+    Nullish,
+    Bool(bool),
+    Number(f64),
+    BigInt,
+    String,
+    Array,
+    Bytes,
+    Map,
+    Object,
+    Other,
+}
+
+fn shape(v: &Val) -> Shape {
+    if v.is_undefined() || v.is_null() {
+        return Shape::Nullish;
+    }
+    if v.is_bool() {
+        return Shape::Bool(v.as_::<bool>());
+    }
+    if v.is_number() {
+        return Shape::Number(v.as_::<f64>());
+    }
+    if v.is_string() {
+        return Shape::String;
+    }
+    match v.typeof_str() {
+        "bigint" => Shape::BigInt,
+        "object" => {
+            if Val::global("Array")
+                .call("isArray", core::slice::from_ref(v))
+                .as_::<bool>()
+            {
+                Shape::Array
+            } else if v.instanceof(Val::global("Uint8Array"))
+                || v.instanceof(Val::global("ArrayBuffer"))
+            {
+                Shape::Bytes
+            } else if Val::global("ArrayBuffer")
+                .call("isView", core::slice::from_ref(v))
+                .as_::<bool>()
+            {
+                Shape::Array
+            } else if v.instanceof(Val::global("Map")) {
+                Shape::Map
+            } else {
+                Shape::Object
+            }
+        }
+        _ => Shape::Other,
+    }
+}
+
+fn val_to_bytes(v: &Val) -> Vec<u8> {
+    v.to_typed_vec::<u8>()
+}
+
+fn string_of(v: &Val) -> String {
+    v.as_string().unwrap_or_default()
+}
+
+/// Names a map key in error paths
+fn key_name(key: &Val) -> String {
+    if key.is_string() {
+        string_of(key)
+    } else {
+        string_of(&Val::global("String").invoke(core::slice::from_ref(key)))
+    }
+}
+
+/// Deserializes Rust values out of a JS value.
+pub struct Deserializer {
+    val: Val,
+}
+
+impl Deserializer {
+    /// Creates a deserializer reading `val`
+    pub fn new(val: Val) -> Deserializer {
+        Deserializer { val }
+    }
+}
+
+impl From<Val> for Deserializer {
+    fn from(val: Val) -> Self {
+        Deserializer::new(val)
+    }
+}
+
+impl From<&Val> for Deserializer {
+    fn from(val: &Val) -> Self {
+        Deserializer::new(val.clone())
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match shape(&self.val) {
+            Shape::Nullish => visitor.visit_unit(),
+            Shape::Bool(b) => visitor.visit_bool(b),
+            Shape::Number(n) => {
+                // Integral numbers go through the integer visitors so that
+                // integer fields accept them
+                let safe = MAX_SAFE_INTEGER as f64;
+                if (-safe..=safe).contains(&n) && n == n as i64 as f64 {
+                    if n < 0.0 {
+                        visitor.visit_i64(n as i64)
+                    } else {
+                        visitor.visit_u64(n as u64)
+                    }
+                } else {
+                    visitor.visit_f64(n)
+                }
+            }
+            Shape::BigInt => {
+                if self.val < Val::from(0) {
+                    if self.val < Val::from(i64::MIN) {
+                        return Err(Error::new("BigInt is out of the 64-bit range"));
+                    }
+                    visitor.visit_i64(self.val.as_::<i64>())
+                } else {
+                    if self.val > Val::from(u64::MAX) {
+                        return Err(Error::new("BigInt is out of the 64-bit range"));
+                    }
+                    visitor.visit_u64(self.val.as_::<u64>())
+                }
+            }
+            Shape::String => visitor.visit_string(string_of(&self.val)),
+            Shape::Array => visitor.visit_seq(SeqAccess::new(self.val)),
+            Shape::Bytes => visitor.visit_byte_buf(val_to_bytes(&self.val)),
+            Shape::Map => visitor.visit_map(MapAccess::new(&self.val)),
+            Shape::Object => visitor.visit_map(ObjectAccess::new(self.val)),
+            Shape::Other => Err(Error::new(format!(
+                "can't deserialize a JS {}",
+                self.val.typeof_str()
+            ))),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        if self.val.is_undefined() || self.val.is_null() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match shape(&self.val) {
+            Shape::Bytes => visitor.visit_byte_buf(val_to_bytes(&self.val)),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match shape(&self.val) {
+            // `Vec<u8>` and friends are sequences to serde
+            Shape::Bytes => {
+                let bytes = val_to_bytes(&self.val);
+                visitor.visit_seq(de::value::SeqDeserializer::new(bytes.into_iter()))
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match shape(&self.val) {
+            Shape::String => visitor.visit_enum(EnumAccess {
+                variant: string_of(&self.val),
+                content: None,
+            }),
+            Shape::Object => {
+                let keys = Val::global("Object")
+                    .call("keys", core::slice::from_ref(&self.val))
+                    .to_vec::<Option<String>>();
+                match keys.as_slice() {
+                    [Some(variant)] => {
+                        let content = self.val.get(variant.as_str());
+                        visitor.visit_enum(EnumAccess {
+                            variant: variant.clone(),
+                            content: Some(content),
+                        })
+                    }
+                    _ => Err(Error::new(
+                        "expected an enum as a string or an object with a single key",
+                    )),
+                }
+            }
+            _ => Err(Error::new(format!(
+                "expected an enum as a string or an object with a single key, got {}",
+                self.val.typeof_str()
+            ))),
+        }
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    ::serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        unit unit_struct map struct identifier
+    }
+}
+
+/// Reads the elements of an array or typed array.
+struct SeqAccess {
+    arr: Val,
+    len: usize,
+    next: usize,
+}
+
+impl SeqAccess {
+    fn new(arr: Val) -> SeqAccess {
+        let len = arr.get("length").as_::<usize>();
+        SeqAccess { arr, len, next: 0 }
+    }
+}
+
+impl<'de> de::SeqAccess<'de> for SeqAccess {
+    type Error = Error;
+
+    fn next_element_seed<T: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        if self.next >= self.len {
+            return Ok(None);
+        }
+        let i = self.next;
+        self.next += 1;
+        let item = self.arr.get(i as u32);
+        seed.deserialize(Deserializer::new(item))
+            .map(Some)
+            .map_err(|e| e.at_index(i))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.len - self.next)
+    }
+}
+
+/// Reads the own enumerable string keys of a plain object.
+struct ObjectAccess {
+    obj: Val,
+    keys: alloc::vec::IntoIter<String>,
+    key: Option<String>,
+}
+
+impl ObjectAccess {
+    fn new(obj: Val) -> ObjectAccess {
+        let keys = Val::global("Object")
+            .call("keys", core::slice::from_ref(&obj))
+            .to_vec::<Option<String>>()
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+        ObjectAccess {
+            obj,
+            keys: keys.into_iter(),
+            key: None,
+        }
+    }
+}
+
+impl<'de> de::MapAccess<'de> for ObjectAccess {
+    type Error = Error;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        let Some(key) = self.keys.next() else {
+            return Ok(None);
+        };
+        let k = seed
+            .deserialize(KeyDeserializer(key.clone()))
+            .map_err(|e| e.at_key(&key))?;
+        self.key = Some(key);
+        Ok(Some(k))
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| Error::new("next_value called before next_key"))?;
+        let v = self.obj.get(key.as_str());
+        seed.deserialize(Deserializer::new(v))
+            .map_err(|e| e.at_key(&key))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.keys.len())
+    }
+}
+
+/// Reads the entries of a JS `Map`, in insertion order.
+struct MapAccess {
+    entries: SeqAccess,
+    value: Option<(Val, String)>,
+}
+
+impl MapAccess {
+    fn new(map: &Val) -> MapAccess {
+        let entries = Val::global("Array").call("from", core::slice::from_ref(map));
+        MapAccess {
+            entries: SeqAccess::new(entries),
+            value: None,
+        }
+    }
+}
+
+impl<'de> de::MapAccess<'de> for MapAccess {
+    type Error = Error;
+
+    fn next_key_seed<K: de::DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        let entries = &mut self.entries;
+        if entries.next >= entries.len {
+            return Ok(None);
+        }
+        let entry = entries.arr.get(entries.next as u32);
+        entries.next += 1;
+        let key = entry.get(0);
+        let name = key_name(&key);
+        let k = seed
+            .deserialize(Deserializer::new(key))
+            .map_err(|e| e.at_key(&name))?;
+        self.value = Some((entry.get(1), name));
+        Ok(Some(k))
+    }
+
+    fn next_value_seed<V: de::DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let (v, name) = self
+            .value
+            .take()
+            .ok_or_else(|| Error::new("next_value called before next_key"))?;
+        seed.deserialize(Deserializer::new(v))
+            .map_err(|e| e.at_key(&name))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        de::SeqAccess::size_hint(&self.entries)
+    }
+}
+
+/// Deserializes object property names, which are always strings in JS but
+/// may stand for numeric map keys on the Rust side.
+struct KeyDeserializer(String);
+
+macro_rules! deserialize_parsed_key {
+    ($($method:ident => $visit:ident),*) => {$(
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+            match self.0.parse() {
+                Ok(n) => visitor.$visit(n),
+                Err(_) => self.deserialize_any(visitor),
+            }
+        }
+    )*};
+}
+
+impl<'de> de::Deserializer<'de> for KeyDeserializer {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_string(self.0)
+    }
+
+    deserialize_parsed_key! {
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_bool => visit_bool
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_enum(self.0.into_deserializer())
+    }
+
+    ::serde::forward_to_deserialize_any! {
+        i128 u128 char str string bytes byte_buf option unit unit_struct seq tuple
+        tuple_struct map struct identifier ignored_any
+    }
+}
+
+/// Reads `"Variant"` or `{ Variant: content }`.
+struct EnumAccess {
+    variant: String,
+    content: Option<Val>,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumAccess {
+    type Error = Error;
+    type Variant = VariantAccess;
+
+    fn variant_seed<V: de::DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, VariantAccess), Error> {
+        let variant = seed.deserialize(KeyDeserializer(self.variant.clone()))?;
+        Ok((
+            variant,
+            VariantAccess {
+                variant: self.variant,
+                content: self.content,
+            },
+        ))
+    }
+}
+
+struct VariantAccess {
+    variant: String,
+    content: Option<Val>,
+}
+
+impl VariantAccess {
+    fn content(self, expected: &str) -> Result<(Deserializer, String), Error> {
+        match self.content {
+            Some(v) => Ok((Deserializer::new(v), self.variant)),
+            None => Err(Error::new(format!(
+                "expected {expected} for variant {}, got a string",
+                self.variant
+            ))),
+        }
+    }
+}
+
+impl<'de> de::VariantAccess<'de> for VariantAccess {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        match &self.content {
+            Some(v) if !(v.is_undefined() || v.is_null()) => Err(Error::new(format!(
+                "expected no content for unit variant {}",
+                self.variant
+            ))
+            .at_key(&self.variant)),
+            _ => Ok(()),
+        }
+    }
+
+    fn newtype_variant_seed<T: de::DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        let (de, variant) = self.content("content")?;
+        seed.deserialize(de).map_err(|e| e.at_key(&variant))
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        let (de, variant) = self.content("an array")?;
+        de::Deserializer::deserialize_seq(de, visitor).map_err(|e| e.at_key(&variant))
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let (de, variant) = self.content("an object")?;
+        de::Deserializer::deserialize_map(de, visitor).map_err(|e| e.at_key(&variant))
+    }
+}
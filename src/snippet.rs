@@ -0,0 +1,63 @@
+use crate::cache::CachedHandle;
+use crate::{FromVal, JsException, Val};
+use alloc::vec::Vec;
+use core::mem::ManuallyDrop;
+
+/// A JS function compiled from source on first use, see [`js!`](crate::js).
+pub struct JsSnippet {
+    params: &'static [&'static str],
+    body: &'static str,
+    func: CachedHandle,
+}
+
+impl JsSnippet {
+    #[doc(hidden)]
+    pub const fn new(params: &'static [&'static str], body: &'static str) -> Self {
+        JsSnippet {
+            params,
+            body,
+            func: CachedHandle::new(),
+        }
+    }
+
+    /// Compiles the function, keeping it only once that succeeds
+    fn try_compiled(&'static self) -> Result<ManuallyDrop<Val>, JsException> {
+        self.func.get_or_try_init(|| {
+            let mut args: Vec<Val> = self.params.iter().map(|&p| p.into()).collect();
+            args.push(self.body.into());
+            Val::global("Function").try_new(&args)
+        })
+    }
+
+    /// Compiles the function, rethrowing a compile error to JS
+    fn compiled(&'static self) -> ManuallyDrop<Val> {
+        self.try_compiled()
+            .unwrap_or_else(|e| Val::throw(e.into_value()))
+    }
+
+    /// Returns the compiled function
+    pub fn function(&'static self) -> Val {
+        Val::clone(&self.compiled())
+    }
+
+    /// Calls the function with `args` as its parameters, in order, and
+    /// converts the returned value
+    pub fn call<R: FromVal>(&'static self, args: &[Val]) -> R {
+        self.compiled().invoke(args).as_::<R>()
+    }
+
+    /// Calls the function like [`JsSnippet::call`], reporting a thrown
+    /// exception, or a syntax error in the source, as `Err`
+    pub fn try_call<R: FromVal>(&'static self, args: &[Val]) -> Result<R, JsException> {
+        self.try_compiled()?.try_invoke(args).map(|v| v.as_::<R>())
+    }
+}
+
+impl core::fmt::Debug for JsSnippet {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("JsSnippet")
+            .field("params", &self.params)
+            .field("body", &self.body)
+            .finish()
+    }
+}
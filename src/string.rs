@@ -0,0 +1,289 @@
+//! JS strings kept on the JS side.
+
+use crate::common::Handle;
+use crate::{AsHandle, ConversionError, IntoVal, JsCast, TryFromVal, Val};
+use alloc::string::{FromUtf16Error, String};
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use core::fmt;
+use core::ops::Deref;
+
+/// A JS string primitive.
+///
+/// Methods work on the JS value, indexing by UTF-16 code unit like JS does,
+/// and nothing is copied into Rust until asked for. Unlike `String`, a
+/// `JsString` can hold unpaired surrogates; [`to_wtf8`](Self::to_wtf8) and
+/// [`from_wtf8`](Self::from_wtf8) carry them through losslessly:
+///
+/// ```ignore
+/// let s = JsString::from("hello world");
+/// assert_eq!(s.index_of("world"), Some(6));
+/// assert!(s.slice(0, 5).eq_str("hello"));
+/// ```
+#[derive(Clone, Debug)]
+#[repr(transparent)]
+pub struct JsString {
+    val: Val,
+}
+
+impl JsString {
+    /// Returns the string
+    pub fn as_val(&self) -> &Val {
+        &self.val
+    }
+
+    /// Returns the length in UTF-16 code units, JS's `length`
+    pub fn len_utf16(&self) -> usize {
+        self.val.get("length").as_()
+    }
+
+    /// Returns true if the string is empty
+    pub fn is_empty(&self) -> bool {
+        self.len_utf16() == 0
+    }
+
+    /// Returns the UTF-16 code unit at `index`, like `charCodeAt`, or `None`
+    /// past the end
+    pub fn char_at(&self, index: usize) -> Option<u16> {
+        // As Numbers: usize converts to a BigInt, which string methods reject
+        let c = self
+            .val
+            .call("charCodeAt", &[(index as f64).into()])
+            .as_::<f64>();
+        (!c.is_nan()).then_some(c as u16)
+    }
+
+    /// Returns the code units in `start..end`, like `String.prototype.slice`.
+    /// Both ends are clamped to the length.
+    pub fn slice(&self, start: usize, end: usize) -> JsString {
+        let (start, end) = (start as f64, end as f64);
+        Self::unchecked_from_val(self.val.call("slice", &[start.into(), end.into()]))
+    }
+
+    /// Returns this string followed by `other`
+    pub fn concat(&self, other: impl IntoVal) -> JsString {
+        Self::unchecked_from_val(self.val.call("concat", &[other.into_val()]))
+    }
+
+    /// Returns true if the string starts with `prefix`
+    pub fn starts_with(&self, prefix: impl IntoVal) -> bool {
+        self.val.call("startsWith", &[prefix.into_val()]).as_()
+    }
+
+    /// Returns the code unit index of the first occurrence of `needle`
+    pub fn index_of(&self, needle: impl IntoVal) -> Option<usize> {
+        let i = self.val.call("indexOf", &[needle.into_val()]).as_::<f64>();
+        (i >= 0.0).then_some(i as usize)
+    }
+
+    /// Returns true if the string equals `s`, comparing on the JS side
+    pub fn eq_str(&self, s: &str) -> bool {
+        self.val == Val::from(s)
+    }
+
+    /// Copies the string into Rust, replacing unpaired surrogates with U+FFFD
+    pub fn to_string_lossy(&self) -> String {
+        self.val.as_string().unwrap_or_default()
+    }
+
+    /// Copies the string into Rust, failing if it holds unpaired surrogates
+    pub fn try_to_string(&self) -> Result<String, FromUtf16Error> {
+        String::from_utf16(&self.to_utf16())
+    }
+
+    /// Copies the string's UTF-16 code units into Rust
+    pub fn to_utf16(&self) -> Vec<u16> {
+        self.val.to_utf16().unwrap_or_default()
+    }
+
+    /// Encodes the string as WTF-8: UTF-8, except that unpaired surrogates are
+    /// encoded like any other code point. Valid UTF-8 for strings without them.
+    pub fn to_wtf8(&self) -> Vec<u8> {
+        let units = self.to_utf16();
+        let mut out = Vec::with_capacity(units.len());
+        for c in char::decode_utf16(units) {
+            let cp = match c {
+                Ok(c) => c as u32,
+                Err(e) => e.unpaired_surrogate() as u32,
+            };
+            push_code_point(&mut out, cp);
+        }
+        out
+    }
+
+    /// Creates a string from WTF-8, as produced by [`to_wtf8`](Self::to_wtf8).
+    /// Returns `None` if `bytes` isn't well-formed WTF-8.
+    pub fn from_wtf8(bytes: &[u8]) -> Option<JsString> {
+        let mut units = Vec::with_capacity(bytes.len());
+        let mut i = 0;
+        while i < bytes.len() {
+            let b = bytes[i];
+            let (len, min, mut cp) = match b {
+                0x00..=0x7F => (1, 0, b as u32),
+                0xC0..=0xDF => (2, 0x80, (b & 0x1F) as u32),
+                0xE0..=0xEF => (3, 0x800, (b & 0x0F) as u32),
+                0xF0..=0xF4 => (4, 0x10000, (b & 0x07) as u32),
+                _ => return None,
+            };
+            for &t in bytes.get(i + 1..i + len)? {
+                if t & 0xC0 != 0x80 {
+                    return None;
+                }
+                cp = cp << 6 | (t & 0x3F) as u32;
+            }
+            if cp < min || cp > 0x10FFFF {
+                return None;
+            }
+            if cp >= 0x10000 {
+                let cp = cp - 0x10000;
+                units.push(0xD800 | (cp >> 10) as u16);
+                units.push(0xDC00 | (cp & 0x3FF) as u16);
+            } else {
+                // A lead surrogate followed by a trail one should have been
+                // encoded as a pair
+                let paired = (0xDC00..0xE000).contains(&cp)
+                    && units.last().is_some_and(|u| (0xD800..0xDC00).contains(u));
+                if paired {
+                    return None;
+                }
+                units.push(cp as u16);
+            }
+            i += len;
+        }
+        Some(JsString::from(units.as_slice()))
+    }
+}
+
+fn push_code_point(out: &mut Vec<u8>, cp: u32) {
+    match cp {
+        0..0x80 => out.push(cp as u8),
+        0x80..0x800 => out.extend([0xC0 | (cp >> 6) as u8, 0x80 | (cp & 0x3F) as u8]),
+        0x800..0x10000 => out.extend([
+            0xE0 | (cp >> 12) as u8,
+            0x80 | (cp >> 6 & 0x3F) as u8,
+            0x80 | (cp & 0x3F) as u8,
+        ]),
+        _ => out.extend([
+            0xF0 | (cp >> 18) as u8,
+            0x80 | (cp >> 12 & 0x3F) as u8,
+            0x80 | (cp >> 6 & 0x3F) as u8,
+            0x80 | (cp & 0x3F) as u8,
+        ]),
+    }
+}
+
+impl JsCast for JsString {
+    fn instanceof(v: &Val) -> bool {
+        v.is_string()
+    }
+
+    fn unchecked_from_val(v: Val) -> Self {
+        JsString { val: v }
+    }
+
+    fn unchecked_from_val_ref(v: &Val) -> &Self {
+        // Sound since the struct is a transparent wrapper around a Val
+        unsafe { &*(v as *const Val as *const Self) }
+    }
+}
+
+impl TryFromVal for JsString {
+    fn try_from_val(v: &Val) -> Result<Self, ConversionError> {
+        v.clone()
+            .dyn_into()
+            .map_err(|v| ConversionError::new("string", &v))
+    }
+}
+
+impl From<&str> for JsString {
+    fn from(s: &str) -> Self {
+        Self::unchecked_from_val(Val::from(s))
+    }
+}
+
+impl From<String> for JsString {
+    fn from(s: String) -> Self {
+        Self::unchecked_from_val(Val::from(s))
+    }
+}
+
+impl From<&[u16]> for JsString {
+    fn from(s: &[u16]) -> Self {
+        Self::unchecked_from_val(Val::from_utf16(s))
+    }
+}
+
+impl PartialEq for JsString {
+    fn eq(&self, other: &JsString) -> bool {
+        self.val == other.val
+    }
+}
+
+impl Eq for JsString {}
+
+impl PartialEq<str> for JsString {
+    fn eq(&self, other: &str) -> bool {
+        self.eq_str(other)
+    }
+}
+
+impl PartialEq<&str> for JsString {
+    fn eq(&self, other: &&str) -> bool {
+        self.eq_str(other)
+    }
+}
+
+impl PartialOrd for JsString {
+    fn partial_cmp(&self, other: &JsString) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for JsString {
+    /// Compares code unit by code unit, like JS's `<`
+    fn cmp(&self, other: &JsString) -> Ordering {
+        self.val.partial_cmp(&other.val).unwrap_or(Ordering::Equal)
+    }
+}
+
+impl fmt::Display for JsString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_string_lossy())
+    }
+}
+
+impl Deref for JsString {
+    type Target = Val;
+
+    fn deref(&self) -> &Val {
+        &self.val
+    }
+}
+
+impl AsRef<Val> for JsString {
+    fn as_ref(&self) -> &Val {
+        &self.val
+    }
+}
+
+impl AsHandle for JsString {
+    fn as_handle(&self) -> Handle {
+        self.val.as_handle()
+    }
+}
+
+impl From<JsString> for Val {
+    fn from(s: JsString) -> Self {
+        s.val
+    }
+}
+
+impl From<&JsString> for Val {
+    fn from(s: &JsString) -> Self {
+        s.val.clone()
+    }
+}
+
+impl crate::VecElement for JsString {}
+
+impl crate::VecElement for &JsString {}
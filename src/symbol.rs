@@ -0,0 +1,64 @@
+use crate::Val;
+
+impl Val {
+    /// Creates a new unique symbol, as `Symbol(description)` does
+    pub fn symbol(description: &str) -> Val {
+        Val::global("Symbol").invoke(&[description.into()])
+    }
+
+    /// Returns the symbol registered under `key` in the global symbol
+    /// registry, creating it if needed, as `Symbol.for(key)` does
+    pub fn symbol_for(key: &str) -> Val {
+        Val::global("Symbol").call("for", &[key.into()])
+    }
+}
+
+/// The well-known symbols exposed as static properties of `Symbol`.
+///
+/// Converts into the symbol `Val`, so it can be used directly as a property
+/// key: `obj.get(WellKnownSymbol::Iterator)`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum WellKnownSymbol {
+    AsyncIterator,
+    HasInstance,
+    IsConcatSpreadable,
+    Iterator,
+    Match,
+    MatchAll,
+    Replace,
+    Search,
+    Species,
+    Split,
+    ToPrimitive,
+    ToStringTag,
+    Unscopables,
+}
+
+impl WellKnownSymbol {
+    /// Returns the property name on `Symbol`, e.g. `"asyncIterator"`
+    pub const fn name(self) -> &'static str {
+        match self {
+            WellKnownSymbol::AsyncIterator => "asyncIterator",
+            WellKnownSymbol::HasInstance => "hasInstance",
+            WellKnownSymbol::IsConcatSpreadable => "isConcatSpreadable",
+            WellKnownSymbol::Iterator => "iterator",
+            WellKnownSymbol::Match => "match",
+            WellKnownSymbol::MatchAll => "matchAll",
+            WellKnownSymbol::Replace => "replace",
+            WellKnownSymbol::Search => "search",
+            WellKnownSymbol::Species => "species",
+            WellKnownSymbol::Split => "split",
+            WellKnownSymbol::ToPrimitive => "toPrimitive",
+            WellKnownSymbol::ToStringTag => "toStringTag",
+            WellKnownSymbol::Unscopables => "unscopables",
+        }
+    }
+}
+
+impl From<WellKnownSymbol> for Val {
+    fn from(s: WellKnownSymbol) -> Self {
+        Val::global("Symbol").get(s.name())
+    }
+}
+
+impl crate::VecElement for WellKnownSymbol {}
@@ -0,0 +1,148 @@
+//! Bulk transfer between Rust slices and JS typed arrays.
+//!
+//! Element data moves in a single host call instead of one call per element,
+//! which is what [`Val::from_slice`] costs.
+
+use crate::Val;
+use crate::sys::*;
+use alloc::vec::Vec;
+
+/// Element types of the JS typed array constructors, as passed to the host.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TypedArrayKind {
+    Int8 = 0,
+    Uint8,
+    Uint8Clamped,
+    Int16,
+    Uint16,
+    Int32,
+    Uint32,
+    Float32,
+    Float64,
+    BigInt64,
+    BigUint64,
+}
+
+impl TypedArrayKind {
+    /// Returns the name of the JS constructor, e.g. `"Float32Array"`
+    pub const fn constructor_name(self) -> &'static str {
+        match self {
+            TypedArrayKind::Int8 => "Int8Array",
+            TypedArrayKind::Uint8 => "Uint8Array",
+            TypedArrayKind::Uint8Clamped => "Uint8ClampedArray",
+            TypedArrayKind::Int16 => "Int16Array",
+            TypedArrayKind::Uint16 => "Uint16Array",
+            TypedArrayKind::Int32 => "Int32Array",
+            TypedArrayKind::Uint32 => "Uint32Array",
+            TypedArrayKind::Float32 => "Float32Array",
+            TypedArrayKind::Float64 => "Float64Array",
+            TypedArrayKind::BigInt64 => "BigInt64Array",
+            TypedArrayKind::BigUint64 => "BigUint64Array",
+        }
+    }
+}
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// A Rust type with a matching JS typed array element type.
+///
+/// Every bit pattern is a valid value of these types, so their slices can be
+/// filled from raw bytes.
+pub trait TypedArrayElement: sealed::Sealed + Copy + Default + 'static {
+    const KIND: TypedArrayKind;
+}
+
+macro_rules! impl_element {
+    ($($t:ty => $kind:ident),*) => {$(
+        impl sealed::Sealed for $t {}
+        impl TypedArrayElement for $t {
+            const KIND: TypedArrayKind = TypedArrayKind::$kind;
+        }
+    )*}
+}
+
+impl_element!(
+    i8 => Int8,
+    u8 => Uint8,
+    i16 => Int16,
+    u16 => Uint16,
+    i32 => Int32,
+    u32 => Uint32,
+    f32 => Float32,
+    f64 => Float64,
+    i64 => BigInt64,
+    u64 => BigUint64
+);
+
+fn as_bytes<T: TypedArrayElement>(s: &[T]) -> &[u8] {
+    unsafe { core::slice::from_raw_parts(s.as_ptr() as *const u8, core::mem::size_of_val(s)) }
+}
+
+fn as_bytes_mut<T: TypedArrayElement>(s: &mut [T]) -> &mut [u8] {
+    unsafe { core::slice::from_raw_parts_mut(s.as_mut_ptr() as *mut u8, core::mem::size_of_val(s)) }
+}
+
+impl Val {
+    /// Creates a typed array holding a copy of `data`, e.g. a `Float32Array`
+    /// for `&[f32]` or a `Uint8Array` for `&[u8]`
+    pub fn from_typed_slice<T: TypedArrayElement>(data: &[T]) -> Val {
+        Val::take_ownership(unsafe {
+            emlite_val_make_typed_array_unified(T::KIND as u32, as_bytes(data), data.len())
+        })
+    }
+
+    /// Returns the number of `T` elements [`Val::to_typed_vec`] would yield:
+    /// the length of a typed array or array, or the number of whole `T`s in
+    /// an `ArrayBuffer`
+    pub fn typed_len<T: TypedArrayElement>(&self) -> usize {
+        unsafe { emlite_val_typed_array_len_unified(self.as_handle(), T::KIND as u32) }
+    }
+
+    /// Copies a typed array, `ArrayBuffer` or array into a new `Vec<T>`.
+    ///
+    /// Typed array and array elements are converted to `T` as the
+    /// corresponding `TypedArray.prototype.set` would; `ArrayBuffer` bytes are
+    /// reinterpreted as `T`s.
+    pub fn to_typed_vec<T: TypedArrayElement>(&self) -> Vec<T> {
+        let mut v = alloc::vec![T::default(); self.typed_len::<T>()];
+        let n = self.copy_to_typed_slice(&mut v);
+        v.truncate(n);
+        v
+    }
+
+    /// Copies as many elements as fit into `dst`, like [`Val::to_typed_vec`],
+    /// returning how many were written
+    pub fn copy_to_typed_slice<T: TypedArrayElement>(&self, dst: &mut [T]) -> usize {
+        let len = dst.len();
+        unsafe {
+            emlite_val_typed_array_copy_unified(
+                self.as_handle(),
+                T::KIND as u32,
+                as_bytes_mut(dst),
+                len,
+            )
+        }
+    }
+
+    /// Creates a typed array viewing `data` in place, without copying.
+    ///
+    /// # Safety
+    ///
+    /// The view aliases wasm linear memory. Until the returned value is dropped
+    /// and no JS code holds on to it, `data` must not be moved, freed or
+    /// mutated from Rust, and JS must not write through the view. Any memory
+    /// growth (including allocations) detaches the view's buffer on the host,
+    /// so it's best used immediately, e.g. to hand data to a call that copies it.
+    ///
+    /// Component-model hosts can't share linear memory, so this isn't
+    /// available with the `wasip2-component` backend.
+    #[cfg(not(all(target_os = "wasi", target_env = "p2", feature = "wasip2-component")))]
+    pub unsafe fn view_typed_slice<T: TypedArrayElement>(data: &[T]) -> Val {
+        Val::take_ownership(unsafe {
+            emlite_val_make_typed_array_view(T::KIND as u32, data.as_ptr() as _, data.len())
+        })
+    }
+}
@@ -1,4 +1,3 @@
-use crate::FromVal;
 use crate::Val;
 use crate::common::Handle;
 use alloc::alloc::{Layout, dealloc};
@@ -105,8 +104,8 @@ pub unsafe fn emlite_val_get_value_string_utf16(h: Handle) -> Vec<u16> {
     host::emlite_val_get_value_string_utf16(h)
 }
 
-pub unsafe fn emlite_val_typeof(h: Handle) -> String {
-    host::emlite_val_typeof(h)
+pub unsafe fn emlite_val_typeof_tag(h: Handle) -> u32 {
+    host::emlite_val_typeof_tag(h)
 }
 
 pub unsafe fn emlite_val_push(arr: Handle, val: Handle) {
@@ -121,6 +120,14 @@ pub unsafe fn emlite_val_set(obj: Handle, idx: Handle, val: Handle) {
     host::emlite_val_set(obj, idx, val);
 }
 
+pub unsafe fn emlite_val_get_index(obj: Handle, idx: u32) -> Handle {
+    host::emlite_val_get_index(obj, idx)
+}
+
+pub unsafe fn emlite_val_set_index(obj: Handle, idx: u32, val: Handle) {
+    host::emlite_val_set_index(obj, idx, val);
+}
+
 pub unsafe fn emlite_val_has(obj: Handle, key: Handle) -> bool {
     host::emlite_val_has(obj, key)
 }
@@ -185,6 +192,40 @@ pub unsafe fn emlite_val_func_call(fn_handle: Handle, argv: Handle) -> Handle {
     host::emlite_val_func_call(fn_handle, argv)
 }
 
+pub unsafe fn emlite_val_obj_call_args(obj: Handle, method: &str, args: &[Handle]) -> Handle {
+    host::emlite_val_obj_call_args(obj, method, args)
+}
+
+pub unsafe fn emlite_val_construct_new_args(ctor: Handle, args: &[Handle]) -> Handle {
+    host::emlite_val_construct_new_args(ctor, args)
+}
+
+pub unsafe fn emlite_val_func_call_args(fn_handle: Handle, args: &[Handle]) -> Handle {
+    host::emlite_val_func_call_args(fn_handle, args)
+}
+
+pub unsafe fn emlite_val_obj_call_args_try(
+    obj: Handle,
+    method: &str,
+    args: &[Handle],
+) -> Result<Handle, Handle> {
+    host::emlite_val_obj_call_args_try(obj, method, args)
+}
+
+pub unsafe fn emlite_val_construct_new_args_try(
+    ctor: Handle,
+    args: &[Handle],
+) -> Result<Handle, Handle> {
+    host::emlite_val_construct_new_args_try(ctor, args)
+}
+
+pub unsafe fn emlite_val_func_call_args_try(
+    fn_handle: Handle,
+    args: &[Handle],
+) -> Result<Handle, Handle> {
+    host::emlite_val_func_call_args_try(fn_handle, args)
+}
+
 pub unsafe fn emlite_val_inc_ref(h: Handle) {
     host::emlite_val_inc_ref(h);
 }
@@ -210,6 +251,18 @@ pub unsafe fn emlite_val_make_callback(fidx: Handle, data: Handle) -> Handle {
     host::emlite_val_make_callback(fidx, data)
 }
 
+pub unsafe fn emlite_val_make_typed_array(kind: u32, data: &[u8]) -> Handle {
+    host::emlite_val_make_typed_array(kind, data)
+}
+
+pub unsafe fn emlite_val_typed_array_len(h: Handle, kind: u32) -> u32 {
+    host::emlite_val_typed_array_len(h, kind)
+}
+
+pub unsafe fn emlite_val_typed_array_copy(h: Handle, kind: u32, len: u32) -> Vec<u8> {
+    host::emlite_val_typed_array_copy(h, kind, len)
+}
+
 // WASI-P2: JS stores callbacks; `data` is a handle to BigInt(pointer to Pack)
 #[repr(C)]
 struct Pack {
@@ -256,24 +309,90 @@ pub unsafe fn emlite_val_make_str_utf16_unified(s: &[u16]) -> Handle {
     unsafe { emlite_val_make_str_utf16(s) }
 }
 
-pub unsafe fn emlite_val_obj_call_unified(obj: Handle, method: &str, argv: Handle) -> Handle {
-    unsafe { emlite_val_obj_call(obj, method, argv) }
+pub unsafe fn emlite_val_obj_call_unified(obj: Handle, method: &str, args: &[Handle]) -> Handle {
+    unsafe { emlite_val_obj_call_args(obj, method, args) }
+}
+
+pub unsafe fn emlite_val_construct_new_unified(ctor: Handle, args: &[Handle]) -> Handle {
+    unsafe { emlite_val_construct_new_args(ctor, args) }
+}
+
+pub unsafe fn emlite_val_func_call_unified(func: Handle, args: &[Handle]) -> Handle {
+    unsafe { emlite_val_func_call_args(func, args) }
 }
 
 pub unsafe fn emlite_val_obj_has_own_prop_unified(obj: Handle, prop: &str) -> bool {
     unsafe { emlite_val_obj_has_own_prop(obj, prop) }
 }
 
-pub unsafe fn emlite_val_typeof_unified(h: Handle) -> String {
-    unsafe { emlite_val_typeof(h) }
+pub unsafe fn emlite_val_obj_call_try_unified(
+    obj: Handle,
+    method: &str,
+    args: &[Handle],
+) -> Result<Handle, Handle> {
+    unsafe { emlite_val_obj_call_args_try(obj, method, args) }
+}
+
+pub unsafe fn emlite_val_construct_new_try_unified(
+    ctor: Handle,
+    args: &[Handle],
+) -> Result<Handle, Handle> {
+    unsafe { emlite_val_construct_new_args_try(ctor, args) }
+}
+
+pub unsafe fn emlite_val_func_call_try_unified(
+    func: Handle,
+    args: &[Handle],
+) -> Result<Handle, Handle> {
+    unsafe { emlite_val_func_call_args_try(func, args) }
+}
+
+pub unsafe fn emlite_val_throw_from_callback_unified(val: Handle) -> Handle {
+    unsafe { emlite_val_throw(val) }
 }
 
-pub unsafe fn emlite_val_get_value_string_unified(h: Handle) -> Option<String> {
-    unsafe { Some(emlite_val_get_value_string(h)) }
+pub unsafe fn emlite_val_typeof_unified(h: Handle) -> &'static str {
+    crate::common::typeof_name(unsafe { emlite_val_typeof_tag(h) })
 }
 
-pub unsafe fn emlite_val_get_value_string_utf16_unified(h: Handle) -> Option<Vec<u16>> {
-    unsafe { Some(emlite_val_get_value_string_utf16(h)) }
+// The canonical ABI already hands over an owned, length-prefixed string, so
+// these only move it into the caller's buffer.
+pub unsafe fn emlite_val_get_value_string_into_unified(h: Handle, out: &mut String) -> bool {
+    out.clear();
+    out.push_str(&unsafe { emlite_val_get_value_string(h) });
+    true
+}
+
+pub unsafe fn emlite_val_get_value_string_utf16_into_unified(
+    h: Handle,
+    out: &mut Vec<u16>,
+) -> bool {
+    out.clear();
+    out.extend_from_slice(&unsafe { emlite_val_get_value_string_utf16(h) });
+    true
+}
+
+pub unsafe fn emlite_val_make_typed_array_unified(kind: u32, data: &[u8], _len: usize) -> Handle {
+    unsafe { emlite_val_make_typed_array(kind, data) }
+}
+
+pub unsafe fn emlite_val_typed_array_len_unified(h: Handle, kind: u32) -> usize {
+    unsafe { emlite_val_typed_array_len(h, kind) as usize }
+}
+
+pub unsafe fn emlite_val_typed_array_copy_unified(
+    h: Handle,
+    kind: u32,
+    dst: &mut [u8],
+    len: usize,
+) -> usize {
+    if len == 0 {
+        return 0;
+    }
+    let bytes = unsafe { emlite_val_typed_array_copy(h, kind, len as u32) };
+    let n = bytes.len().min(dst.len());
+    dst[..n].copy_from_slice(&bytes[..n]);
+    n / (dst.len() / len)
 }
 
 pub unsafe fn emlite_val_not_unified(h: Handle) -> bool {
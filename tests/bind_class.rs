@@ -0,0 +1,107 @@
+use emlite::mockenv::{self, Value};
+use emlite::*;
+
+emlite::bind_class! {
+    /// `Point` from the mock host
+    pub Point;
+    fn new(x: f64, y: f64);
+    /// Moves the point
+    fn translate(&self, dx: f64, dy: f64);
+    #[js_name = "norm2"]
+    fn squared_norm(&self) -> f64;
+    #[getter]
+    fn x(&self) -> f64;
+    #[setter = "x"]
+    fn set_x(&self, x: f64);
+    #[setter]
+    fn set_y(&self, y: f64);
+    #[js_name = "fromArray"]
+    static fn from_array(xy: [f64; 2]) -> Self;
+}
+
+emlite::bind_class! {
+    Missing = "NotAClass";
+}
+
+fn val(v: &Value) -> Val {
+    Val::take_ownership(mockenv::to_handle(v.clone()))
+}
+
+fn function(name: &str, f: impl Fn(Val, Vec<Val>) -> Val + 'static) -> Val {
+    val(&mockenv::new_function(name, move |this, args| {
+        let ret = f(val(this), args.iter().map(val).collect());
+        Ok(mockenv::value_of(ret.as_handle()))
+    }))
+}
+
+/// Defines `class Point { constructor(x, y) ... }` with a few methods
+fn define_point() {
+    let point = function("Point", |this, args| {
+        this.set("x", &args[0]);
+        this.set("y", &args[1]);
+        Val::undefined()
+    });
+    let proto = Val::object();
+    proto.set(
+        "translate",
+        function("translate", |this, args| {
+            this.set("x", this.get("x").as_::<f64>() + args[0].as_::<f64>());
+            this.set("y", this.get("y").as_::<f64>() + args[1].as_::<f64>());
+            Val::undefined()
+        }),
+    );
+    proto.set(
+        "norm2",
+        function("norm2", |this, _| {
+            let (x, y) = (this.get("x").as_::<f64>(), this.get("y").as_::<f64>());
+            Val::from(x * x + y * y)
+        }),
+    );
+    point.set("prototype", &proto);
+    let ctor = point.clone();
+    point.set(
+        "fromArray",
+        function("fromArray", move |_, args| {
+            ctor.new(&[args[0].at(0), args[0].at(1)])
+        }),
+    );
+    Val::global_this().set("Point", point);
+}
+
+#[test]
+fn generated_methods_call_into_js() {
+    emlite::init();
+    define_point();
+    let p = Point::new(3.0, 4.0);
+    assert_eq!(p.squared_norm(), 25.0);
+    p.translate(1.0, 0.0);
+    assert_eq!(p.x(), 4.0);
+    p.set_x(0.0);
+    assert_eq!(p.squared_norm(), 16.0);
+    p.set_y(1.0);
+    assert_eq!(p.get("y").as_::<f64>(), 1.0);
+    assert!(!p.has("set_y"));
+
+    let q = Point::from_array([1.0, 2.0]);
+    assert_eq!(q.get("y").as_::<f64>(), 2.0);
+    assert!(Point::constructor() == Val::global("Point"));
+}
+
+#[test]
+fn wrappers_are_cast_with_instanceof() {
+    emlite::init();
+    define_point();
+    let p: Val = Point::new(0.0, 0.0).into();
+    assert!(p.clone().dyn_into::<Point>().is_ok());
+    assert!(Val::object().dyn_into::<Point>().is_err());
+    assert!(!p.is_instance_of::<Missing>());
+}
+
+#[test]
+fn classes_defined_after_first_use_are_found() {
+    emlite::init();
+    assert!(Point::constructor().is_undefined());
+    define_point();
+    assert!(Point::constructor() == Val::global("Point"));
+    assert_eq!(Point::new(3.0, 4.0).squared_norm(), 25.0);
+}
@@ -0,0 +1,115 @@
+use emlite::mockenv::{self, Value};
+use emlite::*;
+
+#[derive(JsCast, Clone, Debug)]
+#[repr(transparent)]
+struct EventTarget {
+    val: Val,
+}
+
+impl EventTarget {
+    fn kind(&self) -> String {
+        self.get("kind").as_()
+    }
+}
+
+#[derive(JsCast, Clone, Debug)]
+#[repr(transparent)]
+#[emlite(extends = EventTarget)]
+struct Element(Val);
+
+impl Element {
+    fn tag(&self) -> String {
+        self.get("tag").as_()
+    }
+}
+
+#[derive(JsCast, Clone, Debug)]
+#[repr(transparent)]
+#[emlite(js_class = "HTMLButtonElement", extends = Element, extends = EventTarget)]
+struct HtmlButtonElement(Val);
+
+/// Defines global classes `Element extends EventTarget` and
+/// `HTMLButtonElement extends Element`, returning a button instance
+fn button() -> Val {
+    let object = Val::global("Object");
+    let mut parent = Val::null();
+    for name in ["EventTarget", "Element", "HTMLButtonElement"] {
+        let ctor = Val::take_ownership(mockenv::to_handle(mockenv::new_function(name, |_, _| {
+            Ok(Value::Undefined)
+        })));
+        let proto = object.call("create", &[parent]);
+        ctor.set("prototype", &proto);
+        Val::global_this().set(name, &ctor);
+        parent = proto;
+    }
+    let button = object.call("create", &[parent]);
+    button.set("kind", "button");
+    button.set("tag", "BUTTON");
+    button
+}
+
+#[test]
+fn downcasts_check_instanceof() {
+    emlite::init();
+    let v = button();
+    let button: HtmlButtonElement = v.clone().dyn_into().unwrap();
+    assert!(button.is_instance_of::<Element>());
+    assert!(button.dyn_ref::<EventTarget>().is_some());
+
+    let target: EventTarget = Val::global("Object")
+        .call("create", &[Val::global("EventTarget").get("prototype")])
+        .unchecked_into();
+    assert!(target.is_instance_of::<EventTarget>());
+    assert!(!target.is_instance_of::<Element>());
+    let target = target.dyn_into::<HtmlButtonElement>().unwrap_err();
+    assert!(target.dyn_ref::<Element>().is_none());
+
+    assert!(Val::from(1).dyn_into::<EventTarget>().is_err());
+    // Classes missing from the host have no instances
+    assert!(!v.is_instance_of::<Missing>());
+}
+
+#[derive(JsCast)]
+#[repr(transparent)]
+struct Missing(Val);
+
+#[test]
+fn upcasts_follow_deref_chains() {
+    emlite::init();
+    let button: HtmlButtonElement = button().unchecked_into();
+    // Methods of every ancestor are reachable
+    assert_eq!(button.tag(), "BUTTON");
+    assert_eq!(button.kind(), "button");
+    assert_eq!(button.get("tag").as_::<String>(), "BUTTON");
+
+    let element: &Element = &button;
+    assert_eq!(element.as_handle(), button.as_handle());
+    let target: &EventTarget = button.as_ref();
+    assert_eq!(target.kind(), "button");
+    let target = EventTarget::from(button.clone());
+    assert!(target.dyn_into::<HtmlButtonElement>().is_ok());
+    assert!(Val::from(&button) == Val::from(button));
+}
+
+#[derive(JsCast)]
+#[repr(transparent)]
+struct LateLoaded(Val);
+
+#[test]
+fn constructors_defined_after_a_cast_are_found() {
+    emlite::init();
+    let object = Val::global("Object");
+    let proto = object.call("create", &[Val::null()]);
+    let v = object.call("create", std::slice::from_ref(&proto));
+    assert!(v.clone().dyn_into::<LateLoaded>().is_err());
+
+    // As if a script defining the class had loaded since
+    let ctor = Val::take_ownership(mockenv::to_handle(mockenv::new_function(
+        "LateLoaded",
+        |_, _| Ok(Value::Undefined),
+    )));
+    ctor.set("prototype", &proto);
+    Val::global_this().set("LateLoaded", &ctor);
+    assert!(v.dyn_into::<LateLoaded>().is_ok());
+}
@@ -0,0 +1,153 @@
+use emlite::*;
+use std::cell::Cell;
+use std::rc::Rc;
+
+struct DropFlag(Rc<Cell<bool>>);
+
+impl Drop for DropFlag {
+    fn drop(&mut self) {
+        self.0.set(true);
+    }
+}
+
+fn flag() -> (DropFlag, Rc<Cell<bool>>) {
+    let dropped = Rc::new(Cell::new(false));
+    (DropFlag(dropped.clone()), dropped)
+}
+
+#[test]
+fn drop_frees_the_body() {
+    emlite::init();
+    let (guard, dropped) = flag();
+    let c = Closure::new(move |args| {
+        let _ = &guard;
+        Val::from(args.len() as i32)
+    });
+    assert_eq!(c.invoke(&argv![1, 2]).as_::<i32>(), 2);
+    let f = Val::from(&c);
+    drop(c);
+    assert!(dropped.get());
+    assert!(f.is_function());
+}
+
+#[test]
+fn forgotten_closures_are_freed_on_finalization() {
+    emlite::init();
+    let before = mockenv::live_handles();
+    let (guard, dropped) = flag();
+    let f = Closure::new(move |_| {
+        let _ = &guard;
+        Val::undefined()
+    })
+    .forget();
+    let obj = Val::object();
+    obj.set("cb", &f);
+    drop(f);
+    obj.get("cb").invoke(&[]);
+    assert!(!dropped.get());
+
+    drop(obj);
+    mockenv::run_finalizers();
+    assert!(dropped.get());
+    assert_eq!(mockenv::live_handles(), before);
+}
+
+#[test]
+fn once_frees_itself_after_the_first_call() {
+    emlite::init();
+    let (guard, dropped) = flag();
+    let c = Closure::once(move |_| {
+        drop(guard);
+        Val::from("done")
+    });
+    let f = c.forget();
+    assert_eq!(f.invoke(&[]).as_::<Option<String>>().unwrap(), "done");
+    assert!(dropped.get());
+}
+
+#[test]
+fn make_fn_is_released_with_its_function() {
+    emlite::init();
+    let before = mockenv::live_handles();
+    let (guard, dropped) = flag();
+    let f = Val::make_fn(move |_| {
+        let _ = &guard;
+        Val::null()
+    });
+    assert!(f.invoke(&[]).is_null());
+    drop(f);
+    mockenv::run_finalizers();
+    assert!(dropped.get());
+    assert_eq!(mockenv::live_handles(), before);
+}
+
+#[test]
+fn returned_values_are_handed_to_the_caller() {
+    emlite::init();
+    let before = mockenv::live_handles();
+    // The callback holds the only reference to what it returns
+    let f = Val::make_fn(|_| {
+        let obj = Val::object();
+        obj.set("n", 1);
+        obj
+    });
+    let ret = f.invoke(&[]);
+    assert_eq!(mockenv::ref_count(ret.as_handle()), Some(1));
+    assert_eq!(ret.get("n").as_::<i32>(), 1);
+    drop((f, ret));
+    mockenv::run_finalizers();
+    assert_eq!(mockenv::live_handles(), before);
+}
+
+#[test]
+fn wrapped_closures_convert_arguments_and_results() {
+    emlite::init();
+    let add = Closure::wrap(|a: f64, b: i32| a + b as f64);
+    assert_eq!(add.invoke(&argv![1.5, 2]).as_::<f64>(), 3.5);
+
+    let greet = Closure::wrap(|name: String, excited: Option<bool>| {
+        format!("hi {name}{}", if excited == Some(true) { "!" } else { "" })
+    });
+    assert_eq!(greet.invoke(&argv!["bob"]).as_::<String>(), "hi bob");
+    assert_eq!(greet.invoke(&argv!["bob", true]).as_::<String>(), "hi bob!");
+
+    let count = Rc::new(Cell::new(0));
+    let counter = {
+        let count = count.clone();
+        Closure::wrap(move || count.set(count.get() + 1))
+    };
+    assert!(counter.invoke(&[]).is_undefined());
+    assert_eq!(count.get(), 1);
+}
+
+#[test]
+fn wrapped_closures_throw_conversion_failures_and_errors() {
+    emlite::init();
+    let calls = Rc::new(Cell::new(0));
+    let halve = {
+        let calls = calls.clone();
+        Closure::wrap(move |n: u32| -> Result<u32, JsError> {
+            calls.set(calls.get() + 1);
+            if n % 2 == 1 {
+                return Err(JsError::with_constructor("RangeError", "odd"));
+            }
+            Ok(n / 2)
+        })
+    };
+    assert_eq!(halve.try_invoke(&argv![8]).unwrap().as_::<u32>(), 4);
+
+    let err = halve.try_invoke(&argv!["8"]).unwrap_err();
+    assert_eq!(err.name().as_deref(), Some("TypeError"));
+    assert_eq!(
+        err.message().as_deref(),
+        Some("argument 0: expected u32, found string")
+    );
+    assert!(halve.try_invoke(&[]).is_err());
+    assert_eq!(calls.get(), 1);
+
+    let err = halve.try_invoke(&argv![3]).unwrap_err();
+    assert_eq!(err.name().as_deref(), Some("RangeError"));
+    assert_eq!(err.message().as_deref(), Some("odd"));
+    // The closure survives throwing
+    assert_eq!(halve.try_invoke(&argv![2]).unwrap().as_::<u32>(), 1);
+}
@@ -0,0 +1,136 @@
+use emlite::*;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::rc::Rc;
+
+#[test]
+fn sequences_are_arrays() {
+    emlite::init();
+    let v = Val::from(vec![1, 2, 3]);
+    assert!(
+        Val::global("Array")
+            .call("isArray", std::slice::from_ref(&v))
+            .as_::<bool>()
+    );
+    assert_eq!(v.as_::<Vec<i32>>(), [1, 2, 3]);
+    assert_eq!(Val::from(["a", "b"]).as_::<[String; 2]>(), ["a", "b"]);
+
+    let t = Val::from((1.5, "x", true));
+    assert_eq!(t.get("length").as_::<u32>(), 3);
+    assert_eq!(t.as_::<(f64, String, bool)>(), (1.5, "x".into(), true));
+
+    let nested = Val::from(vec![vec![1u8], vec![2, 3]]);
+    assert_eq!(nested.as_::<Vec<Vec<u8>>>(), [vec![1], vec![2, 3]]);
+}
+
+#[test]
+fn options_are_null_or_undefined() {
+    emlite::init();
+    assert!(Val::from(None::<i32>).is_undefined());
+    assert_eq!(Val::from(Some(4)).as_::<i32>(), 4);
+    assert_eq!(Val::null().as_::<Option<i32>>(), None);
+    assert_eq!(Val::undefined().as_::<Option<String>>(), None);
+    assert_eq!(Val::from(0).as_::<Option<i32>>(), Some(0));
+    assert_eq!(Val::from(1u64).as_::<Option<u64>>(), Some(1));
+
+    // Numbers, strings and booleans of the wrong JS type read as None
+    assert_eq!(Val::from(5).as_::<Option<String>>(), None);
+    assert_eq!(Val::from("abc").as_::<Option<i32>>(), None);
+    assert_eq!(Val::from("abc").as_::<Option<char>>(), Some('a'));
+    assert_eq!(Val::from(1).as_::<Option<Vec<u16>>>(), None);
+    assert_eq!(Val::from(1).as_::<Option<Vec<i32>>>(), None);
+    assert_eq!(Val::from(0).as_::<Option<bool>>(), None);
+    assert_eq!(Val::from(false).as_::<Option<bool>>(), Some(false));
+    assert_eq!(
+        Val::from(vec![Some(1), None]).as_::<Vec<Option<i32>>>(),
+        [Some(1), None]
+    );
+}
+
+#[test]
+fn maps_are_objects() {
+    emlite::init();
+    let m = BTreeMap::from([("a".to_string(), 1), ("b".to_string(), 2)]);
+    let v = Val::from(m.clone());
+    assert_eq!(v.get("b").as_::<i32>(), 2);
+    assert_eq!(v.as_::<BTreeMap<String, i32>>(), m);
+
+    let h: HashMap<String, Vec<bool>> = HashMap::from([("k".into(), vec![true])]);
+    assert_eq!(Val::from(h.clone()).as_::<HashMap<String, Vec<bool>>>(), h);
+
+    // JS Maps read back too
+    let map = Val::global("Map").new(&[]);
+    map.call("set", &["x".into(), 9.into()]);
+    assert_eq!(
+        map.as_::<BTreeMap<String, u8>>(),
+        BTreeMap::from([("x".into(), 9)])
+    );
+}
+
+#[test]
+fn sets_are_sets() {
+    emlite::init();
+    let s = BTreeSet::from([3, 1, 2]);
+    let v = Val::from(s.clone());
+    assert!(v.instanceof(Val::global("Set")));
+    assert_eq!(v.get("size").as_::<u32>(), 3);
+    assert!(v.call("has", &[2.into()]).as_::<bool>());
+    assert_eq!(v.as_::<BTreeSet<i32>>(), s);
+
+    // Any iterable reads as a set, dropping duplicates
+    let arr = Val::from(vec!["a", "b", "a"]);
+    assert_eq!(
+        arr.as_::<HashSet<String>>(),
+        HashSet::from(["a".into(), "b".into()])
+    );
+}
+
+#[test]
+fn string_types() {
+    emlite::init();
+    assert_eq!(Val::from('é').as_::<Option<String>>().as_deref(), Some("é"));
+    assert_eq!(Val::from("héllo").as_::<char>(), 'h');
+    assert_eq!(Val::from(Cow::Borrowed("cow")).as_::<Cow<str>>(), "cow");
+    assert_eq!(
+        &*Val::from(Box::<str>::from("box")).as_::<Box<str>>(),
+        "box"
+    );
+    assert_eq!(&*Val::from(Rc::<str>::from("rc")).as_::<Rc<str>>(), "rc");
+}
+
+#[test]
+fn utf16_vecs_are_strings() {
+    emlite::init();
+    let units: Vec<u16> = "hé😀".encode_utf16().collect();
+    let v = Val::from(&units);
+    assert!(v.is_string());
+    assert_eq!(v.as_::<String>(), "hé😀");
+    assert_eq!(Val::from(units.clone()).as_::<Vec<u16>>(), units);
+    assert_eq!(
+        Val::from("hi").as_::<Result<Vec<u16>, Val>>().unwrap(),
+        [104, 105]
+    );
+    assert!(Val::from(1).as_::<Result<Vec<u16>, Val>>().is_err());
+
+    // Arrays of numbers still read back
+    let arr = Val::from(vec![1u32, 2]);
+    assert_eq!(arr.as_::<Vec<u16>>(), [1, 2]);
+    assert!(Val::from(vec![units]).at(0).is_string());
+}
+
+struct Celsius(f64);
+
+impl From<Celsius> for Val {
+    fn from(c: Celsius) -> Self {
+        Val::from(c.0)
+    }
+}
+
+impl VecElement for Celsius {}
+
+#[test]
+fn custom_types_opt_into_vecs() {
+    emlite::init();
+    let v = Val::from(vec![Celsius(1.5), Celsius(-2.0)]);
+    assert_eq!(v.as_::<Vec<f64>>(), [1.5, -2.0]);
+}
@@ -0,0 +1,58 @@
+use emlite::*;
+
+#[test]
+fn integers_are_range_checked() {
+    emlite::init();
+    assert_eq!(Val::from(255).try_as::<u8>(), Ok(255));
+    let err = Val::from(256).try_as::<u8>().unwrap_err();
+    assert_eq!(err.expected, "u8");
+    assert_eq!(err.found_typeof, "number");
+    assert_eq!(err.to_string(), "expected u8, found number");
+    assert!(Val::from(-1).try_as::<u32>().is_err());
+    assert!(Val::from(1.5).try_as::<i32>().is_err());
+    assert!(Val::from(f64::NAN).try_as::<i32>().is_err());
+    let err = Val::from("7").try_as::<i32>().unwrap_err();
+    assert_eq!(err.found_typeof, "string");
+    assert_eq!(Val::from(u32::MAX).try_as::<u32>(), Ok(u32::MAX));
+}
+
+#[test]
+fn numbers_and_bigints_are_interchangeable() {
+    emlite::init();
+    // Safe integers only when reading a Number as 64 bits
+    assert_eq!(
+        Val::from(9007199254740991.0).try_as::<i64>(),
+        Ok(9007199254740991)
+    );
+    assert!(Val::from(9007199254740992.0).try_as::<i64>().is_err());
+
+    assert_eq!(Val::from(i64::MIN).try_as::<i64>(), Ok(i64::MIN));
+    assert_eq!(Val::from(u64::MAX).try_as::<u64>(), Ok(u64::MAX));
+    assert!(Val::from(u64::MAX).try_as::<i64>().is_err());
+    assert!(Val::from(-1i64).try_as::<u64>().is_err());
+    assert_eq!(Val::from(7i64).try_as::<u8>(), Ok(7));
+
+    // The lenient conversions accept both too
+    assert_eq!(Val::from(7i64).as_::<Option<i64>>(), Some(7));
+    assert_eq!(Val::from(7).as_::<Option<u64>>(), Some(7));
+    assert_eq!(Val::from(3).as_::<usize>(), 3);
+    assert_eq!(Val::undefined().as_::<Option<i64>>(), None);
+    assert_eq!(Val::from(u32::MAX).as_::<u32>(), u32::MAX);
+}
+
+#[test]
+fn other_types_are_checked() {
+    emlite::init();
+    assert_eq!(Val::from(0.5).try_as::<f64>(), Ok(0.5));
+    assert!(Val::from(1e300).try_as::<f32>().is_err());
+    assert!(Val::from(true).try_as::<f64>().is_err());
+    assert_eq!(Val::from(true).try_as::<bool>(), Ok(true));
+    assert!(Val::from(1).try_as::<bool>().is_err());
+    assert_eq!(Val::from("s").try_as::<String>().as_deref(), Ok("s"));
+    assert_eq!(Val::null().try_as::<Option<i32>>(), Ok(None));
+    assert_eq!(Val::from(2).try_as::<Option<i32>>(), Ok(Some(2)));
+    assert!(Val::from("2").try_as::<Option<i32>>().is_err());
+
+    let e = Val::from(Val::object().try_as::<u8>().unwrap_err());
+    assert!(e.instanceof(Val::global("TypeError")));
+}
@@ -0,0 +1,151 @@
+use emlite::*;
+
+#[derive(IntoVal, FromVal, Debug, PartialEq)]
+struct Point {
+    x: f64,
+    #[emlite(rename = "yPos")]
+    y: f64,
+    #[emlite(skip)]
+    cached: Option<u32>,
+}
+
+#[derive(IntoVal, FromVal, Debug, PartialEq)]
+struct Pair(i32, bool);
+
+#[derive(IntoVal, FromVal, Debug, PartialEq)]
+struct Tagged(i32, #[emlite(skip)] std::rc::Rc<()>, bool);
+
+#[derive(IntoVal, FromVal, Debug, PartialEq)]
+enum Mode {
+    #[emlite(rename = "read-only")]
+    ReadOnly,
+    ReadWrite,
+    #[emlite(other)]
+    Unknown,
+}
+
+#[derive(IntoVal, FromVal, Debug, PartialEq)]
+#[emlite(discriminant)]
+enum Level {
+    Low = 1,
+    Mid,
+    High = 10,
+}
+
+#[derive(IntoVal, FromVal, Debug, PartialEq)]
+#[emlite(tag = "kind")]
+enum Event {
+    Click { x: i32, y: i32 },
+    Key { code: String },
+    Blur,
+}
+
+#[derive(IntoVal, FromVal)]
+#[emlite(transparent, deref)]
+struct Widget {
+    val: Val,
+}
+
+#[derive(IntoVal, FromVal, Debug, PartialEq)]
+struct Meters(f64);
+
+#[test]
+fn structs_map_to_objects_and_arrays() {
+    emlite::init();
+    let before = mockenv::live_handles();
+    {
+        let p = Point {
+            x: 1.0,
+            y: 2.5,
+            cached: Some(3),
+        };
+        let v = Val::from(p);
+        assert_eq!(v.get("yPos").as_::<f64>(), 2.5);
+        assert!(!v.has("cached"));
+        assert_eq!(
+            v.as_::<Point>(),
+            Point {
+                x: 1.0,
+                y: 2.5,
+                cached: None
+            }
+        );
+
+        let v = Val::from(Pair(7, true));
+        assert!(
+            Val::global("Array")
+                .call("isArray", std::slice::from_ref(&v))
+                .as_::<bool>()
+        );
+        assert_eq!(v.as_::<Pair>(), Pair(7, true));
+
+        let v = Val::from(Tagged(7, Default::default(), true));
+        assert!(v.get(1).is_undefined());
+        assert!(v.get(2).as_::<bool>());
+        assert_eq!(v.as_::<Tagged>(), Tagged(7, Default::default(), true));
+
+        assert_eq!(Val::from(Meters(3.5)).as_::<f64>(), 3.5);
+        assert_eq!(Val::from(2.0).as_::<Meters>(), Meters(2.0));
+    }
+    assert_eq!(mockenv::live_handles(), before);
+}
+
+#[test]
+fn enums_map_to_tags() {
+    emlite::init();
+    let v = Val::from(Mode::ReadOnly);
+    assert_eq!(v.as_::<Option<String>>().unwrap(), "read-only");
+    assert_eq!(v.as_::<Mode>(), Mode::ReadOnly);
+    assert_eq!(Val::from("ReadWrite").as_::<Mode>(), Mode::ReadWrite);
+    assert_eq!(Val::from("append").as_::<Mode>(), Mode::Unknown);
+
+    assert_eq!(Val::from(Level::Mid).as_::<i32>(), 2);
+    assert_eq!(Val::from(10).try_as::<Level>(), Ok(Level::High));
+
+    let v = Val::from(Event::Click { x: 3, y: 4 });
+    assert_eq!(v.get("kind").as_::<Option<String>>().unwrap(), "Click");
+    assert_eq!(v.get("y").as_::<i32>(), 4);
+    assert_eq!(v.try_as::<Event>(), Ok(Event::Click { x: 3, y: 4 }));
+    let v = Val::from(Event::Blur);
+    assert_eq!(v.try_as::<Event>(), Ok(Event::Blur));
+    let key = Val::object();
+    key.set("kind", "Key");
+    key.set("code", "Enter");
+    assert_eq!(
+        key.try_as::<Event>(),
+        Ok(Event::Key {
+            code: "Enter".into()
+        })
+    );
+}
+
+#[test]
+fn unknown_tags_fail_without_a_fallback() {
+    emlite::init();
+    let err = Val::from(4).try_as::<Level>().unwrap_err();
+    assert_eq!(err.expected, "Level");
+    assert_eq!(err.found_typeof, "number");
+    let click = Val::object();
+    click.set("kind", "Scroll");
+    assert_eq!(click.try_as::<Event>().unwrap_err().expected, "Event");
+    // A fallback variant catches them, as well as values of the wrong type
+    assert_eq!(Val::from(1).try_as::<Mode>(), Ok(Mode::Unknown));
+}
+
+#[test]
+fn wrappers_share_the_handle() {
+    emlite::init();
+    let obj = Val::object();
+    obj.set("n", 1);
+    let w = obj.as_::<Widget>();
+    assert_eq!(w.as_handle(), obj.as_handle());
+    // Deref reaches the wrapped Val's methods
+    assert_eq!(w.get("n").as_::<i32>(), 1);
+    let count = mockenv::ref_count(obj.as_handle()).unwrap();
+    let by_ref = Val::from(&w);
+    assert_eq!(mockenv::ref_count(obj.as_handle()), Some(count + 1));
+    drop(by_ref);
+    let back = Val::from(w);
+    assert!(back == obj);
+    assert_eq!(mockenv::ref_count(obj.as_handle()), Some(count));
+}
@@ -0,0 +1,55 @@
+use emlite::mockenv::{self, Value};
+use emlite::*;
+
+fn js_fn(f: impl Fn(&Value, &[Value]) -> Result<Value, Value> + 'static) -> Val {
+    Val::take_ownership(mockenv::to_handle(mockenv::new_function("f", f)))
+}
+
+#[test]
+fn thrown_errors_are_reported() {
+    emlite::init();
+    let err = Val::null().try_call("foo", &[]).unwrap_err();
+    assert!(err.is_error());
+    assert_eq!(err.name().as_deref(), Some("TypeError"));
+    assert!(
+        err.message()
+            .unwrap()
+            .contains("Cannot read properties of null")
+    );
+    assert!(err.stack().is_some());
+
+    let err = Val::global("BigInt").try_new(&[]).unwrap_err();
+    assert_eq!(err.to_string(), "TypeError: BigInt is not a constructor");
+}
+
+#[test]
+fn returned_errors_are_not_exceptions() {
+    emlite::init();
+    let make_err = js_fn(|_, _| Ok(mockenv::new_error("Error", "returned")));
+    let ret = make_err.try_invoke(&[]).unwrap();
+    assert!(ret.is_error());
+}
+
+#[test]
+fn non_error_throws_keep_their_value() {
+    emlite::init();
+    let thrower = js_fn(|_, _| Err(Value::String("boom".encode_utf16().collect())));
+    let err = thrower.try_invoke(&[]).unwrap_err();
+    assert!(!err.is_error());
+    assert_eq!(err.name(), None);
+    assert_eq!(err.value().as_::<Option<String>>().as_deref(), Some("boom"));
+    assert_eq!(err.to_string(), "boom");
+}
+
+#[test]
+fn successful_calls() {
+    emlite::init();
+    let arr = Val::global("Array").try_new(&argv![1, 2]).unwrap();
+    assert_eq!(
+        arr.try_call("join", &argv!["+"])
+            .unwrap()
+            .as_::<Option<String>>()
+            .unwrap(),
+        "1+2"
+    );
+}
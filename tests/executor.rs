@@ -0,0 +1,80 @@
+use emlite::*;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+#[test]
+fn spawned_tasks_run_on_microtasks() {
+    emlite::init();
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let l = log.clone();
+    let handle = spawn_local(async move {
+        l.borrow_mut().push("ran");
+        7
+    });
+    assert!(log.borrow().is_empty());
+    assert!(!handle.is_finished());
+    mockenv::run_microtasks();
+    assert_eq!(*log.borrow(), ["ran"]);
+    assert!(handle.is_finished());
+}
+
+#[test]
+fn join_handles_can_be_awaited() {
+    emlite::init();
+    let out = Rc::new(RefCell::new(None));
+    let o = out.clone();
+    spawn_local(async move {
+        let promise = Val::global("Promise").call("resolve", &argv![20]);
+        let inner = spawn_local(async move { promise.await.unwrap().as_::<i32>() + 1 });
+        *o.borrow_mut() = Some(inner.await * 2);
+    });
+    mockenv::run_microtasks();
+    assert_eq!(*out.borrow(), Some(42));
+}
+
+#[test]
+fn yield_now_interleaves_tasks() {
+    emlite::init();
+    let log = Rc::new(RefCell::new(Vec::new()));
+    for name in ["a", "b"] {
+        let l = log.clone();
+        spawn_local(async move {
+            l.borrow_mut().push(format!("{name}1"));
+            yield_now().await;
+            l.borrow_mut().push(format!("{name}2"));
+        });
+    }
+    mockenv::run_microtasks();
+    assert_eq!(*log.borrow(), ["a1", "b1", "a2", "b2"]);
+}
+
+#[test]
+fn cancelled_tasks_stop() {
+    emlite::init();
+    let log = Rc::new(RefCell::new(Vec::new()));
+    let l = log.clone();
+    let handle = spawn_local(async move {
+        l.borrow_mut().push("start");
+        yield_now().await;
+        l.borrow_mut().push("end");
+    });
+    assert!(mockenv::run_microtask());
+    handle.cancel();
+    mockenv::run_microtasks();
+    assert_eq!(*log.borrow(), ["start"]);
+}
+
+#[test]
+fn finished_tasks_free_their_handles() {
+    emlite::init();
+    let before = mockenv::live_handles();
+    let handle = spawn_local(async {
+        for _ in 0..10 {
+            yield_now().await;
+        }
+    });
+    mockenv::run_microtasks();
+    assert!(handle.is_finished());
+    drop(handle);
+    assert_eq!(mockenv::live_handles(), before);
+}
@@ -0,0 +1,43 @@
+use emlite::mockenv::{self, Value};
+use emlite::*;
+
+/// A function returning `this.base + x.length`
+fn method() -> Val {
+    let f = mockenv::new_function("method", |this, args| {
+        let this = Val::take_ownership(mockenv::to_handle(this.clone()));
+        let x = Val::take_ownership(mockenv::to_handle(args[0].clone()));
+        let n = this.get("base").as_::<f64>() + x.get("length").as_::<f64>();
+        Ok(Value::Number(n))
+    });
+    Val::take_ownership(mockenv::to_handle(f))
+}
+
+#[test]
+fn calls_convert_arguments_and_results() {
+    emlite::init();
+    let add = Closure::wrap(|a: i32, b: i32| a + b).forget();
+    let add: JsFunction<(i32, i32), i32> = add.dyn_into().unwrap();
+    assert_eq!(add.call(2, 3), 5);
+    assert_eq!(add.apply(&Val::undefined(), (4, 5)), 9);
+
+    let nothing: JsFunction<(), Val> = Closure::wrap(|| "x").forget().dyn_into().unwrap();
+    assert_eq!(nothing.call().as_::<String>(), "x");
+}
+
+#[test]
+fn this_can_be_passed_or_bound() {
+    emlite::init();
+    let obj = Val::object();
+    obj.set("base", 10);
+    let method: JsFunction<(String,), i32> = method().dyn_into().unwrap();
+    assert_eq!(method.call_with_this(&obj, "abc".into()), 13);
+    let bound = method.bind(&obj);
+    assert_eq!(bound.call("ab".into()), 12);
+}
+
+#[test]
+fn dyn_into_checks_for_functions() {
+    emlite::init();
+    let not_fn = Val::object().dyn_into::<JsFunction<(), ()>>();
+    assert!(not_fn.unwrap_err().get("call").is_undefined());
+}
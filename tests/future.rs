@@ -0,0 +1,122 @@
+use emlite::*;
+use std::cell::RefCell;
+use std::future::{Future, IntoFuture};
+use std::pin::pin;
+use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::task::{Context, Poll, Wake, Waker};
+
+struct CountingWaker(AtomicUsize);
+
+impl Wake for CountingWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+fn poll_once<F: Future>(fut: std::pin::Pin<&mut F>, waker: &Waker) -> Poll<F::Output> {
+    fut.poll(&mut Context::from_waker(waker))
+}
+
+#[test]
+fn awaits_fulfilled_promise() {
+    emlite::init();
+    let promise = Val::global("Promise").call("resolve", &argv![42]);
+    let mut fut = pin!(promise.into_future());
+    assert!(poll_once(fut.as_mut(), Waker::noop()).is_pending());
+    mockenv::run_microtasks();
+    match poll_once(fut.as_mut(), Waker::noop()) {
+        Poll::Ready(Ok(v)) => assert_eq!(v.as_::<i32>(), 42),
+        _ => panic!("expected fulfilled"),
+    }
+}
+
+#[test]
+fn rejection_is_err() {
+    emlite::init();
+    let reason = Val::global("Error").new(&argv!["nope"]);
+    let promise = Val::global("Promise").call("reject", std::slice::from_ref(&reason));
+    let mut fut = pin!(JsFuture::from(promise));
+    mockenv::run_microtasks();
+    match poll_once(fut.as_mut(), Waker::noop()) {
+        Poll::Ready(Err(e)) => assert!(e == reason),
+        _ => panic!("expected rejected"),
+    }
+}
+
+#[test]
+fn wakes_when_settled_later() {
+    emlite::init();
+    let resolver = Rc::new(RefCell::new(None));
+    let slot = resolver.clone();
+    let promise = Val::global("Promise").new(&[Val::make_fn(move |args| {
+        *slot.borrow_mut() = Some(args[0].clone());
+        Val::undefined()
+    })]);
+
+    let counter = Arc::new(CountingWaker(AtomicUsize::new(0)));
+    let waker = Waker::from(counter.clone());
+    let mut fut = pin!(JsFuture::new(&promise));
+    mockenv::run_microtasks();
+    assert!(poll_once(fut.as_mut(), &waker).is_pending());
+    assert_eq!(counter.0.load(Ordering::SeqCst), 0);
+
+    let resolve = resolver.borrow_mut().take().unwrap();
+    resolve.invoke(&argv!["later"]);
+    mockenv::run_microtasks();
+    assert_eq!(counter.0.load(Ordering::SeqCst), 1);
+    match poll_once(fut.as_mut(), &waker) {
+        Poll::Ready(Ok(v)) => assert_eq!(v.as_::<Option<String>>().unwrap(), "later"),
+        _ => panic!("expected fulfilled"),
+    }
+}
+
+#[test]
+fn plain_values_resolve_to_themselves() {
+    emlite::init();
+    let mut fut = pin!(Val::from("x").into_future());
+    mockenv::run_microtasks();
+    assert!(matches!(poll_once(fut.as_mut(), Waker::noop()), Poll::Ready(Ok(v)) if v.is_string()));
+}
+
+#[test]
+fn futures_become_promises() {
+    emlite::init();
+    let ok = future_to_promise(async { Ok(Val::from(5)) });
+    let reason = Val::object();
+    let err = future_to_promise({
+        let reason = reason.clone();
+        async move { Err(reason) }
+    });
+    assert!(ok.instanceof(Val::global("Promise")));
+
+    let results = Rc::new(RefCell::new(Vec::new()));
+    let r = results.clone();
+    spawn_local(async move {
+        let ok = ok.await;
+        let err = err.await;
+        r.borrow_mut().extend([ok, err]);
+    });
+    mockenv::run_microtasks();
+    let results = results.borrow();
+    assert_eq!(results[0].as_ref().unwrap().as_::<i32>(), 5);
+    assert!(*results[1].as_ref().unwrap_err() == reason);
+}
+
+#[test]
+fn callbacks_are_freed_once_settled() {
+    emlite::init();
+    let before = mockenv::live_handles();
+    let promise = Val::global("Promise").call("resolve", &argv![1]);
+    {
+        let mut fut = pin!(JsFuture::new(&promise));
+        mockenv::run_microtasks();
+        assert!(poll_once(fut.as_mut(), Waker::noop()).is_ready());
+    }
+    // A future dropped early still lets its callbacks run
+    drop(JsFuture::new(&promise));
+    mockenv::run_microtasks();
+    drop(promise);
+    assert_eq!(mockenv::live_handles(), before);
+}
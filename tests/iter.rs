@@ -0,0 +1,95 @@
+use emlite::*;
+use std::cell::Cell;
+use std::rc::Rc;
+
+#[test]
+fn enumerates_own_properties() {
+    emlite::init();
+    let obj = Val::object();
+    obj.set("a", 1);
+    obj.set("b", "two");
+    assert_eq!(obj.keys(), ["a", "b"]);
+    let values = obj.values();
+    assert_eq!(values[0].as_::<i32>(), 1);
+    assert_eq!(values[1].as_::<Option<String>>().unwrap(), "two");
+    let entries = obj.entries();
+    assert_eq!(entries[1].0, "b");
+    assert_eq!(entries[0].1.as_::<i32>(), 1);
+
+    assert_eq!(Val::from_slice(&[5, 6]).keys(), ["0", "1"]);
+    assert!(Val::from(3).keys().is_empty());
+}
+
+#[test]
+fn iterates_builtin_iterables() {
+    emlite::init();
+    let arr = Val::from_slice(&[1, 2, 3]);
+    let sum: i32 = arr.iter().map(|v| v.as_::<i32>()).sum();
+    assert_eq!(sum, 6);
+
+    let map = Val::global("Map").new(&[]);
+    map.call("set", &argv!["k", 9]);
+    let entries: Vec<Val> = map.iter().collect();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].at(0).as_::<Option<String>>().unwrap(), "k");
+    assert_eq!(entries[0].at(1).as_::<i32>(), 9);
+
+    let chars: Vec<String> = Val::from("hi").iter().map(|v| v.as_()).collect();
+    assert_eq!(chars, ["h", "i"]);
+
+    let mut it = Val::object().iter();
+    assert!(it.next().is_none());
+    assert_eq!(it.exception().unwrap().name().unwrap(), "TypeError");
+}
+
+/// Builds an iterable yielding 0..n that counts `return()` calls
+fn counter(n: i32, returns: Rc<Cell<u32>>) -> Val {
+    let iterable = Val::object();
+    let iter = Val::object();
+    let i = Rc::new(Cell::new(0));
+    iter.set(
+        "next",
+        Val::make_fn(move |_| {
+            let result = Val::object();
+            let cur = i.get();
+            i.set(cur + 1);
+            result.set("done", cur >= n);
+            result.set("value", cur);
+            result
+        }),
+    );
+    iter.set(
+        "return",
+        Val::make_fn(move |_| {
+            returns.set(returns.get() + 1);
+            let result = Val::object();
+            result.set("done", true);
+            result
+        }),
+    );
+    iterable.set(
+        Val::global("Symbol").get("iterator"),
+        Val::make_fn(move |_| iter.clone()),
+    );
+    iterable
+}
+
+#[test]
+fn early_drop_closes_the_iterator() {
+    emlite::init();
+    let returns = Rc::new(Cell::new(0));
+    let iterable = counter(5, returns.clone());
+
+    let first: Vec<i32> = iterable.iter().take(2).map(|v| v.as_()).collect();
+    assert_eq!(first, [0, 1]);
+    assert_eq!(returns.get(), 1);
+
+    let rest: Vec<i32> = iterable.iter().map(|v| v.as_()).collect();
+    assert_eq!(rest, [2, 3, 4]);
+    assert_eq!(returns.get(), 1);
+
+    // Array.from drives custom iterables too
+    let fresh = counter(2, returns.clone());
+    let arr = Val::global("Array").call("from", &[fresh]);
+    assert_eq!(arr.to_vec::<i32>(), [0, 1]);
+}
@@ -0,0 +1,73 @@
+use emlite::json::Stringify;
+use emlite::*;
+
+#[test]
+fn stringifies_with_options() {
+    emlite::init();
+    let v = Val::object();
+    v.set("name", "a\"b");
+    v.set("list", Val::from_slice(&[1, 2]));
+    v.set("skip", Val::undefined());
+    assert_eq!(
+        v.to_json_string().unwrap(),
+        r#"{"name":"a\"b","list":[1,2]}"#
+    );
+    assert_eq!(
+        Stringify::new().pretty().stringify(&v).unwrap(),
+        "{\n  \"name\": \"a\\\"b\",\n  \"list\": [\n    1,\n    2\n  ]\n}"
+    );
+
+    let text = Stringify::new()
+        .replacer(|key, value| match key {
+            "name" => Val::undefined(),
+            "1" => Val::from(20),
+            _ => value,
+        })
+        .stringify(&v)
+        .unwrap();
+    assert_eq!(text, r#"{"list":[1,20]}"#);
+}
+
+#[test]
+fn stringify_failures_are_errors() {
+    emlite::init();
+    let err = Val::undefined().to_json_string().unwrap_err();
+    assert_eq!(err.name().unwrap(), "TypeError");
+    let err = Val::from(1u64 << 60).to_json_string().unwrap_err();
+    assert_eq!(err.name().unwrap(), "TypeError");
+    let cyclic = Val::object();
+    cyclic.set("me", &cyclic);
+    assert!(cyclic.to_json_string().is_err());
+}
+
+#[test]
+fn parses_with_reviver() {
+    emlite::init();
+    let v = Val::parse_json(r#" {"a": [1, 2.5e1, "xA"], "b": null} "#).unwrap();
+    assert_eq!(v.get("a").at(1).as_::<f64>(), 25.0);
+    assert_eq!(v.get("a").at(2).as_::<Option<String>>().unwrap(), "xA");
+    assert!(v.get("b").is_null());
+
+    let err = Val::parse_json("{\"a\":}").unwrap_err();
+    assert_eq!(err.name().unwrap(), "SyntaxError");
+    assert!(Val::parse_json("").is_err());
+
+    let v = Val::parse_json_with(r#"{"n": 2, "drop": true}"#, |key, value| match key {
+        "n" => Val::from(value.as_::<i32>() * 10),
+        "drop" => Val::undefined(),
+        _ => value,
+    })
+    .unwrap();
+    assert_eq!(v.get("n").as_::<i32>(), 20);
+    assert!(!v.has("drop"));
+}
+
+#[test]
+fn converts_to_json_trees() {
+    emlite::init();
+    let tree = serde_json::json!({"a": [1, true, null], "b": {"c": "d"}});
+    let v = Val::from(&tree);
+    assert_eq!(v.get("b").get("c").as_::<Option<String>>().unwrap(), "d");
+    v.set("f", Val::make_fn(|_| Val::undefined()));
+    assert_eq!(v.to_json_value().unwrap(), tree);
+}
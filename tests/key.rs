@@ -0,0 +1,99 @@
+use emlite::*;
+
+#[test]
+fn string_keys_reuse_their_handle() {
+    emlite::init();
+    let obj = Val::object();
+    obj.set("x", 1);
+    let before = mockenv::live_handles();
+    for i in 0..10 {
+        obj.set("x", i);
+        assert_eq!(obj.get("x").as_::<i32>(), i);
+        assert!(obj.has(String::from("x")));
+    }
+    assert_eq!(mockenv::live_handles(), before);
+
+    // Past the cache's capacity, older names are released
+    for i in 0..200 {
+        obj.set(format!("k{i}"), i);
+    }
+    assert_eq!(obj.get("k3").as_::<i32>(), 3);
+    let name = String::from("k199");
+    assert_eq!(obj.get(&name).as_::<i32>(), 199);
+    drop(obj);
+    assert_eq!(mockenv::live_handles(), before - 1);
+}
+
+#[test]
+fn interned_names_are_pinned() {
+    emlite::init();
+    let a = intern("textContent");
+    let b = intern("textContent");
+    assert!(core::ptr::eq(a, b));
+    assert_eq!(a.as_::<String>(), "textContent");
+
+    let obj = Val::object();
+    let refs = mockenv::ref_count(a.as_handle());
+    obj.set(a, "hi");
+    assert_eq!(obj.get(a).as_::<String>(), "hi");
+    assert!(obj.has(a));
+    assert_eq!(mockenv::ref_count(a.as_handle()), refs);
+}
+
+#[test]
+fn other_keys_still_work() {
+    emlite::init();
+    let arr = Val::from(vec![10, 20]);
+    assert_eq!(arr.get(1).as_::<i32>(), 20);
+    assert_eq!(arr.get(1usize).as_::<i32>(), 20);
+    let sym = Val::symbol("s");
+    let obj = Val::object();
+    obj.set(&sym, true);
+    assert!(obj.get(sym).as_::<bool>());
+    assert!(arr.has(WellKnownSymbol::Iterator));
+    assert!(obj.has(JsString::from("toString")));
+}
+
+#[test]
+fn indices_and_explicit_keys() {
+    emlite::init();
+    let arr = Val::array();
+    arr.set(0u32, "a");
+    arr.set(PropKey::Index(1), "b");
+    assert_eq!(arr.get("length").as_::<u32>(), 2);
+    assert_eq!(arr.to_vec::<String>(), ["a", "b"]);
+    assert_eq!(arr.get(PropKey::Str("1")).as_::<String>(), "b");
+    assert!(arr.get(5).is_undefined());
+
+    let obj = Val::object();
+    obj.set(-1, "neg");
+    assert_eq!(obj.get("-1").as_::<String>(), "neg");
+    let sym = Val::symbol("s");
+    obj.set(PropKey::Val(&sym), 1);
+    assert!(obj.has(PropKey::from(&sym)));
+    assert!(arr.has(PropKey::Symbol(WellKnownSymbol::Iterator)));
+}
+
+#[test]
+fn paths_read_through_missing_links() {
+    emlite::init();
+    let v = Val::parse_json(r#"{"a": {"b": [{"c": 7}], "x-id": "q"}, "n": null}"#).unwrap();
+    assert_eq!(v.path("a.b[0].c").as_::<i32>(), 7);
+    assert_eq!(v.path("a['x-id']").as_::<String>(), "q");
+    assert_eq!(v.path(r#"a["b"][0]"#).get("c").as_::<i32>(), 7);
+    assert_eq!(v.path("a.b.length").as_::<u32>(), 1);
+    assert!(v.path("a.missing.c").is_undefined());
+    assert!(v.path("n.c.d").is_undefined());
+    assert!(v.path("a.b[3].c").is_undefined());
+    assert!(v.path("") == v);
+}
+
+#[test]
+fn malformed_paths_read_as_undefined() {
+    emlite::init();
+    let v = Val::parse_json(r#"{"a": {"b": [{"c": 7}]}}"#).unwrap();
+    for path in ["a..b", "a.", ".a", "a[0", "a.b[]", "a.b[0]c", "a.b[0].c["] {
+        assert!(v.path(path).is_undefined(), "{path}");
+    }
+    assert_eq!(v.path("a.b[0]").get("c").as_::<i32>(), 7);
+}
@@ -0,0 +1,207 @@
+use emlite::*;
+
+#[test]
+fn objects_and_properties() {
+    emlite::init();
+    let obj = Val::object();
+    obj.set("x", 5);
+    obj.set("name", "emlite");
+    assert_eq!(obj.get("x").as_::<i32>(), 5);
+    assert_eq!(
+        obj.get("name").as_::<Option<String>>().as_deref(),
+        Some("emlite")
+    );
+    assert!(obj.has("x"));
+    assert!(obj.has_own_property("name"));
+    assert!(!obj.has_own_property("toString"));
+    assert!(obj.get("missing").is_undefined());
+    assert_eq!(obj.type_of(), "object");
+}
+
+#[test]
+fn arrays() {
+    emlite::init();
+    let arr = Val::from_slice(&[1, 2, 3]);
+    assert_eq!(arr.get("length").as_::<usize>(), 3);
+    assert_eq!(arr.to_vec::<i32>(), [1, 2, 3]);
+    assert_eq!(
+        arr.call("join", &argv!["-"])
+            .as_::<Option<String>>()
+            .unwrap(),
+        "1-2-3"
+    );
+    assert!(Val::global("Array").call("isArray", &[arr]).as_::<bool>());
+}
+
+#[test]
+fn numbers_and_bigints() {
+    emlite::init();
+    assert_eq!(Val::from(2.5f64).as_::<f64>(), 2.5);
+    assert_eq!(Val::from(u64::MAX).as_::<u64>(), u64::MAX);
+    assert_eq!(Val::from(i64::MIN).as_::<i64>(), i64::MIN);
+    assert_eq!(Val::from(7u64).type_of(), "bigint");
+    assert!(Val::from(1) < Val::from(2));
+    assert!(Val::from("a") == Val::from("a"));
+}
+
+#[test]
+fn typeof_covers_every_type() {
+    emlite::init();
+    let vals = [
+        Val::undefined(),
+        Val::null(),
+        Val::from(true),
+        Val::from(1),
+        Val::from(1u64),
+        Val::from("s"),
+        Val::global("Array"),
+        Val::symbol("s"),
+    ];
+    let names: Vec<_> = vals.iter().map(Val::type_of).collect();
+    assert_eq!(
+        names,
+        [
+            "undefined",
+            "object",
+            "boolean",
+            "number",
+            "bigint",
+            "string",
+            "function",
+            "symbol"
+        ]
+    );
+    let h = vals[5].as_handle();
+    assert_eq!(unsafe { mockenv::emlite_val_typeof_tag(h) }, 5);
+}
+
+#[test]
+fn bigints_only_convert_to_numbers_explicitly() {
+    emlite::init();
+    let big = Val::from(5u64);
+    let args = std::slice::from_ref(&big);
+    // ToNumber throws, as with `"abc".charCodeAt(5n)` in JS
+    let err = Val::from("abc").try_call("charCodeAt", args).unwrap_err();
+    assert_eq!(err.name().as_deref(), Some("TypeError"));
+    assert!(Val::array().try_call("at", args).is_err());
+    // while Number() and the host's getters take BigInts
+    assert_eq!(Val::global("Number").invoke(args).as_::<f64>(), 5.0);
+    assert_eq!(big.as_::<f64>(), 5.0);
+    assert_eq!(big.as_::<i32>(), 5);
+    assert!(big < Val::from(6));
+}
+
+#[test]
+fn utf16_strings_keep_lone_surrogates() {
+    emlite::init();
+    let units = [0x68, 0xD800, 0x69];
+    let v = Val::from_utf16(&units);
+    assert!(v.is_string());
+    assert_eq!(v.to_utf16().unwrap(), units);
+    assert_eq!(v.get("length").as_::<u32>(), 3);
+}
+
+#[test]
+fn strings_are_read_with_their_length() {
+    emlite::init();
+    let v = Val::from("a\0b\0");
+    assert_eq!(v.as_::<String>(), "a\0b\0");
+    assert_eq!(v.to_utf16().unwrap(), [0x61, 0, 0x62, 0]);
+
+    let mut buf = String::with_capacity(64);
+    let ptr = buf.as_ptr();
+    for s in ["first", "second", ""] {
+        assert!(Val::from(s).read_string_into(&mut buf));
+        assert_eq!(buf, s);
+        assert_eq!(buf.as_ptr(), ptr);
+    }
+    // A short buffer grows to fit
+    let long = "x".repeat(100);
+    assert!(Val::from(long.as_str()).read_string_into(&mut buf));
+    assert_eq!(buf, long);
+    assert!(!Val::from(5).read_string_into(&mut buf));
+    assert!(buf.is_empty());
+
+    let mut units = Vec::new();
+    assert!(Val::from("héllo").read_utf16_into(&mut units));
+    assert_eq!(String::from_utf16(&units).unwrap(), "héllo");
+    assert!(!Val::null().read_utf16_into(&mut units));
+    assert!(units.is_empty());
+}
+
+#[test]
+fn closures_are_callable() {
+    emlite::init();
+    let add = Val::make_fn(|args| Val::from(args[0].as_::<i32>() + args[1].as_::<i32>()));
+    assert!(add.is_function());
+    assert_eq!(add.invoke(&argv![2, 3]).as_::<i32>(), 5);
+
+    let obj = Val::object();
+    obj.set(
+        "greet",
+        Val::make_fn(|args| Val::from(format!("hi {}", args[0].as_::<Option<String>>().unwrap()))),
+    );
+    let ret = obj.call("greet", &argv!["bob"]);
+    assert_eq!(ret.as_::<Option<String>>().unwrap(), "hi bob");
+}
+
+#[test]
+fn arguments_are_passed_in_place() {
+    emlite::init();
+    let sum = Val::make_fn(|args| Val::from(args.iter().map(|a| a.as_::<i32>()).sum::<i32>()));
+    let obj = Val::object();
+    obj.set("sum", &sum);
+    let args = argv![3, 9, 4];
+    let before = mockenv::live_handles();
+    let refs = mockenv::ref_count(args[1].as_handle());
+    assert_eq!(sum.invoke(&args).as_::<i32>(), 16);
+    assert_eq!(sum.try_invoke(&args).unwrap().as_::<i32>(), 16);
+    assert_eq!(obj.call("sum", &args).as_::<i32>(), 16);
+    assert_eq!(obj.try_call("sum", &args).unwrap().as_::<i32>(), 16);
+    assert_eq!(
+        Val::global("Array").new(&args).get("length").as_::<u32>(),
+        3
+    );
+    assert_eq!(sum.invoke(&[]).as_::<i32>(), 0);
+    assert_eq!(mockenv::ref_count(args[1].as_handle()), refs);
+    assert_eq!(mockenv::live_handles(), before);
+}
+
+#[test]
+fn errors() {
+    emlite::init();
+    let err = Val::global("TypeError").new(&argv!["bad"]);
+    assert!(err.is_error());
+    assert_eq!(err.get("message").as_::<Option<String>>().unwrap(), "bad");
+    assert!(!Val::object().is_error());
+
+    let ret = Val::null().call("foo", &[]);
+    assert!(ret.is_error());
+    assert!(eval!("1 + 1").is_error());
+}
+
+#[test]
+fn console_output_is_captured() {
+    emlite::init();
+    Console::get().log(&argv!["hello", 1, true]);
+    assert_eq!(mockenv::take_console_output(), ["hello 1 true"]);
+}
+
+#[test]
+fn handles_are_released() {
+    emlite::init();
+    let before = mockenv::live_handles();
+    {
+        let obj = Val::object();
+        let copy = obj.clone();
+        assert_eq!(mockenv::ref_count(obj.as_handle()), Some(2));
+        drop(copy);
+        assert_eq!(mockenv::ref_count(obj.as_handle()), Some(1));
+        obj.set("k", Val::array());
+    }
+    assert_eq!(mockenv::live_handles(), before);
+    assert_eq!(
+        mockenv::ref_count(Val::global_this().as_handle()),
+        Some(u32::MAX)
+    );
+}
@@ -0,0 +1,63 @@
+use emlite::*;
+
+#[derive(FromVal, IntoVal)]
+#[emlite(transparent)]
+struct Wrapper {
+    val: Val,
+}
+
+#[test]
+fn borrowed_conversions_keep_the_callers_reference() {
+    emlite::init();
+    let obj = Val::object();
+    let err = Val::global("Error").new(&["boom".into()]);
+    let before = mockenv::live_handles();
+    let copy = obj.as_::<Val>();
+    assert_eq!(mockenv::ref_count(obj.as_handle()), Some(2));
+    drop(copy);
+    assert_eq!(mockenv::ref_count(obj.as_handle()), Some(1));
+    for v in [&obj, &err] {
+        let r = v.as_::<Result<Val, Val>>();
+        assert_eq!(r.as_handle(), v.as_handle());
+        assert_eq!(mockenv::ref_count(v.as_handle()), Some(2));
+        drop(r);
+        assert_eq!(mockenv::ref_count(v.as_handle()), Some(1));
+    }
+    let _ = obj.as_::<bool>();
+    let _ = obj.as_::<Option<String>>();
+    let _ = err.as_::<Result<i32, Val>>();
+    assert_eq!(mockenv::live_handles(), before);
+}
+
+#[test]
+fn take_ownership_consumes_the_reference() {
+    emlite::init();
+    let before = mockenv::live_handles();
+    let h = Val::from("text").into_handle();
+    assert_eq!(String::take_ownership(h), "text");
+    let h = Val::from(7).into_handle();
+    assert_eq!(<Option<i32>>::take_ownership(h), Some(7));
+    let h = Val::global("Error").new(&["boom".into()]).into_handle();
+    assert!(<Result<f64, Val>>::take_ownership(h).is_err());
+    assert_eq!(mockenv::live_handles(), before);
+
+    let obj = Val::object();
+    let h = obj.clone().into_handle();
+    let w = Wrapper::take_ownership(h);
+    assert_eq!(w.as_handle(), obj.as_handle());
+    assert_eq!(mockenv::ref_count(obj.as_handle()), Some(2));
+    drop(w);
+    assert_eq!(mockenv::ref_count(obj.as_handle()), Some(1));
+}
+
+#[test]
+fn absent_values_map_to_undefined() {
+    emlite::init();
+    let none: Option<Val> = None;
+    assert_eq!(none.as_handle(), Val::undefined().as_handle());
+    assert_eq!(
+        Console::get().as_handle(),
+        Val::global("console").as_handle()
+    );
+    assert!(5.into_val() == Val::from(5));
+}
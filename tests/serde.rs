@@ -0,0 +1,112 @@
+use emlite::serde_val::Serializer;
+use emlite::*;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+enum Shape {
+    Empty,
+    Circle(f64),
+    Rect { w: u32, h: u32 },
+    Pair(i8, i8),
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+struct Scene {
+    name: String,
+    shapes: Vec<Shape>,
+    tags: BTreeMap<String, u32>,
+    parent: Option<Box<Scene>>,
+    #[serde(with = "serde_bytes")]
+    blob: Vec<u8>,
+}
+
+fn scene() -> Scene {
+    Scene {
+        name: "root".into(),
+        shapes: vec![
+            Shape::Empty,
+            Shape::Circle(1.5),
+            Shape::Rect { w: 2, h: 3 },
+            Shape::Pair(-1, 1),
+        ],
+        tags: BTreeMap::from([("a".into(), 1), ("b".into(), 2)]),
+        parent: None,
+        blob: vec![0, 127, 255],
+    }
+}
+
+#[test]
+fn round_trips_through_js_shapes() {
+    emlite::init();
+    let v = to_val(&scene()).unwrap();
+    assert_eq!(v.get("name").as_::<Option<String>>().unwrap(), "root");
+    let shapes = v.get("shapes");
+    assert!(
+        Val::global("Array")
+            .call("isArray", std::slice::from_ref(&shapes))
+            .as_::<bool>()
+    );
+    assert_eq!(shapes.at(0).as_::<Option<String>>().unwrap(), "Empty");
+    assert_eq!(shapes.at(1).get("Circle").as_::<f64>(), 1.5);
+    assert_eq!(shapes.at(2).get("Rect").get("h").as_::<u32>(), 3);
+    assert_eq!(v.get("tags").get("b").as_::<u32>(), 2);
+    assert!(v.get("parent").is_undefined());
+    assert!(v.get("blob").instanceof(Val::global("Uint8Array")));
+
+    assert_eq!(from_val::<Scene>(&v).unwrap(), scene());
+}
+
+#[test]
+fn maps_and_large_numbers_are_configurable() {
+    emlite::init();
+    let ser = Serializer::new()
+        .maps_as_objects(false)
+        .large_numbers_as_bigints(true);
+    let map = BTreeMap::from([(1u8, u64::MAX)]);
+    let v = serde::Serialize::serialize(&map, &ser).unwrap();
+    assert!(v.instanceof(Val::global("Map")));
+    let entry = v.call("get", &argv![1]);
+    assert_eq!(entry.type_of(), "bigint");
+    assert_eq!(from_val::<BTreeMap<u8, u64>>(&v).unwrap(), map);
+
+    let err = to_val(&map).unwrap_err();
+    assert!(err.to_string().contains("18446744073709551615"));
+    assert_eq!(err.path(), "1");
+
+    // Numeric keys survive plain objects too
+    let small = BTreeMap::from([(7u16, 9u64)]);
+    let v = to_val(&small).unwrap();
+    assert_eq!(v.get("7").as_::<u32>(), 9);
+    assert_eq!(from_val::<BTreeMap<u16, u64>>(&v).unwrap(), small);
+}
+
+#[test]
+fn errors_report_the_property_path() {
+    emlite::init();
+    let v = to_val(&scene()).unwrap();
+    v.get("shapes").at(2).get("Rect").set("w", "wide");
+    let err = from_val::<Scene>(&v).unwrap_err();
+    assert_eq!(err.path(), "shapes[2].Rect.w");
+    assert!(err.to_string().ends_with(" at shapes[2].Rect.w"), "{err}");
+
+    let bad = Val::object();
+    bad.set("Circle", Val::null());
+    bad.set("Empty", Val::null());
+    assert!(from_val::<Shape>(&bad).is_err());
+}
+
+#[test]
+fn js_values_are_read_leniently() {
+    emlite::init();
+    assert_eq!(from_val::<Option<i32>>(&Val::null()).unwrap(), None);
+    assert_eq!(from_val::<u64>(&Val::from(5u64)).unwrap(), 5);
+    assert_eq!(from_val::<f32>(&Val::from(3)).unwrap(), 3.0);
+    assert!(from_val::<u8>(&Val::from(300)).is_err());
+    assert!(from_val::<i32>(&Val::from(1.5)).is_err());
+
+    let bytes = Val::global("Uint8Array").new(&[Val::from_slice(&[1, 2, 3])]);
+    assert_eq!(from_val::<Vec<u8>>(&bytes).unwrap(), [1, 2, 3]);
+    let floats = Val::global("Float32Array").new(&[Val::from_slice(&[0.5, 2.0])]);
+    assert_eq!(from_val::<Vec<f32>>(&floats).unwrap(), [0.5, 2.0]);
+}
@@ -0,0 +1,55 @@
+use emlite::*;
+
+fn define_add() {
+    mockenv::define_function_source(&["a", "b"], "return a + b", |_, args| {
+        let n = |i: usize| match args.get(i) {
+            Some(mockenv::Value::Number(n)) => *n,
+            _ => f64::NAN,
+        };
+        Ok(mockenv::Value::Number(n(0) + n(1)))
+    });
+}
+
+fn add() -> &'static JsSnippet {
+    js!(|a, b| "return a + b")
+}
+
+#[test]
+fn snippets_compile_once_and_take_arguments() {
+    emlite::init();
+    define_add();
+    let f = add().function();
+    for i in 0..3 {
+        assert_eq!(add().call::<i32>(&argv![i, 10]), i + 10);
+    }
+    assert!(add().function() == f);
+    assert_eq!(add().call::<f64>(&argv![0.5, 0.25]), 0.75);
+
+    // A fresh host compiles it again
+    emlite::init();
+    assert_eq!(add().call::<i32>(&argv![1, 2]), 3);
+}
+
+#[test]
+fn snippets_report_exceptions() {
+    emlite::init();
+    mockenv::define_function_source(&[], "throw new RangeError('no')", |_, _| {
+        Err(mockenv::new_error("RangeError", "no"))
+    });
+    let err = js!(|| "throw new RangeError('no')")
+        .try_call::<Val>(&[])
+        .unwrap_err();
+    assert_eq!(err.name().unwrap(), "RangeError");
+}
+
+#[test]
+fn syntax_errors_are_reported_and_not_cached() {
+    emlite::init();
+    let snippet = js!(|a| "return a *");
+    let err = snippet.try_call::<Val>(&argv![1]).unwrap_err();
+    assert_eq!(err.name().unwrap(), "SyntaxError");
+
+    // Compiling is retried on the next call
+    mockenv::define_function_source(&["a"], "return a *", |_, _| Ok(mockenv::Value::Number(0.0)));
+    assert_eq!(snippet.try_call::<i32>(&argv![1]).unwrap(), 0);
+}
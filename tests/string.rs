@@ -0,0 +1,82 @@
+use emlite::*;
+
+#[test]
+fn strings_are_queried_on_the_js_side() {
+    emlite::init();
+    let s = JsString::from("hello world");
+    assert_eq!(s.len_utf16(), 11);
+    assert_eq!(s.char_at(4), Some(b'o' as u16));
+    assert_eq!(s.char_at(11), None);
+    assert!(s.slice(6, 100).eq_str("world"));
+    assert_eq!(s.index_of("o"), Some(4));
+    assert_eq!(s.index_of("xyz"), None);
+    assert!(s.starts_with("hell"));
+    assert!(!s.starts_with(JsString::from("world")));
+    assert_eq!(s.slice(0, 5).concat("!"), "hello!");
+    assert!(JsString::from("").is_empty());
+
+    let (a, b) = (JsString::from("a"), JsString::from("b"));
+    assert!(a < b);
+    assert_eq!(JsString::from("a").concat("b"), JsString::from("ab"));
+    assert_eq!(s.to_string(), "hello world");
+}
+
+#[test]
+fn lone_surrogates_survive() {
+    emlite::init();
+    let units = [0x61, 0xD800, 0xD83D, 0xDE00, 0xDC00];
+    let s = JsString::from(&units[..]);
+    assert_eq!(s.len_utf16(), 5);
+    assert_eq!(s.char_at(1), Some(0xD800));
+    assert!(s.try_to_string().is_err());
+    assert_eq!(s.to_string_lossy(), "a\u{FFFD}😀\u{FFFD}");
+
+    let wtf8 = s.to_wtf8();
+    assert_eq!(wtf8, b"a\xED\xA0\x80\xF0\x9F\x98\x80\xED\xB0\x80");
+    let back = JsString::from_wtf8(&wtf8).unwrap();
+    assert_eq!(back.to_utf16(), units);
+    assert_eq!(back, s);
+
+    assert_eq!(JsString::from("é😀").to_wtf8(), "é😀".as_bytes());
+    // A surrogate pair must be encoded as one code point
+    assert!(JsString::from_wtf8(b"\xED\xA0\xBD\xED\xB8\x80").is_none());
+    assert!(JsString::from_wtf8(b"\xC0\x80").is_none());
+}
+
+#[test]
+fn only_string_primitives_cast() {
+    emlite::init();
+    assert!(Val::from("x").dyn_into::<JsString>().is_ok());
+    assert!(Val::from(1).dyn_into::<JsString>().is_err());
+    let err = Val::object().try_as::<JsString>().unwrap_err();
+    assert_eq!(err.expected, "string");
+}
+
+#[test]
+fn indices_are_passed_as_numbers() {
+    use emlite::mockenv::{Value, call_value, new_error, new_function, to_handle, value_of};
+    emlite::init();
+    // Wraps the methods to reject BigInt indices, as an engine's ToNumber does
+    let proto = Val::global("String").get("prototype");
+    for name in ["charCodeAt", "slice"] {
+        let original = value_of(proto.get(name).as_handle());
+        let checked = new_function(name, move |this, args| {
+            if args.iter().any(|a| matches!(a, Value::BigInt(_))) {
+                return Err(new_error(
+                    "TypeError",
+                    "Cannot convert a BigInt value to a number",
+                ));
+            }
+            call_value(&original, this, args)
+        });
+        proto.set(name, Val::take_ownership(to_handle(checked)));
+    }
+    let s = JsString::from("abc");
+    assert!(
+        Val::from("abc")
+            .try_call("charCodeAt", &[Val::from(1u64)])
+            .is_err()
+    );
+    assert_eq!(s.char_at(1), Some(b'b' as u16));
+    assert!(s.slice(1, 5).eq_str("bc"));
+}
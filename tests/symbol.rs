@@ -0,0 +1,44 @@
+use emlite::*;
+
+#[test]
+fn symbols_are_unique_unless_registered() {
+    emlite::init();
+    let a = Val::symbol("tag");
+    assert!(a.is_symbol());
+    assert_eq!(a.type_of(), "symbol");
+    assert!(!Val::from("tag").is_symbol());
+    assert!(a != Val::symbol("tag"));
+    assert_eq!(a.get("description").as_::<Option<String>>().unwrap(), "tag");
+
+    assert!(Val::symbol_for("app.key") == Val::symbol_for("app.key"));
+    assert_eq!(
+        Val::global("Symbol")
+            .call("keyFor", &[Val::symbol_for("app.key")])
+            .as_::<Option<String>>()
+            .unwrap(),
+        "app.key"
+    );
+}
+
+#[test]
+fn symbols_key_properties() {
+    emlite::init();
+    let key = Val::symbol("hidden");
+    let obj = Val::object();
+    obj.set(&key, 42);
+    obj.set("visible", 1);
+    assert!(obj.has(&key));
+    assert!(!obj.has(Val::symbol("hidden")));
+    assert_eq!(obj.get(&key).as_::<i32>(), 42);
+    // Symbol-keyed properties aren't enumerated
+    assert_eq!(obj.keys(), ["visible"]);
+
+    let tag = Val::from(WellKnownSymbol::ToStringTag);
+    assert!(tag.is_symbol());
+    assert!(tag == Val::global("Symbol").get("toStringTag"));
+    assert!(
+        Val::from_slice(&[1])
+            .get(WellKnownSymbol::Iterator)
+            .is_function()
+    );
+}
@@ -0,0 +1,56 @@
+use emlite::*;
+
+#[test]
+fn slices_round_trip_through_typed_arrays() {
+    emlite::init();
+    let bytes = Val::from_typed_slice(&[1u8, 2, 255]);
+    assert!(bytes.instanceof(Val::global("Uint8Array")));
+    assert_eq!(bytes.to_typed_vec::<u8>(), [1, 2, 255]);
+
+    let floats = Val::from_typed_slice(&[0.5f32, -2.0, 1e3]);
+    assert!(floats.instanceof(Val::global("Float32Array")));
+    assert_eq!(floats.at(1).as_::<f32>(), -2.0);
+    assert_eq!(floats.to_typed_vec::<f32>(), [0.5, -2.0, 1e3]);
+    // Elements convert like TypedArray.prototype.set
+    assert_eq!(floats.to_typed_vec::<i32>(), [0, -2, 1000]);
+
+    let big = Val::from_typed_slice(&[i64::MIN, 7]);
+    assert!(big.instanceof(Val::global("BigInt64Array")));
+    assert_eq!(big.at(0).type_of(), "bigint");
+    assert_eq!(big.to_typed_vec::<i64>(), [i64::MIN, 7]);
+
+    let arr = Val::from_slice(&[3, 4, 5]);
+    assert_eq!(arr.to_typed_vec::<u16>(), [3, 4, 5]);
+}
+
+#[test]
+fn array_buffers_are_reinterpreted() {
+    emlite::init();
+    let words = Val::from_typed_slice(&[0x0102_0304u32, 0x0506_0708]);
+    let buffer = words.get("buffer");
+    assert_eq!(buffer.typed_len::<u8>(), 8);
+    assert_eq!(buffer.typed_len::<u32>(), 2);
+    assert_eq!(buffer.to_typed_vec::<u32>(), [0x0102_0304, 0x0506_0708]);
+    assert_eq!(
+        buffer.to_typed_vec::<u8>(),
+        [0x0102_0304u32.to_ne_bytes(), 0x0506_0708u32.to_ne_bytes()].concat()
+    );
+
+    let mut short = [0u32; 1];
+    assert_eq!(words.copy_to_typed_slice(&mut short), 1);
+    assert_eq!(short, [0x0102_0304]);
+}
+
+#[test]
+fn views_alias_rust_memory() {
+    emlite::init();
+    let mut data = vec![1.0f64, 2.0, 3.0];
+    {
+        let view = unsafe { Val::view_typed_slice(&data) };
+        assert_eq!(view.get("length").as_::<u32>(), 3);
+        assert_eq!(view.at(2).as_::<f64>(), 3.0);
+    }
+    data[2] = 9.0;
+    let view = unsafe { Val::view_typed_slice(&data) };
+    assert_eq!(view.to_typed_vec::<f64>(), [1.0, 2.0, 9.0]);
+}